@@ -1,70 +1,46 @@
-use anyhow::Context;
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    str::FromStr,
+};
+
+use anyhow::{Context, bail};
 use aoc_runner_derive::{aoc, aoc_generator};
 
+const DEFAULT_CONFIG: &str = "Player: 50 hit points, 500 mana
+Spell Magic Missile: costs 53, deals 4 damage
+Spell Drain: costs 73, deals 2 damage, heals 2 hit points
+Spell Shield: costs 113, armor 7 for 6 turns
+Spell Poison: costs 173, damage 3 for 6 turns
+Spell Recharge: costs 229, mana 101 for 5 turns
+Difficulty normal: drain 0
+Difficulty hard: drain 1";
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum EffectBenefit {
     Armor(u32),
     Damage(u32),
     Mana(u32),
+    Heal(u32),
 }
 
 #[derive(Clone, Copy, Debug)]
 struct Effect {
     benefit: EffectBenefit,
     duration: u32,
+    stacks: bool,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 struct Spell {
+    name: String,
     cost: u32,
     damage: u32,
     heal: u32,
     effect: Option<Effect>,
 }
 
-const SPELLS: [Spell; 5] = [
-    Spell {
-        cost: 53,
-        damage: 4,
-        heal: 0,
-        effect: None,
-    },
-    Spell {
-        cost: 73,
-        damage: 2,
-        heal: 2,
-        effect: None,
-    },
-    Spell {
-        cost: 113,
-        damage: 0,
-        heal: 0,
-        effect: Some(Effect {
-            benefit: EffectBenefit::Armor(7),
-            duration: 6,
-        }),
-    },
-    Spell {
-        cost: 173,
-        damage: 0,
-        heal: 0,
-        effect: Some(Effect {
-            benefit: EffectBenefit::Damage(3),
-            duration: 6,
-        }),
-    },
-    Spell {
-        cost: 229,
-        damage: 0,
-        heal: 0,
-        effect: Some(Effect {
-            benefit: EffectBenefit::Mana(101),
-            duration: 5,
-        }),
-    },
-];
-
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Default)]
 struct Player {
     hit_points: u32,
     mana: u32,
@@ -77,160 +53,478 @@ struct Boss {
     damage: u32,
 }
 
+#[derive(Clone, Copy, Debug, Default)]
+struct Difficulty {
+    drain: u32,
+}
+
 #[derive(Clone, Debug)]
-struct Game {
+struct Config {
     player: Player,
-    boss: Boss,
-    effects: Vec<Effect>,
-    turn: u32,
-    mana_spent: u32,
+    spells: Vec<Spell>,
+    difficulties: HashMap<String, Difficulty>,
 }
 
-#[aoc_generator(day22)]
-fn generator(input: &str) -> anyhow::Result<Boss> {
-    let mut lines = input.lines();
-    let hit_points = lines
-        .next()
-        .context("Boss hit points")?
-        .split(": ")
-        .last()
-        .context("Boss hit points")?
-        .parse()?;
-    let damage = lines
-        .next()
-        .context("Boss damage")?
-        .split(": ")
-        .last()
-        .context("Boss damage")?
-        .parse()?;
-    Ok(Boss { hit_points, damage })
+fn number(word: &str) -> anyhow::Result<u32> {
+    word.parse()
+        .with_context(|| format!("Expected a number, found {word}"))
 }
 
-const PLAYER: Player = Player {
-    hit_points: 50,
-    mana: 500,
-    armor: 0,
-};
-
-fn play(boss: Boss, hard: bool) -> u32 {
-    let game = Game {
-        player: PLAYER,
-        boss,
-        effects: Vec::new(),
-        turn: 0,
-        mana_spent: 0,
+fn parse_spell(name: &str, clauses: &str) -> anyhow::Result<Spell> {
+    let mut spell = Spell {
+        name: name.to_string(),
+        cost: 0,
+        damage: 0,
+        heal: 0,
+        effect: None,
     };
 
-    let mut min_mana_spent = u32::MAX;
-    let mut queue = vec![game];
-
-    while let Some(mut current) = queue.pop() {
-        if current.mana_spent >= min_mana_spent || current.player.hit_points == 0 {
-            continue;
+    for clause in clauses.split(", ") {
+        let words = clause.split_whitespace().collect::<Vec<_>>();
+        match words.as_slice() {
+            ["costs", cost] => spell.cost = number(cost)?,
+            ["deals", damage, "damage"] => spell.damage = number(damage)?,
+            ["heals", heal, "hit", "points"] => spell.heal = number(heal)?,
+            ["stacks"] => {
+                let effect = spell
+                    .effect
+                    .as_mut()
+                    .with_context(|| format!("{name} stacks but has no effect"))?;
+                effect.stacks = true;
+            }
+            [kind, amount, "for", duration, "turns"] => {
+                let amount = number(amount)?;
+                let benefit = match *kind {
+                    "armor" => EffectBenefit::Armor(amount),
+                    "damage" => EffectBenefit::Damage(amount),
+                    "mana" => EffectBenefit::Mana(amount),
+                    "heal" => EffectBenefit::Heal(amount),
+                    _ => bail!("Unknown effect {kind} in {name}"),
+                };
+                spell.effect = Some(Effect {
+                    benefit,
+                    duration: number(duration)?,
+                    stacks: false,
+                });
+            }
+            _ => bail!("Unknown clause {clause} in {name}"),
         }
+    }
 
-        if current.boss.hit_points == 0 {
-            min_mana_spent = min_mana_spent.min(current.mana_spent);
-            continue;
-        }
+    Ok(spell)
+}
 
-        if current.turn % 2 == 0 {
-            // player's turn
-            if hard {
-                current.player.hit_points = current.player.hit_points.saturating_sub(1);
-                if current.player.hit_points == 0 {
-                    continue;
-                }
-            }
+impl FromStr for Config {
+    type Err = anyhow::Error;
 
-            for effect in current.effects.iter_mut() {
-                effect.duration -= 1;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = Config {
+            player: Player::default(),
+            spells: vec![],
+            difficulties: HashMap::new(),
+        };
 
-                match effect.benefit {
-                    EffectBenefit::Damage(damage) => {
-                        current.boss.hit_points = current.boss.hit_points.saturating_sub(damage)
-                    }
-                    EffectBenefit::Mana(mana) => current.player.mana += mana,
-                    EffectBenefit::Armor(armor) => {
-                        if effect.duration == 0 {
-                            current.player.armor -= armor;
+        for line in s.lines().filter(|line| !line.trim().is_empty()) {
+            let (heading, clauses) = line
+                .split_once(": ")
+                .with_context(|| format!("Invalid config line {line}"))?;
+
+            if heading == "Player" {
+                for clause in clauses.split(", ") {
+                    let words = clause.split_whitespace().collect::<Vec<_>>();
+                    match words.as_slice() {
+                        [hit_points, "hit", "points"] => {
+                            config.player.hit_points = number(hit_points)?
                         }
+                        [mana, "mana"] => config.player.mana = number(mana)?,
+                        [armor, "armor"] => config.player.armor = number(armor)?,
+                        _ => bail!("Unknown player clause {clause}"),
                     }
                 }
+            } else if let Some(name) = heading.strip_prefix("Spell ") {
+                config.spells.push(parse_spell(name, clauses)?);
+            } else if let Some(name) = heading.strip_prefix("Difficulty ") {
+                let Some(drain) = clauses.strip_prefix("drain ") else {
+                    bail!("Unknown difficulty rule {clauses}");
+                };
+                config.difficulties.insert(
+                    name.to_string(),
+                    Difficulty {
+                        drain: number(drain)?,
+                    },
+                );
+            } else {
+                bail!("Unknown config section {heading}");
             }
+        }
+
+        Ok(config)
+    }
+}
 
-            current.effects.retain(|effect| effect.duration > 0);
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct State {
+    hit_points: u32,
+    mana: u32,
+    boss_hit_points: u32,
+    effects: Vec<(usize, u32)>,
+}
 
-            let next_spells = SPELLS
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Outcome {
+    Continue,
+    Won,
+    Lost,
+}
+
+struct Narrator {
+    enabled: bool,
+    lines: Vec<String>,
+}
+
+impl Narrator {
+    fn say(&mut self, line: impl FnOnce() -> String) {
+        if self.enabled {
+            self.lines.push(line());
+        }
+    }
+}
+
+fn plural(count: u32, noun: &str) -> String {
+    if count == 1 {
+        format!("{count} {noun}")
+    } else {
+        format!("{count} {noun}s")
+    }
+}
+
+struct Battle<'a> {
+    config: &'a Config,
+    boss: Boss,
+    difficulty: Difficulty,
+}
+
+impl Battle<'_> {
+    fn armor(&self, state: &State) -> u32 {
+        self.config.player.armor
+            + state
+                .effects
                 .iter()
-                .filter(|spell| spell.cost <= current.player.mana)
-                .filter(|spell| {
-                    spell
-                        .effect
-                        .as_ref()
-                        .map(|effect| {
-                            current
-                                .effects
-                                .iter()
-                                .all(|current_effect| current_effect.benefit != effect.benefit)
-                        })
-                        .unwrap_or(true)
-                });
-            for spell in next_spells {
-                // println!("Player casts {:?} spell", &spell);
-                let mut next_game = current.clone();
-                next_game.turn += 1;
-                next_game.mana_spent += spell.cost;
-                next_game.player.mana -= spell.cost;
-                next_game.boss.hit_points = next_game.boss.hit_points.saturating_sub(spell.damage);
-                next_game.player.hit_points += spell.heal;
-
-                if let Some(effect) = spell.effect {
-                    if let EffectBenefit::Armor(armor) = effect.benefit {
-                        next_game.player.armor += armor;
-                    }
+                .filter_map(
+                    |&(spell, _)| match self.config.spells[spell].effect?.benefit {
+                        EffectBenefit::Armor(armor) => Some(armor),
+                        _ => None,
+                    },
+                )
+                .sum::<u32>()
+    }
 
-                    next_game.effects.push(effect);
-                }
+    fn status(&self, state: &State, who: &str, narrator: &mut Narrator) {
+        narrator.say(|| format!("-- {who} turn --"));
+        narrator.say(|| {
+            format!(
+                "- Player has {}, {} armor, {} mana",
+                plural(state.hit_points, "hit point"),
+                self.armor(state),
+                state.mana
+            )
+        });
+        narrator.say(|| format!("- Boss has {}", plural(state.boss_hit_points, "hit point")));
+    }
 
-                queue.push(next_game);
-            }
-        } else {
-            // boss's turn
-            for effect in current.effects.iter_mut() {
-                effect.duration -= 1;
-
-                match effect.benefit {
-                    EffectBenefit::Damage(damage) => {
-                        current.boss.hit_points = current.boss.hit_points.saturating_sub(damage)
+    fn apply_effects(&self, state: &mut State, narrator: &mut Narrator) -> Outcome {
+        for (spell, timer) in state.effects.iter_mut() {
+            let Spell { name, effect, .. } = &self.config.spells[*spell];
+            let Some(effect) = effect else {
+                continue;
+            };
+            *timer -= 1;
+
+            match effect.benefit {
+                EffectBenefit::Damage(damage) => {
+                    state.boss_hit_points = state.boss_hit_points.saturating_sub(damage);
+                    if state.boss_hit_points == 0 {
+                        narrator.say(|| {
+                            format!(
+                                "{name} deals {damage} damage. This kills the boss, and the player wins."
+                            )
+                        });
+                        return Outcome::Won;
                     }
-                    EffectBenefit::Mana(mana) => current.player.mana += mana,
+                    narrator
+                        .say(|| format!("{name} deals {damage} damage; its timer is now {timer}."));
+                }
+                EffectBenefit::Mana(mana) => {
+                    state.mana += mana;
+                    narrator
+                        .say(|| format!("{name} provides {mana} mana; its timer is now {timer}."));
+                }
+                EffectBenefit::Heal(heal) => {
+                    state.hit_points += heal;
+                    narrator.say(|| {
+                        format!(
+                            "{name} heals {}; its timer is now {timer}.",
+                            plural(heal, "hit point")
+                        )
+                    });
+                }
+                EffectBenefit::Armor(_) => {
+                    narrator.say(|| format!("{name}'s timer is now {timer}."));
+                }
+            }
+
+            if *timer == 0 {
+                narrator.say(|| match effect.benefit {
                     EffectBenefit::Armor(armor) => {
-                        if effect.duration == 0 {
-                            current.player.armor -= armor;
-                        }
+                        format!("{name} wears off, decreasing armor by {armor}.")
                     }
+                    _ => format!("{name} wears off."),
+                });
+            }
+        }
+
+        state.effects.retain(|&(_, timer)| timer > 0);
+        Outcome::Continue
+    }
+
+    fn can_cast(&self, state: &State, spell: usize) -> bool {
+        let Spell { cost, effect, .. } = &self.config.spells[spell];
+        *cost <= state.mana
+            && effect.is_none_or(|effect| {
+                effect.stacks || state.effects.iter().all(|&(active, _)| active != spell)
+            })
+    }
+
+    fn begin_player_turn(&self, state: &mut State, narrator: &mut Narrator) -> Outcome {
+        self.status(state, "Player", narrator);
+        if self.difficulty.drain > 0 {
+            state.hit_points = state.hit_points.saturating_sub(self.difficulty.drain);
+            narrator.say(|| {
+                format!(
+                    "Player loses {}.",
+                    plural(self.difficulty.drain, "hit point")
+                )
+            });
+            if state.hit_points == 0 {
+                narrator.say(|| "This kills the player, and the boss wins.".to_string());
+                return Outcome::Lost;
+            }
+        }
+        self.apply_effects(state, narrator)
+    }
+
+    fn round(&self, state: &mut State, spell: usize, narrator: &mut Narrator) -> Outcome {
+        if !self.can_cast(state, spell) {
+            return Outcome::Lost;
+        }
+        let Spell {
+            name,
+            cost,
+            damage,
+            heal,
+            effect,
+        } = &self.config.spells[spell];
+        state.mana -= cost;
+        state.boss_hit_points = state.boss_hit_points.saturating_sub(*damage);
+        state.hit_points += heal;
+        if let Some(effect) = effect {
+            state.effects.push((spell, effect.duration));
+            state.effects.sort_unstable();
+        }
+
+        narrator.say(|| {
+            let mut parts = vec![];
+            if *damage > 0 {
+                parts.push(format!("dealing {damage} damage"));
+            }
+            if *heal > 0 {
+                parts.push(format!("healing {}", plural(*heal, "hit point")));
+            }
+            if let Some(Effect {
+                benefit: EffectBenefit::Armor(armor),
+                ..
+            }) = effect
+                && parts.is_empty()
+            {
+                parts.push(format!("increasing armor by {armor}"));
+            }
+            match parts.as_slice() {
+                [] => format!("Player casts {name}."),
+                [part] => format!("Player casts {name}, {part}."),
+                [first, second] => format!("Player casts {name}, {first}, and {second}."),
+                _ => unreachable!(),
+            }
+        });
+        if state.boss_hit_points == 0 {
+            narrator.say(|| "This kills the boss, and the player wins.".to_string());
+            return Outcome::Won;
+        }
+
+        narrator.say(String::new);
+        self.status(state, "Boss", narrator);
+        if self.apply_effects(state, narrator) == Outcome::Won {
+            return Outcome::Won;
+        }
+
+        let armor = self.armor(state);
+        let damage = self.boss.damage.saturating_sub(armor).max(1);
+        state.hit_points = state.hit_points.saturating_sub(damage);
+        narrator.say(|| {
+            if armor > 0 {
+                format!(
+                    "Boss attacks for {} - {armor} = {damage} damage!",
+                    self.boss.damage
+                )
+            } else {
+                format!("Boss attacks for {damage} damage.")
+            }
+        });
+        if state.hit_points == 0 {
+            narrator.say(|| "This kills the player, and the boss wins.".to_string());
+            return Outcome::Lost;
+        }
+
+        narrator.say(String::new);
+        self.begin_player_turn(state, narrator)
+    }
+
+    fn start(&self, narrator: &mut Narrator) -> (State, Outcome) {
+        let mut state = State {
+            hit_points: self.config.player.hit_points,
+            mana: self.config.player.mana,
+            boss_hit_points: self.boss.hit_points,
+            effects: vec![],
+        };
+        let outcome = self.begin_player_turn(&mut state, narrator);
+        (state, outcome)
+    }
+
+    fn cheapest_win(&self) -> Option<(u32, Vec<usize>)> {
+        let mut silent = Narrator {
+            enabled: false,
+            lines: vec![],
+        };
+        let (start, outcome) = self.start(&mut silent);
+        match outcome {
+            Outcome::Won => return Some((0, vec![])),
+            Outcome::Lost => return None,
+            Outcome::Continue => {}
+        }
+
+        let mut spent = HashMap::from([(start.clone(), 0)]);
+        let mut previous = HashMap::<State, (State, usize)>::new();
+        let mut queue = BinaryHeap::from([Reverse((0, start))]);
+
+        while let Some(Reverse((mana_spent, state))) = queue.pop() {
+            if mana_spent > spent[&state] {
+                continue;
+            }
+            if state.boss_hit_points == 0 {
+                let mut spells = vec![];
+                let mut current = &state;
+                while let Some((before, spell)) = previous.get(current) {
+                    spells.push(*spell);
+                    current = before;
                 }
+                spells.reverse();
+                return Some((mana_spent, spells));
             }
 
-            current.effects.retain(|effect| effect.duration > 0);
+            for spell in 0..self.config.spells.len() {
+                if !self.can_cast(&state, spell) {
+                    continue;
+                }
+
+                let mut next = state.clone();
+                match self.round(&mut next, spell, &mut silent) {
+                    Outcome::Lost => continue,
+                    Outcome::Won => next.boss_hit_points = 0,
+                    Outcome::Continue => {}
+                }
 
-            let damage = current.boss.damage.saturating_sub(current.player.armor);
-            current.player.hit_points = current.player.hit_points.saturating_sub(damage);
-            current.turn += 1;
-            queue.push(current);
+                let cost = mana_spent + self.config.spells[spell].cost;
+                if cost < spent.get(&next).copied().unwrap_or(u32::MAX) {
+                    spent.insert(next.clone(), cost);
+                    previous.insert(next.clone(), (state.clone(), spell));
+                    queue.push(Reverse((cost, next)));
+                }
+            }
         }
+
+        None
     }
 
-    min_mana_spent
+    fn transcript(&self, spells: &[usize]) -> String {
+        let mut narrator = Narrator {
+            enabled: true,
+            lines: vec![],
+        };
+        let (mut state, mut outcome) = self.start(&mut narrator);
+        for &spell in spells {
+            if outcome != Outcome::Continue {
+                break;
+            }
+            outcome = self.round(&mut state, spell, &mut narrator);
+        }
+        narrator.lines.join("\n")
+    }
+}
+
+#[aoc_generator(day22)]
+fn generator(input: &str) -> anyhow::Result<Boss> {
+    let mut lines = input.lines();
+    let hit_points = lines
+        .next()
+        .context("Boss hit points")?
+        .split(": ")
+        .last()
+        .context("Boss hit points")?
+        .parse()?;
+    let damage = lines
+        .next()
+        .context("Boss damage")?
+        .split(": ")
+        .last()
+        .context("Boss damage")?
+        .parse()?;
+    Ok(Boss { hit_points, damage })
+}
+
+fn battle<'a>(config: &'a Config, boss: Boss, difficulty: &str) -> anyhow::Result<Battle<'a>> {
+    let difficulty = *config
+        .difficulties
+        .get(difficulty)
+        .with_context(|| format!("Unknown difficulty {difficulty}"))?;
+    Ok(Battle {
+        config,
+        boss,
+        difficulty,
+    })
+}
+
+fn play(boss: Boss, difficulty: &str) -> anyhow::Result<(u32, String)> {
+    let config = DEFAULT_CONFIG.parse::<Config>()?;
+    let battle = battle(&config, boss, difficulty)?;
+    let (mana_spent, spells) = battle.cheapest_win().context("The boss cannot be beaten")?;
+    Ok((mana_spent, battle.transcript(&spells)))
 }
 
 #[aoc(day22, part1)]
-fn part1(boss: &Boss) -> u32 {
-    play(*boss, false)
+fn part1(boss: &Boss) -> anyhow::Result<u32> {
+    Ok(play(*boss, "normal")?.0)
+}
+
+#[aoc(day22, part1, transcript)]
+fn part1_transcript(boss: &Boss) -> anyhow::Result<String> {
+    Ok(play(*boss, "normal")?.1)
 }
 
 #[aoc(day22, part2)]
-fn part2(boss: &Boss) -> u32 {
-    play(*boss, true)
+fn part2(boss: &Boss) -> anyhow::Result<u32> {
+    Ok(play(*boss, "hard")?.0)
+}
+
+#[aoc(day22, part2, transcript)]
+fn part2_transcript(boss: &Boss) -> anyhow::Result<String> {
+    Ok(play(*boss, "hard")?.1)
 }
@@ -1,6 +1,9 @@
-use std::{collections::HashMap, str::FromStr};
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+};
 
-use anyhow::bail;
+use anyhow::{Context, bail};
 use aoc_runner_derive::{aoc, aoc_generator};
 use nom::{
     IResult, Parser,
@@ -103,8 +106,6 @@ struct Instruction {
     destination: String,
 }
 
-type Environment = HashMap<String, Expression>;
-
 impl FromStr for Instruction {
     type Err = anyhow::Error;
 
@@ -117,69 +118,219 @@ impl FromStr for Instruction {
     }
 }
 
-fn execute(
-    expression: &Expression,
-    environment: &Environment,
-    cache: &mut HashMap<String, u16>,
-) -> u16 {
-    match expression {
-        Expression::Literal(value) => *value,
-        Expression::Variable(name) => match cache.get(name) {
-            Some(v) => *v,
-            None => {
-                let value = execute(&environment[name], environment, cache);
-                cache.insert(name.to_string(), value);
-                value
+#[derive(Clone, Copy, Debug)]
+enum Operand {
+    Literal(u16),
+    Wire(usize),
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Gate {
+    Copy(Operand),
+    And(Operand, Operand),
+    Or(Operand, Operand),
+    LeftShift(Operand, Operand),
+    RightShift(Operand, Operand),
+    Not(Operand),
+}
+
+impl Gate {
+    fn inputs(&self) -> impl Iterator<Item = usize> {
+        let (lhs, rhs) = match *self {
+            Gate::Copy(operand) | Gate::Not(operand) => (operand, None),
+            Gate::And(lhs, rhs)
+            | Gate::Or(lhs, rhs)
+            | Gate::LeftShift(lhs, rhs)
+            | Gate::RightShift(lhs, rhs) => (lhs, Some(rhs)),
+        };
+        std::iter::once(lhs)
+            .chain(rhs)
+            .filter_map(|operand| match operand {
+                Operand::Wire(wire) => Some(wire),
+                Operand::Literal(_) => None,
+            })
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Circuit {
+    names: Vec<String>,
+    wires: HashMap<String, usize>,
+    gates: Vec<Gate>,
+    order: Vec<usize>,
+}
+
+impl Circuit {
+    fn compile(instructions: &[Instruction]) -> anyhow::Result<Self> {
+        let mut circuit = Circuit {
+            names: vec![],
+            wires: HashMap::new(),
+            gates: vec![],
+            order: vec![],
+        };
+
+        for instruction in instructions {
+            if circuit
+                .wires
+                .insert(instruction.destination.clone(), circuit.names.len())
+                .is_some()
+            {
+                bail!("Wire {} is driven more than once", instruction.destination);
             }
-        },
-        Expression::And(lhs, rhs) => {
-            let lhs = execute(lhs, environment, cache);
-            let rhs = execute(rhs, environment, cache);
-            lhs & rhs
+            circuit.names.push(instruction.destination.clone());
         }
-        Expression::Or(lhs, rhs) => {
-            let lhs = execute(lhs, environment, cache);
-            let rhs = execute(rhs, environment, cache);
-            lhs | rhs
+
+        for instruction in instructions {
+            let gate = circuit.gate(&instruction.expression, &instruction.destination)?;
+            circuit.gates.push(gate);
         }
-        Expression::LeftShift(lhs, rhs) => {
-            let lhs = execute(lhs, environment, cache);
-            let rhs = execute(rhs, environment, cache);
-            lhs << rhs
+
+        circuit.order = circuit.sort()?;
+        Ok(circuit)
+    }
+
+    fn operand(&self, expression: &Expression, destination: &str) -> anyhow::Result<Operand> {
+        match expression {
+            Expression::Literal(value) => Ok(Operand::Literal(*value)),
+            Expression::Variable(name) => match self.wires.get(name) {
+                Some(&wire) => Ok(Operand::Wire(wire)),
+                None => bail!("Wire {name} feeds {destination} but is never driven"),
+            },
+            _ => bail!("Gate inputs to {destination} must be wires or signals"),
         }
-        Expression::RightShift(lhs, rhs) => {
-            let lhs = execute(lhs, environment, cache);
-            let rhs = execute(rhs, environment, cache);
-            lhs >> rhs
+    }
+
+    fn gate(&self, expression: &Expression, destination: &str) -> anyhow::Result<Gate> {
+        let operand = |expression| self.operand(expression, destination);
+        Ok(match expression {
+            Expression::Literal(_) | Expression::Variable(_) => Gate::Copy(operand(expression)?),
+            Expression::And(lhs, rhs) => Gate::And(operand(lhs)?, operand(rhs)?),
+            Expression::Or(lhs, rhs) => Gate::Or(operand(lhs)?, operand(rhs)?),
+            Expression::LeftShift(lhs, rhs) => Gate::LeftShift(operand(lhs)?, operand(rhs)?),
+            Expression::RightShift(lhs, rhs) => Gate::RightShift(operand(lhs)?, operand(rhs)?),
+            Expression::Not(expression) => Gate::Not(operand(expression)?),
+        })
+    }
+
+    fn sort(&self) -> anyhow::Result<Vec<usize>> {
+        let mut order = Vec::with_capacity(self.gates.len());
+        let mut finished = vec![false; self.gates.len()];
+        let mut on_path = vec![false; self.gates.len()];
+
+        for root in 0..self.gates.len() {
+            if finished[root] {
+                continue;
+            }
+
+            let mut path = vec![(root, self.gates[root].inputs())];
+            on_path[root] = true;
+            while let Some((wire, inputs)) = path.last_mut() {
+                let wire = *wire;
+                match inputs.find(|&input| !finished[input]) {
+                    Some(input) if on_path[input] => {
+                        let start = path.iter().position(|&(wire, _)| wire == input).unwrap();
+                        let cycle = path[start..]
+                            .iter()
+                            .map(|&(wire, _)| self.names[wire].as_str())
+                            .chain([self.names[input].as_str()])
+                            .collect::<Vec<_>>();
+                        bail!("Wires form a loop: {}", cycle.join(" <- "));
+                    }
+                    Some(input) => {
+                        on_path[input] = true;
+                        path.push((input, self.gates[input].inputs()));
+                    }
+                    None => {
+                        on_path[wire] = false;
+                        finished[wire] = true;
+                        order.push(wire);
+                        path.pop();
+                    }
+                }
+            }
         }
-        Expression::Not(expr) => {
-            let value = execute(expr, environment, cache);
-            !value
+
+        Ok(order)
+    }
+
+    fn wire(&self, name: &str) -> anyhow::Result<usize> {
+        self.wires
+            .get(name)
+            .copied()
+            .with_context(|| format!("There is no wire {name}"))
+    }
+
+    fn override_wire(&mut self, name: &str, value: u16) -> anyhow::Result<()> {
+        let wire = self.wire(name)?;
+        self.gates[wire] = Gate::Copy(Operand::Literal(value));
+        Ok(())
+    }
+
+    fn run(&self) -> Vec<u16> {
+        let mut signals = vec![0; self.gates.len()];
+        for &wire in &self.order {
+            let signal = |operand| match operand {
+                Operand::Literal(value) => value,
+                Operand::Wire(wire) => signals[wire],
+            };
+            signals[wire] = match self.gates[wire] {
+                Gate::Copy(operand) => signal(operand),
+                Gate::And(lhs, rhs) => signal(lhs) & signal(rhs),
+                Gate::Or(lhs, rhs) => signal(lhs) | signal(rhs),
+                Gate::LeftShift(lhs, rhs) => {
+                    signal(lhs).checked_shl(signal(rhs).into()).unwrap_or(0)
+                }
+                Gate::RightShift(lhs, rhs) => {
+                    signal(lhs).checked_shr(signal(rhs).into()).unwrap_or(0)
+                }
+                Gate::Not(operand) => !signal(operand),
+            };
         }
+        signals
+    }
+
+    fn signal(&self, name: &str) -> anyhow::Result<u16> {
+        let wire = self.wire(name)?;
+        Ok(self.run()[wire])
+    }
+
+    fn signals(&self) -> BTreeMap<&str, u16> {
+        self.names
+            .iter()
+            .map(String::as_str)
+            .zip(self.run())
+            .collect()
     }
 }
 
 #[aoc_generator(day7)]
-fn generator(input: &str) -> anyhow::Result<Environment> {
-    input
+fn generator(input: &str) -> anyhow::Result<Circuit> {
+    let instructions = input
         .lines()
         .map(str::parse::<Instruction>)
-        .map(|instruction| instruction.map(|i| (i.destination, i.expression)))
-        .collect()
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Circuit::compile(&instructions)
 }
 
 #[aoc(day7, part1)]
-fn part1(environment: &Environment) -> u16 {
-    let mut cache = HashMap::new();
-    execute(&environment["a"], environment, &mut cache)
+fn part1(circuit: &Circuit) -> anyhow::Result<u16> {
+    circuit.signal("a")
 }
 
-#[aoc(day7, part2)]
-fn part2(environment: &Environment) -> u16 {
-    let mut cache = HashMap::new();
-    let a = execute(&environment["a"], environment, &mut cache);
+#[aoc(day7, part1, signals)]
+fn part1_signals(circuit: &Circuit) -> String {
+    circuit
+        .signals()
+        .into_iter()
+        .map(|(name, signal)| format!("{name}: {signal}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
 
-    let mut cache = HashMap::new();
-    cache.insert("b".to_string(), a);
-    execute(&environment["a"], environment, &mut cache)
+#[aoc(day7, part2)]
+fn part2(circuit: &Circuit) -> anyhow::Result<u16> {
+    let a = circuit.signal("a")?;
+    let mut circuit = circuit.clone();
+    circuit.override_wire("b", a)?;
+    circuit.signal("a")
 }
@@ -1,6 +1,6 @@
-use std::collections::{BTreeSet, HashSet, VecDeque};
+use std::collections::{BTreeSet, HashMap, VecDeque, hash_map::Entry};
 
-use anyhow::bail;
+use anyhow::{Context, anyhow, bail};
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::{Itertools, iproduct};
 use regex::Regex;
@@ -29,10 +29,6 @@ impl Floor {
             Part::Generator(_) => true,
         })
     }
-
-    fn is_empty(&self) -> bool {
-        self.parts.is_empty()
-    }
 }
 
 #[aoc_generator(day11)]
@@ -65,87 +61,223 @@ fn generator(input: &str) -> anyhow::Result<Vec<Floor>> {
         .collect()
 }
 
+const CHIP: usize = 0;
+const GENERATOR: usize = 1;
+
 #[derive(Clone, Debug, PartialEq, Eq)]
-struct Step {
-    count: usize,
-    current_floor: usize,
-    floors: Vec<Floor>,
+struct Move {
+    items: Vec<usize>,
+    from: usize,
+    to: usize,
 }
 
-impl Step {
-    fn is_valid(&self) -> bool {
-        self.floors.iter().all(Floor::is_valid)
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Facility {
+    elements: Vec<String>,
+    floors: usize,
+    elevator: usize,
+    positions: Vec<usize>,
+}
+
+impl Facility {
+    fn new(input: &[Floor]) -> anyhow::Result<Self> {
+        if let Some(floor) = input.iter().position(|floor| !floor.is_valid()) {
+            bail!("A microchip is fried on floor {}", floor + 1);
+        }
+
+        let elements = input
+            .iter()
+            .flat_map(|floor| &floor.parts)
+            .map(|part| match part {
+                Part::Microchip(element) | Part::Generator(element) => element.clone(),
+            })
+            .sorted()
+            .dedup()
+            .collect_vec();
+
+        let mut positions = vec![usize::MAX; elements.len() * 2];
+        for (index, floor) in input.iter().enumerate() {
+            for part in &floor.parts {
+                let (element, kind) = match part {
+                    Part::Microchip(element) => (element, CHIP),
+                    Part::Generator(element) => (element, GENERATOR),
+                };
+                let element = elements.binary_search(element).unwrap();
+                positions[element * 2 + kind] = index;
+            }
+        }
+        if let Some(element) = elements
+            .iter()
+            .enumerate()
+            .find(|&(element, _)| positions[element * 2..element * 2 + 2].contains(&usize::MAX))
+            .map(|(_, name)| name)
+        {
+            bail!("The {element} microchip and generator are not both present");
+        }
+
+        let facility = Self {
+            elements,
+            floors: input.len(),
+            elevator: 0,
+            positions,
+        };
+        facility.key()?;
+        Ok(facility)
     }
 
-    fn is_complete(&self) -> bool {
-        self.floors
+    fn with_element(mut self, element: &str) -> Self {
+        self.elements.push(element.to_string());
+        self.positions.extend([0, 0]);
+        self
+    }
+
+    fn key(&self) -> anyhow::Result<u64> {
+        let radix = self.floors as u64;
+        self.positions
             .iter()
-            .take(self.floors.len() - 1)
-            .all(|floor| floor.is_empty())
+            .tuples()
+            .sorted()
+            .flat_map(|(&chip, &generator)| [chip, generator])
+            .try_fold(self.elevator as u64, |key, floor| {
+                key.checked_mul(radix)
+                    .map(|key| key + floor as u64)
+                    .ok_or_else(|| anyhow!("Facility is too large to pack into a state"))
+            })
     }
-}
 
-fn solve(input: &[Floor]) -> usize {
-    let floors = input.to_vec();
-    let mut visited = HashSet::new();
-    let mut search = VecDeque::from_iter([Step {
-        count: 0,
-        current_floor: 0,
-        floors,
-    }]);
-
-    while let Some(step) = search.pop_front() {
-        if step.is_complete() {
-            return step.count;
-        }
+    fn is_floor_safe(&self, floor: usize) -> bool {
+        let shielded = |element: usize| self.positions[element * 2 + GENERATOR] == floor;
+        let exposed = (0..self.elements.len())
+            .any(|element| self.positions[element * 2 + CHIP] == floor && !shielded(element));
+        !exposed || !(0..self.elements.len()).any(shielded)
+    }
+
+    fn is_complete(&self) -> bool {
+        self.positions.iter().all(|&floor| floor == self.floors - 1)
+    }
+
+    fn moves(&self) -> impl Iterator<Item = Move> + '_ {
+        let here = (0..self.positions.len())
+            .filter(|&item| self.positions[item] == self.elevator)
+            .collect_vec();
+        let below_empty = self.positions.iter().all(|&floor| floor >= self.elevator);
+
+        let destinations = [self.elevator.checked_sub(1), Some(self.elevator + 1)]
+            .into_iter()
+            .flatten()
+            .filter(move |&to| to < self.floors && (to > self.elevator || !below_empty));
+        let loads = (1..=2).flat_map(move |size| here.clone().into_iter().combinations(size));
 
-        if !visited.insert((step.current_floor, step.floors.clone())) {
-            continue;
+        iproduct!(destinations, loads.collect_vec()).map(|(to, items)| Move {
+            items,
+            from: self.elevator,
+            to,
+        })
+    }
+
+    fn apply(&self, step: &Move) -> Option<Self> {
+        let mut next = self.clone();
+        next.elevator = step.to;
+        for &item in &step.items {
+            next.positions[item] = step.to;
         }
+        (next.is_floor_safe(step.from) && next.is_floor_safe(step.to)).then_some(next)
+    }
 
-        let next_floors = [-1, 1]
+    fn describe(&self, step: &Move) -> String {
+        let items = step
+            .items
             .iter()
-            .map(|&d| step.current_floor.saturating_add_signed(d))
-            .filter(|&f| f != step.current_floor && f < step.floors.len())
-            .collect_vec();
-        for (next_floor, size) in iproduct!(next_floors, 1..=2) {
-            let part_groups = step.floors[step.current_floor]
-                .parts
-                .iter()
-                .combinations(size);
-            for part_group in part_groups {
-                let mut next_step = step.clone();
-                next_step.count += 1;
-                next_step.current_floor = next_floor;
-                for part in part_group {
-                    next_step.floors[step.current_floor].parts.remove(part);
-                    next_step.floors[next_floor].parts.insert(part.clone());
+            .map(|&item| {
+                let element = &self.elements[item / 2];
+                if item % 2 == CHIP {
+                    format!("the {element}-compatible microchip")
+                } else {
+                    format!("the {element} generator")
                 }
+            })
+            .join(" and ");
+        let direction = if step.to > step.from { "up" } else { "down" };
+        format!("Take {items} {direction} to floor {}", step.to + 1)
+    }
+
+    fn solve(&self) -> anyhow::Result<Option<Vec<Move>>> {
+        let start = self.key()?;
+        let mut previous = HashMap::<u64, Option<(u64, Move)>>::from([(start, None)]);
+        let mut search = VecDeque::from([(start, self.clone())]);
 
-                if next_step.is_valid() {
-                    search.push_back(next_step);
+        while let Some((key, facility)) = search.pop_front() {
+            if facility.is_complete() {
+                let mut plan = vec![];
+                let mut current = key;
+                while let Some((before, step)) = &previous[&current] {
+                    plan.push(step.clone());
+                    current = *before;
+                }
+                plan.reverse();
+                return Ok(Some(plan));
+            }
+
+            for step in facility.moves() {
+                let Some(next) = facility.apply(&step) else {
+                    continue;
+                };
+                let next_key = next.key()?;
+                if let Entry::Vacant(entry) = previous.entry(next_key) {
+                    entry.insert(Some((key, step)));
+                    search.push_back((next_key, next));
                 }
             }
         }
+
+        Ok(None)
     }
+}
 
-    unreachable!()
+fn fewest_steps(facility: &Facility) -> anyhow::Result<usize> {
+    Ok(facility
+        .solve()?
+        .context("The items cannot all reach the top floor")?
+        .len())
+}
+
+fn plan(facility: &Facility) -> anyhow::Result<String> {
+    let moves = facility
+        .solve()?
+        .context("The items cannot all reach the top floor")?;
+    let mut current = facility.clone();
+    let mut lines = vec![];
+    for (index, step) in moves.iter().enumerate() {
+        lines.push(format!("{}. {}", index + 1, current.describe(step)));
+        current = current.apply(step).unwrap();
+    }
+    Ok(lines.join("\n"))
+}
+
+fn extended(input: &[Floor]) -> anyhow::Result<Facility> {
+    Ok(Facility::new(input)?
+        .with_element("elerium")
+        .with_element("dilithium"))
 }
 
 #[aoc(day11, part1)]
-fn part1(input: &[Floor]) -> usize {
-    solve(input)
+fn part1(input: &[Floor]) -> anyhow::Result<usize> {
+    fewest_steps(&Facility::new(input)?)
+}
+
+#[aoc(day11, part1, plan)]
+fn part1_plan(input: &[Floor]) -> anyhow::Result<String> {
+    plan(&Facility::new(input)?)
 }
 
 #[aoc(day11, part2)]
-fn part2(input: &[Floor]) -> usize {
-    let mut floors = input.to_vec();
-    for name in ["elerium", "dilithium"].iter() {
-        floors[0].parts.insert(Part::Microchip(name.to_string()));
-        floors[0].parts.insert(Part::Generator(name.to_string()));
-    }
+fn part2(input: &[Floor]) -> anyhow::Result<usize> {
+    fewest_steps(&extended(input)?)
+}
 
-    solve(&floors)
+#[aoc(day11, part2, plan)]
+fn part2_plan(input: &[Floor]) -> anyhow::Result<String> {
+    plan(&extended(input)?)
 }
 
 #[cfg(test)]
@@ -174,4 +306,74 @@ mod tests {
         floor.parts.insert(Part::Generator("lithium".to_string()));
         assert!(!floor.is_valid());
     }
+
+    const EXAMPLE: &str = "The first floor contains a hydrogen-compatible microchip and a lithium-compatible microchip.
+The second floor contains a hydrogen generator.
+The third floor contains a lithium generator.
+The fourth floor contains nothing relevant.";
+
+    #[test]
+    fn test_part1_example() {
+        let input = generator(EXAMPLE).unwrap();
+        assert_eq!(part1(&input).unwrap(), 11);
+
+        let plan = part1_plan(&input).unwrap();
+        assert_eq!(plan.lines().count(), 11);
+        assert_eq!(
+            plan.lines().next(),
+            Some("1. Take the hydrogen-compatible microchip up to floor 2")
+        );
+    }
+
+    #[test]
+    fn test_interchangeable_pairs_share_a_key() {
+        let facility = Facility::new(&generator(EXAMPLE).unwrap()).unwrap();
+        let mut swapped = facility.clone();
+        swapped.positions.swap(1, 3);
+        assert_ne!(facility.positions, swapped.positions);
+        swapped.positions.swap(0, 2);
+        assert_eq!(facility.key().unwrap(), swapped.key().unwrap());
+
+        let mut moved = facility.clone();
+        moved.elevator = 1;
+        assert_ne!(facility.key().unwrap(), moved.key().unwrap());
+    }
+
+    #[test]
+    fn test_any_number_of_floors() {
+        let input = generator(
+            "The first floor contains a hydrogen-compatible microchip.
+The second floor contains a hydrogen generator.",
+        )
+        .unwrap();
+        assert_eq!(part1(&input).unwrap(), 1);
+
+        let input = generator(
+            "The first floor contains a hydrogen generator and a hydrogen-compatible microchip.
+The second floor contains nothing relevant.
+The third floor contains nothing relevant.
+The fourth floor contains nothing relevant.
+The fifth floor contains nothing relevant.",
+        )
+        .unwrap();
+        assert_eq!(part1(&input).unwrap(), 4);
+    }
+
+    #[test]
+    fn test_invalid_facilities() {
+        assert!(
+            Facility::new(&generator("The first floor contains a hydrogen generator.").unwrap())
+                .is_err()
+        );
+        assert!(
+            Facility::new(
+                &generator(
+                    "The first floor contains a hydrogen-compatible microchip and a lithium generator.
+The second floor contains a lithium-compatible microchip."
+                )
+                .unwrap()
+            )
+            .is_err()
+        );
+    }
 }
@@ -1,3 +1,4 @@
+use advent_common::modular::crt;
 use anyhow::Context;
 use aoc_runner_derive::{aoc, aoc_generator};
 use regex::Regex;
@@ -22,28 +23,27 @@ fn generator(input: &str) -> anyhow::Result<Vec<Disc>> {
         .collect()
 }
 
+fn first_drop_time(discs: &[Disc]) -> Option<i64> {
+    let congruences = discs
+        .iter()
+        .enumerate()
+        .map(|(i, disc)| (-((disc.start + i + 1) as i64), disc.positions as i64))
+        .collect::<Vec<_>>();
+    crt(&congruences).map(|(time, _)| time)
+}
+
 #[aoc(day15, part1)]
-fn part1(input: &[Disc]) -> Option<usize> {
-    (0..).find(|time| {
-        input
-            .iter()
-            .enumerate()
-            .all(|(i, disc)| (disc.start + time + i + 1) % disc.positions == 0)
-    })
+fn part1(input: &[Disc]) -> Option<i64> {
+    first_drop_time(input)
 }
 
 #[aoc(day15, part2)]
-fn part2(input: &[Disc]) -> Option<usize> {
+fn part2(input: &[Disc]) -> Option<i64> {
     let mut discs = input.to_vec();
     discs.push(Disc {
         positions: 11,
         start: 0,
     });
 
-    (0..).find(|time| {
-        discs
-            .iter()
-            .enumerate()
-            .all(|(i, disc)| (disc.start + time + i + 1) % disc.positions == 0)
-    })
+    first_drop_time(&discs)
 }
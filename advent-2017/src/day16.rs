@@ -1,5 +1,6 @@
 use std::str::FromStr;
 
+use advent_common::cycle::find_cycle;
 use anyhow::{Context, bail};
 use aoc_runner_derive::{aoc, aoc_generator};
 
@@ -78,22 +79,9 @@ fn part1(input: &[Operation]) -> String {
 
 #[aoc(day16, part2)]
 fn part2(input: &[Operation]) -> String {
-    let mut program = START_PROGRAM.to_vec();
-    let mut i = 0;
-    let iterations = 1_000_000_000;
-    while i < iterations {
-        program = input
-            .iter()
-            .fold(program, |acc, op| op.transform(acc))
-            .into_iter()
-            .collect();
-
-        if program == START_PROGRAM {
-            i += ((iterations / (i + 1)) - 1) * (i + 1);
-        }
-
-        i += 1;
-    }
-
-    program.iter().collect()
+    let dance = |program| input.iter().fold(program, |acc, op| op.transform(acc));
+    let cycle = find_cycle(START_PROGRAM.to_vec(), dance, Vec::clone, |program| {
+        program.iter().collect::<String>()
+    });
+    cycle.at(1_000_000_000).clone()
 }
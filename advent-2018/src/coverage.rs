@@ -0,0 +1,311 @@
+use std::{cmp::Reverse, collections::BinaryHeap, fmt};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Ball<const D: usize> {
+    pub center: [i64; D],
+    pub radius: i64,
+}
+
+impl<const D: usize> Ball<D> {
+    pub fn contains(&self, point: [i64; D]) -> bool {
+        distance(self.center, point) <= self.radius
+    }
+
+    fn touches(&self, region: &Region<D>) -> bool {
+        region.nearest_distance(self.center) <= self.radius
+    }
+
+    fn covers(&self, region: &Region<D>) -> bool {
+        region.farthest_distance(self.center) <= self.radius
+    }
+}
+
+pub fn distance<const D: usize>(a: [i64; D], b: [i64; D]) -> i64 {
+    a.iter().zip(b).map(|(a, b)| (a - b).abs()).sum()
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Region<const D: usize> {
+    pub min: [i64; D],
+    pub max: [i64; D],
+}
+
+impl<const D: usize> Region<D> {
+    fn bounding(balls: &[Ball<D>]) -> Option<Self> {
+        let first = balls.first()?;
+        let mut region = Region {
+            min: first.center,
+            max: first.center,
+        };
+        for ball in balls {
+            for axis in 0..D {
+                region.min[axis] = region.min[axis].min(ball.center[axis] - ball.radius);
+                region.max[axis] = region.max[axis].max(ball.center[axis] + ball.radius);
+            }
+        }
+        Some(region)
+    }
+
+    pub fn nearest_point(&self, point: [i64; D]) -> [i64; D] {
+        std::array::from_fn(|axis| point[axis].clamp(self.min[axis], self.max[axis]))
+    }
+
+    pub fn nearest_distance(&self, point: [i64; D]) -> i64 {
+        distance(point, self.nearest_point(point))
+    }
+
+    fn farthest_distance(&self, point: [i64; D]) -> i64 {
+        (0..D)
+            .map(|axis| {
+                (point[axis] - self.min[axis])
+                    .abs()
+                    .max((point[axis] - self.max[axis]).abs())
+            })
+            .sum()
+    }
+
+    pub fn volume(&self) -> u128 {
+        (0..D)
+            .map(|axis| (self.max[axis] - self.min[axis] + 1) as u128)
+            .product()
+    }
+
+    fn split(&self) -> Vec<Self> {
+        let halves = |axis: usize| {
+            let (min, max) = (self.min[axis], self.max[axis]);
+            let mid = min + (max - min) / 2;
+            if min == max {
+                vec![(min, max)]
+            } else {
+                vec![(min, mid), (mid + 1, max)]
+            }
+        };
+
+        let mut regions = vec![*self];
+        for axis in 0..D {
+            regions = regions
+                .into_iter()
+                .flat_map(|region| {
+                    halves(axis).into_iter().map(move |(min, max)| {
+                        let mut half = region;
+                        half.min[axis] = min;
+                        half.max[axis] = max;
+                        half
+                    })
+                })
+                .collect();
+        }
+        regions
+    }
+}
+
+impl<const D: usize> fmt::Display for Region<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let axes = (0..D)
+            .map(|axis| {
+                if self.min[axis] == self.max[axis] {
+                    self.min[axis].to_string()
+                } else {
+                    format!("{}..={}", self.min[axis], self.max[axis])
+                }
+            })
+            .collect::<Vec<_>>();
+        write!(f, "({})", axes.join(", "))
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Coverage<const D: usize> {
+    pub count: usize,
+    pub closest: [i64; D],
+    pub distance: i64,
+}
+
+pub struct Search<'a, const D: usize> {
+    balls: &'a [Ball<D>],
+    origin: [i64; D],
+    heap: BinaryHeap<(usize, Reverse<i64>, Region<D>)>,
+}
+
+impl<const D: usize> Search<'_, D> {
+    fn push(&mut self, region: Region<D>) {
+        let upper = self
+            .balls
+            .iter()
+            .filter(|ball| ball.touches(&region))
+            .count();
+        self.heap
+            .push((upper, Reverse(region.nearest_distance(self.origin)), region));
+    }
+}
+
+impl<const D: usize> Iterator for Search<'_, D> {
+    type Item = (usize, Region<D>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((upper, _, region)) = self.heap.pop() {
+            let lower = self
+                .balls
+                .iter()
+                .filter(|ball| ball.covers(&region))
+                .count();
+            if lower == upper {
+                return Some((upper, region));
+            }
+            for part in region.split() {
+                self.push(part);
+            }
+        }
+        None
+    }
+}
+
+pub fn search<const D: usize>(balls: &[Ball<D>], origin: [i64; D]) -> Search<'_, D> {
+    let mut search = Search {
+        balls,
+        origin,
+        heap: BinaryHeap::new(),
+    };
+    if let Some(bounds) = Region::bounding(balls) {
+        search.push(bounds);
+    }
+    search
+}
+
+pub fn best_coverage<const D: usize>(balls: &[Ball<D>], origin: [i64; D]) -> Option<Coverage<D>> {
+    let mut regions = search(balls, origin).peekable();
+    let &(count, ref region) = regions.peek()?;
+    let distance = region.nearest_distance(origin);
+    let closest = regions
+        .take_while(|(upper, region)| {
+            *upper == count && region.nearest_distance(origin) == distance
+        })
+        .map(|(_, region)| region.nearest_point(origin))
+        .min()?;
+    Some(Coverage {
+        count,
+        closest,
+        distance,
+    })
+}
+
+pub fn optimal_regions<const D: usize>(balls: &[Ball<D>]) -> Vec<Region<D>> {
+    let mut regions = search(balls, [0; D]).peekable();
+    let Some(&(count, _)) = regions.peek() else {
+        return vec![];
+    };
+    let mut regions = regions
+        .take_while(|&(upper, _)| upper == count)
+        .map(|(_, region)| region)
+        .collect::<Vec<_>>();
+    regions.sort_unstable();
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn brute_force<const D: usize>(
+        balls: &[Ball<D>],
+        origin: [i64; D],
+    ) -> (usize, Vec<[i64; D]>, [i64; D]) {
+        let bounds = Region::bounding(balls).unwrap();
+        let mut points = vec![bounds.min];
+        for axis in 0..D {
+            points = points
+                .into_iter()
+                .flat_map(|point| {
+                    (bounds.min[axis]..=bounds.max[axis]).map(move |value| {
+                        let mut point = point;
+                        point[axis] = value;
+                        point
+                    })
+                })
+                .collect();
+        }
+
+        let coverage = |point: &[i64; D]| balls.iter().filter(|ball| ball.contains(*point)).count();
+        let count = points.iter().map(coverage).max().unwrap();
+        let mut best = points
+            .into_iter()
+            .filter(|point| coverage(point) == count)
+            .collect::<Vec<_>>();
+        best.sort_unstable();
+        let closest = *best
+            .iter()
+            .min_by_key(|&&point| (distance(origin, point), point))
+            .unwrap();
+        (count, best, closest)
+    }
+
+    fn expand<const D: usize>(regions: &[Region<D>]) -> Vec<[i64; D]> {
+        let mut points = vec![];
+        for region in regions {
+            let mut cells = vec![region.min];
+            for axis in 0..D {
+                cells = cells
+                    .into_iter()
+                    .flat_map(|cell| {
+                        (region.min[axis]..=region.max[axis]).map(move |value| {
+                            let mut cell = cell;
+                            cell[axis] = value;
+                            cell
+                        })
+                    })
+                    .collect();
+            }
+            points.extend(cells);
+        }
+        points.sort_unstable();
+        points
+    }
+
+    fn random_balls<const D: usize>(seed: &mut u64, count: usize) -> Vec<Ball<D>> {
+        let mut next = |range: i64| {
+            *seed ^= *seed << 13;
+            *seed ^= *seed >> 7;
+            *seed ^= *seed << 17;
+            (*seed % range as u64) as i64
+        };
+        (0..count)
+            .map(|_| Ball {
+                center: std::array::from_fn(|_| next(13) - 6),
+                radius: next(5),
+            })
+            .collect()
+    }
+
+    fn cross_check<const D: usize>(seed: u64, rounds: usize) {
+        let mut seed = seed;
+        for _ in 0..rounds {
+            let count = 1 + seed as usize % 6;
+            let balls = random_balls::<D>(&mut seed, count);
+            let origin = std::array::from_fn(|axis| (seed >> (axis * 4)) as i64 % 5);
+            let coverage = best_coverage(&balls, origin).unwrap();
+            let (count, points, closest) = brute_force(&balls, origin);
+
+            assert_eq!(coverage.count, count, "{balls:?}");
+            assert_eq!(expand(&optimal_regions(&balls)), points, "{balls:?}");
+            assert_eq!(coverage.closest, closest, "{balls:?}");
+            assert_eq!(coverage.distance, distance(origin, closest));
+        }
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        cross_check::<1>(0x9e3779b97f4a7c15, 200);
+        cross_check::<2>(0x2545f4914f6cdd1d, 200);
+        cross_check::<3>(0x5851f42d4c957f2d, 100);
+    }
+
+    #[test]
+    fn test_region_display() {
+        let region = Region {
+            min: [1, 2, 3],
+            max: [1, 4, 3],
+        };
+        assert_eq!(region.to_string(), "(1, 2..=4, 3)");
+        assert_eq!(region.volume(), 3);
+    }
+}
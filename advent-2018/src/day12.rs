@@ -1,7 +1,9 @@
 use std::collections::{HashMap, HashSet};
 
+use advent_common::cycle::find_cycle;
 use anyhow::Context;
 use aoc_runner_derive::{aoc, aoc_generator};
+use itertools::Itertools;
 
 #[derive(Debug)]
 struct Input {
@@ -74,27 +76,15 @@ fn part1(input: &Input) -> isize {
 }
 
 #[aoc(day12, part2)]
-fn part2(input: &Input) -> Option<isize> {
-    (1..)
-        .scan(
-            (0, 0, 0, input.initial_state.clone()),
-            |(_, last_growth, last_sum, last_state), generation| {
-                let state = next_generation(last_state, &input.rules);
-                let sum = state.iter().sum::<isize>();
-                let growth = sum - *last_sum;
-                if *last_growth == growth {
-                    None
-                } else {
-                    *last_growth = growth;
-                    *last_sum = sum;
-                    *last_state = state;
-                    Some((generation, growth, sum))
-                }
-            },
-        )
-        .last()
-        .map(|(generation, growth, sum)| {
-            let rest = (50_000_000_000isize - generation) * growth;
-            sum + rest
-        })
+fn part2(input: &Input) -> isize {
+    let cycle = find_cycle(
+        input.initial_state.clone(),
+        |state| next_generation(&state, &input.rules),
+        |state| {
+            let min = state.iter().min().copied().unwrap_or_default();
+            state.iter().map(|pot| pot - min).sorted().collect_vec()
+        },
+        |state| state.iter().sum::<isize>() as i64,
+    );
+    cycle.extrapolate(50_000_000_000) as isize
 }
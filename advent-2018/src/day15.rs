@@ -1,6 +1,7 @@
 use std::{
     cmp::{Ordering, Reverse},
     collections::{BinaryHeap, HashMap, HashSet},
+    fmt,
 };
 
 use aoc_runner_derive::{aoc, aoc_generator};
@@ -23,6 +24,12 @@ impl PartialOrd for Point {
     }
 }
 
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}", self.0, self.1)
+    }
+}
+
 impl Point {
     fn adjacent(self) -> impl Iterator<Item = Point> {
         let Point(x, y) = self;
@@ -42,6 +49,70 @@ enum Race {
     Goblin,
 }
 
+impl fmt::Display for Race {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Race::Elf => write!(f, "E"),
+            Race::Goblin => write!(f, "G"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Event {
+    Move {
+        race: Race,
+        from: Point,
+        to: Point,
+    },
+    Attack {
+        race: Race,
+        from: Point,
+        target: Point,
+        damage: i16,
+        remaining: i16,
+    },
+    Death {
+        race: Race,
+        position: Point,
+        round: usize,
+    },
+    RoundEnd {
+        round: usize,
+        hit_points: Vec<(Race, Point, i16)>,
+    },
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Move { race, from, to } => write!(f, "{race} at {from} moves to {to}"),
+            Event::Attack {
+                race,
+                from,
+                target,
+                damage,
+                remaining,
+            } => write!(
+                f,
+                "{race} at {from} hits {target} for {damage}, leaving {remaining}"
+            ),
+            Event::Death {
+                race,
+                position,
+                round,
+            } => write!(f, "{race} at {position} dies in round {round}"),
+            Event::RoundEnd { round, hit_points } => {
+                write!(f, "After round {round}:")?;
+                for (race, position, hit_points) in hit_points {
+                    write!(f, " {race}({hit_points}) at {position}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Ord, PartialOrd)]
 struct Character {
     position: Point,
@@ -77,9 +148,32 @@ impl Character {
 struct Simulation {
     characters: Vec<Character>,
     grid: HashMap<Point, bool>,
+    rounds: usize,
+    events: Vec<Event>,
 }
 
 impl Simulation {
+    fn new(input: &Input, elf_attack: i16) -> Self {
+        let characters = input
+            .characters
+            .iter()
+            .map(|&c| match c.race {
+                Race::Elf => Character {
+                    attack_power: elf_attack,
+                    ..c
+                },
+                Race::Goblin => c,
+            })
+            .collect();
+
+        Simulation {
+            characters,
+            grid: input.grid.clone(),
+            rounds: 0,
+            events: vec![],
+        }
+    }
+
     fn is_complete(&self) -> bool {
         if let Some(&Character { race, .. }) = self.characters.first() {
             self.characters
@@ -176,20 +270,107 @@ impl Simulation {
 
             let next_move = self.find_move(&self.characters[i]);
             if let Some(point) = next_move {
+                self.events.push(Event::Move {
+                    race: self.characters[i].race,
+                    from: self.characters[i].position,
+                    to: point,
+                });
                 self.characters[i].position = point;
             }
 
             let enemy = self.find_enemy_to_attack(&self.characters[i]);
             if let Some(enemy_index) = enemy {
-                let attack = self.characters[i].attack_power;
-                self.characters[enemy_index].take_hit(attack);
+                let Character {
+                    race,
+                    position,
+                    attack_power,
+                    ..
+                } = self.characters[i];
+                let target = &mut self.characters[enemy_index];
+                target.take_hit(attack_power);
+                self.events.push(Event::Attack {
+                    race,
+                    from: position,
+                    target: target.position,
+                    damage: attack_power,
+                    remaining: target.hit_points.max(0),
+                });
+                if !target.is_alive() {
+                    self.events.push(Event::Death {
+                        race: target.race,
+                        position: target.position,
+                        round: self.rounds + 1,
+                    });
+                }
             }
         }
 
         self.remove_dead();
+        self.rounds += 1;
+        self.characters
+            .sort_by_key(|&Character { position, .. }| position);
+        self.events.push(Event::RoundEnd {
+            round: self.rounds,
+            hit_points: self
+                .characters
+                .iter()
+                .map(|c| (c.race, c.position, c.hit_points))
+                .collect(),
+        });
 
         false
     }
+
+    fn run(&mut self) -> i64 {
+        while !self.tick() {}
+        self.rounds as i64 * self.total_health()
+    }
+
+    fn first_elf_death(&self) -> Option<(usize, Point)> {
+        self.events.iter().find_map(|event| match event {
+            Event::Death {
+                race: Race::Elf,
+                position,
+                round,
+            } => Some((*round, *position)),
+            _ => None,
+        })
+    }
+
+    fn render(&self) -> String {
+        let width = self.grid.keys().map(|p| p.0).max().unwrap_or(-1) + 1;
+        let height = self.grid.keys().map(|p| p.1).max().unwrap_or(-1) + 1;
+        let units = self
+            .characters
+            .iter()
+            .filter(|c| c.is_alive())
+            .map(|c| (c.position, c))
+            .collect::<HashMap<_, _>>();
+
+        (0..height)
+            .map(|y| {
+                let row = (0..width)
+                    .map(|x| {
+                        let point = Point(x, y);
+                        match units.get(&point) {
+                            Some(c) => c.race.to_string(),
+                            None if self.grid.get(&point) == Some(&true) => ".".to_string(),
+                            None => "#".to_string(),
+                        }
+                    })
+                    .collect::<String>();
+                let mut row_units = (0..width)
+                    .filter_map(|x| units.get(&Point(x, y)))
+                    .map(|c| format!("{}({})", c.race, c.hit_points))
+                    .peekable();
+                if row_units.peek().is_some() {
+                    format!("{row}   {}\n", row_units.collect::<Vec<_>>().join(", "))
+                } else {
+                    format!("{row}\n")
+                }
+            })
+            .collect()
+    }
 }
 
 struct Input {
@@ -237,65 +418,198 @@ fn generator(input: &str) -> Input {
     Input { grid, characters }
 }
 
-#[aoc(day15, part1)]
-fn part1(input: &Input) -> i64 {
-    let characters = input.characters.to_vec();
-    let mut simulation = Simulation {
-        grid: input.grid.clone(),
-        characters,
-    };
-    for t in 0.. {
-        if simulation.tick() {
-            return t * simulation.total_health();
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Trial {
+    attack: i16,
+    first_elf_death: Option<(usize, Point)>,
+}
+
+impl fmt::Display for Trial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.first_elf_death {
+            Some((round, position)) => write!(
+                f,
+                "Attack {}: first elf died at {} in round {}",
+                self.attack, position, round
+            ),
+            None => write!(f, "Attack {}: no elves died", self.attack),
+        }
+    }
+}
+
+fn find_elf_power(input: &Input) -> (i64, Vec<Trial>) {
+    let mut trials = vec![];
+    for attack in 4.. {
+        let mut simulation = Simulation::new(input, attack);
+        while !simulation.tick() {
+            if simulation.first_elf_death().is_some() {
+                break;
+            }
+        }
+
+        let first_elf_death = simulation.first_elf_death();
+        trials.push(Trial {
+            attack,
+            first_elf_death,
+        });
+        if first_elf_death.is_none() {
+            let outcome = simulation.rounds as i64 * simulation.total_health();
+            return (outcome, trials);
         }
     }
 
     unreachable!()
 }
 
+#[aoc(day15, part1)]
+fn part1(input: &Input) -> i64 {
+    Simulation::new(input, 3).run()
+}
+
+#[aoc(day15, part1, replay)]
+fn part1_replay(input: &Input) -> String {
+    let mut simulation = Simulation::new(input, 3);
+    let mut transcript = format!("Initially:\n{}", simulation.render());
+    let mut logged = 0;
+    loop {
+        let finished = simulation.tick();
+        for event in &simulation.events[logged..] {
+            transcript += &format!("{event}\n");
+        }
+        logged = simulation.events.len();
+        if finished {
+            break;
+        }
+        transcript += &simulation.render();
+    }
+    transcript
+        + &format!(
+            "Outcome: {}\n",
+            simulation.rounds as i64 * simulation.total_health()
+        )
+}
+
 #[aoc(day15, part2)]
 fn part2(input: &Input) -> i64 {
-    let total_elf_count = input
-        .characters
+    find_elf_power(input).0
+}
+
+#[aoc(day15, part2, trials)]
+fn part2_trials(input: &Input) -> String {
+    let (outcome, trials) = find_elf_power(input);
+    trials
         .iter()
-        .filter(|c| c.race == Race::Elf)
-        .count();
+        .map(|trial| format!("{trial}\n"))
+        .collect::<String>()
+        + &format!("Outcome: {outcome}\n")
+}
 
-    for attack in 4.. {
-        let characters = input
-            .characters
-            .iter()
-            .map(|&c| {
-                if c.race == Race::Elf {
-                    Character {
-                        attack_power: attack,
-                        ..c
-                    }
-                } else {
-                    c
-                }
-            })
-            .collect();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "#######
+#.G...#
+#...EG#
+#.#.#G#
+#..G#E#
+#.....#
+#######";
+
+    #[test]
+    fn test_render_rounds() {
+        let input = generator(EXAMPLE);
+        let mut simulation = Simulation::new(&input, 3);
+        assert_eq!(
+            simulation.render(),
+            "#######
+#.G...#   G(200)
+#...EG#   E(200), G(200)
+#.#.#G#   G(200)
+#..G#E#   G(200), E(200)
+#.....#
+#######
+"
+        );
+
+        simulation.tick();
+        assert_eq!(
+            simulation.render(),
+            "#######
+#..G..#   G(200)
+#...EG#   E(197), G(197)
+#.#G#G#   G(200), G(197)
+#...#E#   E(197)
+#.....#
+#######
+"
+        );
+
+        while !simulation.tick() {}
+        assert_eq!(simulation.rounds, 47);
+        assert_eq!(
+            simulation.render(),
+            "#######
+#G....#   G(200)
+#.G...#   G(131)
+#.#.#G#   G(59)
+#...#.#
+#....G#   G(200)
+#######
+"
+        );
+    }
 
-        let mut simulation = Simulation {
-            grid: input.grid.clone(),
-            characters,
-        };
-        for t in 0.. {
-            if simulation.tick() {
-                let elf_count = simulation
-                    .characters
-                    .iter()
-                    .filter(|c| c.race == Race::Elf)
-                    .count();
-                if elf_count == total_elf_count {
-                    return t * simulation.total_health();
-                } else {
-                    break;
-                }
+    #[test]
+    fn test_event_log() {
+        let input = generator(EXAMPLE);
+        let mut simulation = Simulation::new(&input, 3);
+        simulation.tick();
+
+        assert_eq!(
+            simulation.events[0],
+            Event::Move {
+                race: Race::Goblin,
+                from: Point(2, 1),
+                to: Point(3, 1),
             }
-        }
+        );
+        assert_eq!(
+            simulation.events[1].to_string(),
+            "E at 4,2 hits 5,2 for 3, leaving 197"
+        );
+        assert!(matches!(
+            simulation.events.last(),
+            Some(Event::RoundEnd { round: 1, .. })
+        ));
+
+        while !simulation.tick() {}
+        let deaths = simulation
+            .events
+            .iter()
+            .filter(|event| matches!(event, Event::Death { .. }))
+            .count();
+        assert_eq!(deaths, 2);
+        assert!(simulation.first_elf_death().is_some());
     }
 
-    unreachable!()
+    #[test]
+    fn test_part1() {
+        assert_eq!(part1(&generator(EXAMPLE)), 27730);
+        assert!(part1_replay(&generator(EXAMPLE)).ends_with("Outcome: 27730\n"));
+    }
+
+    #[test]
+    fn test_part2() {
+        let input = generator(EXAMPLE);
+        let (outcome, trials) = find_elf_power(&input);
+        assert_eq!(outcome, 4988);
+        assert_eq!(trials.len(), 12);
+        assert_eq!(trials.last().unwrap().attack, 15);
+        assert!(
+            trials[..11]
+                .iter()
+                .all(|trial| trial.first_elf_death.is_some())
+        );
+    }
 }
@@ -1,8 +1,6 @@
-use std::{
-    collections::HashMap,
-    fmt::{self, Display},
-};
+use std::fmt::{self, Display};
 
+use advent_common::cycle::find_cycle;
 use anyhow::bail;
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
@@ -119,6 +117,13 @@ impl Grid {
 
         Grid(next)
     }
+
+    fn resource_value(&self) -> usize {
+        let Grid(cells) = self;
+        let counts = cells.iter().flatten().counts();
+        counts.get(&Cell::Tree).copied().unwrap_or_default()
+            * counts.get(&Cell::Lumberyard).copied().unwrap_or_default()
+    }
 }
 
 #[aoc_generator(day18)]
@@ -150,36 +155,11 @@ fn part1(input: &Grid) -> usize {
         grid = grid.next();
     }
 
-    let Grid(cells) = grid;
-    let counts = cells.iter().flatten().counts();
-    counts[&Cell::Tree] * counts[&Cell::Lumberyard]
+    grid.resource_value()
 }
 
 #[aoc(day18, part2)]
 fn part2(input: &Grid) -> usize {
-    let mut grid = input.clone();
-    let mut seen = HashMap::new();
-    let mut cycle = None;
-
-    for i in 0.. {
-        if let Some(&prev) = seen.get(&grid) {
-            cycle = Some((prev, i));
-            break;
-        }
-
-        seen.insert(grid.clone(), i);
-        grid = grid.next();
-    }
-
-    let (prev, i) = cycle.unwrap();
-    let cycle_len = i - prev;
-    let remaining = (1_000_000_000 - i) % cycle_len;
-
-    for _ in 0..remaining {
-        grid = grid.next();
-    }
-
-    let Grid(cells) = grid;
-    let counts = cells.iter().flatten().counts();
-    counts[&Cell::Tree] * counts[&Cell::Lumberyard]
+    let cycle = find_cycle(input.clone(), Grid::next, Grid::clone, Grid::resource_value);
+    *cycle.at(1_000_000_000)
 }
@@ -1,146 +1,302 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 
-use aoc_runner_derive::aoc;
+use anyhow::{bail, ensure};
+use aoc_runner_derive::{aoc, aoc_generator};
 
 type Point = (i32, i32);
 
-#[derive(Debug)]
-struct Map {
-    doors: HashSet<(Point, Point)>,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    North,
+    South,
+    East,
+    West,
 }
 
-impl Map {
-    fn from_regex(regex: &str) -> Self {
-        let mut doors = HashSet::new();
-        let mut positions = vec![(0, 0)];
-        let mut stack = Vec::new();
+impl Direction {
+    fn from_char(ch: char) -> Option<Self> {
+        match ch {
+            'N' => Some(Direction::North),
+            'S' => Some(Direction::South),
+            'E' => Some(Direction::East),
+            'W' => Some(Direction::West),
+            _ => None,
+        }
+    }
+
+    fn to_char(self) -> char {
+        match self {
+            Direction::North => 'N',
+            Direction::South => 'S',
+            Direction::East => 'E',
+            Direction::West => 'W',
+        }
+    }
+
+    fn opposite(self) -> Self {
+        match self {
+            Direction::North => Direction::South,
+            Direction::South => Direction::North,
+            Direction::East => Direction::West,
+            Direction::West => Direction::East,
+        }
+    }
 
-        for ch in regex.chars() {
+    fn step(self, (x, y): Point) -> Point {
+        match self {
+            Direction::North => (x, y - 1),
+            Direction::South => (x, y + 1),
+            Direction::East => (x + 1, y),
+            Direction::West => (x - 1, y),
+        }
+    }
+}
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::North,
+    Direction::South,
+    Direction::East,
+    Direction::West,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Term {
+    Step(Direction),
+    Branch(Vec<Route>),
+}
+
+type Route = Vec<Term>;
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+}
+
+impl Parser<'_> {
+    fn route(&mut self) -> anyhow::Result<Route> {
+        let mut route = vec![];
+        while let Some(&(index, ch)) = self.chars.peek() {
             match ch {
-                '^' | '$' => {}
-                'N' => {
-                    let mut new_positions = Vec::new();
-                    for &(x, y) in &positions {
-                        let next = (x, y - 1);
-                        let mut door = ((x, y), next);
-                        let (from, to) = door;
-                        if from > to {
-                            door = (to, from);
-                        }
-                        doors.insert(door);
-                        new_positions.push(next);
-                    }
-                    positions = new_positions;
-                }
-                'S' => {
-                    let mut new_positions = Vec::new();
-                    for &(x, y) in &positions {
-                        let next = (x, y + 1);
-                        let mut door = ((x, y), next);
-                        let (from, to) = door;
-                        if from > to {
-                            door = (to, from);
-                        }
-                        doors.insert(door);
-                        new_positions.push(next);
-                    }
-                    positions = new_positions;
-                }
-                'E' => {
-                    let mut new_positions = Vec::new();
-                    for &(x, y) in &positions {
-                        let next = (x + 1, y);
-                        let mut door = ((x, y), next);
-                        let (from, to) = door;
-                        if from > to {
-                            door = (to, from);
-                        }
-                        doors.insert(door);
-                        new_positions.push(next);
-                    }
-                    positions = new_positions;
-                }
-                'W' => {
-                    let mut new_positions = Vec::new();
-                    for &(x, y) in &positions {
-                        let next = (x - 1, y);
-                        let mut door = ((x, y), next);
-                        let (from, to) = door;
-                        if from > to {
-                            door = (to, from);
-                        }
-                        doors.insert(door);
-                        new_positions.push(next);
-                    }
-                    positions = new_positions;
-                }
                 '(' => {
-                    stack.push((positions.clone(), HashSet::new()));
+                    self.chars.next();
+                    route.push(Term::Branch(self.branches(index)?));
                 }
-                '|' => {
-                    let (branch_start, branch_ends) = stack.last_mut().unwrap();
-                    for pos in &positions {
-                        branch_ends.insert(*pos);
+                '|' | ')' | '$' => break,
+                _ => match Direction::from_char(ch) {
+                    Some(direction) => {
+                        self.chars.next();
+                        route.push(Term::Step(direction));
                     }
-                    positions = branch_start.clone();
-                }
-                ')' => {
-                    let (_, mut branch_ends) = stack.pop().unwrap();
-                    for pos in &positions {
-                        branch_ends.insert(*pos);
-                    }
-                    positions = branch_ends.into_iter().collect();
-                }
-                _ => panic!("unexpected character: {}", ch),
+                    None => bail!("Unexpected character {ch} at position {index}"),
+                },
             }
         }
+        Ok(route)
+    }
 
-        Map { doors }
+    fn branches(&mut self, open: usize) -> anyhow::Result<Vec<Route>> {
+        let mut branches = vec![self.route()?];
+        loop {
+            match self.chars.next() {
+                Some((_, '|')) => branches.push(self.route()?),
+                Some((_, ')')) => return Ok(branches),
+                _ => bail!("Parenthesis opened at position {open} is never closed"),
+            }
+        }
     }
+}
 
-    fn find_distances(&self) -> HashMap<Point, usize> {
-        let mut distances = HashMap::new();
-        let mut queue = VecDeque::new();
+fn parse(regex: &str) -> anyhow::Result<Route> {
+    let regex = regex.trim();
+    let mut parser = Parser {
+        chars: regex.char_indices().peekable(),
+    };
+    ensure!(
+        parser.chars.next().is_some_and(|(_, ch)| ch == '^'),
+        "Route must start with ^"
+    );
 
-        distances.insert((0, 0), 0);
-        queue.push_back((0, 0));
+    let route = parser.route()?;
+    match parser.chars.next() {
+        Some((index, '$')) => {
+            ensure!(
+                index + 1 == regex.len(),
+                "Unexpected input after $ at position {}",
+                index + 1
+            );
+            Ok(route)
+        }
+        Some((index, ')')) => bail!("Parenthesis closed at position {index} was never opened"),
+        Some((index, '|')) => bail!("Branch at position {index} is outside any parentheses"),
+        _ => bail!("Route must end with $"),
+    }
+}
 
-        while let Some(pos) = queue.pop_front() {
-            let dist = distances[&pos];
+fn door(from: Point, to: Point) -> (Point, Point) {
+    if from < to { (from, to) } else { (to, from) }
+}
 
-            let (x, y) = pos;
-            let neighbors = [(x, y - 1), (x, y + 1), (x + 1, y), (x - 1, y)];
+#[derive(Debug)]
+struct Map {
+    doors: HashSet<(Point, Point)>,
+    rooms: HashSet<Point>,
+}
 
-            for next in &neighbors {
-                let mut door = (pos, *next);
-                let (from, to) = door;
-                if from > to {
-                    door = (to, from);
-                }
+impl Map {
+    fn from_route(route: &Route) -> Self {
+        let mut map = Map {
+            doors: HashSet::new(),
+            rooms: HashSet::from([(0, 0)]),
+        };
+        map.walk(route, BTreeSet::from([(0, 0)]));
+        map
+    }
+
+    fn walk(&mut self, route: &Route, mut positions: BTreeSet<Point>) -> BTreeSet<Point> {
+        for term in route {
+            positions = match term {
+                Term::Step(direction) => positions
+                    .into_iter()
+                    .map(|from| {
+                        let to = direction.step(from);
+                        self.doors.insert(door(from, to));
+                        self.rooms.insert(to);
+                        to
+                    })
+                    .collect(),
+                Term::Branch(branches) => branches
+                    .iter()
+                    .flat_map(|branch| self.walk(branch, positions.clone()))
+                    .collect(),
+            };
+        }
+        positions
+    }
 
-                if self.doors.contains(&door) && !distances.contains_key(next) {
-                    distances.insert(*next, dist + 1);
-                    queue.push_back(*next);
+    fn has_door(&self, from: Point, to: Point) -> bool {
+        self.doors.contains(&door(from, to))
+    }
+
+    fn explore(&self) -> HashMap<Point, (usize, Option<Direction>)> {
+        let mut rooms = HashMap::from([((0, 0), (0, None))]);
+        let mut queue = VecDeque::from([(0, 0)]);
+
+        while let Some(pos) = queue.pop_front() {
+            let dist = rooms[&pos].0;
+            for direction in DIRECTIONS {
+                let next = direction.step(pos);
+                if self.has_door(pos, next) && !rooms.contains_key(&next) {
+                    rooms.insert(next, (dist + 1, Some(direction)));
+                    queue.push_back(next);
                 }
             }
         }
 
-        distances
+        rooms
+    }
+
+    fn find_distances(&self) -> HashMap<Point, usize> {
+        self.explore()
+            .into_iter()
+            .map(|(pos, (dist, _))| (pos, dist))
+            .collect()
+    }
+
+    fn shortest_path(&self, room: Point) -> Option<Vec<Direction>> {
+        let rooms = self.explore();
+        let mut path = vec![];
+        let mut pos = room;
+        while let (_, Some(direction)) = rooms.get(&pos)? {
+            path.push(*direction);
+            pos = direction.opposite().step(pos);
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    fn histogram(&self) -> BTreeMap<usize, usize> {
+        let mut histogram = BTreeMap::new();
+        for dist in self.find_distances().into_values() {
+            *histogram.entry(dist).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    fn render(&self) -> String {
+        let min_x = self.rooms.iter().map(|&(x, _)| x).min().unwrap();
+        let max_x = self.rooms.iter().map(|&(x, _)| x).max().unwrap();
+        let min_y = self.rooms.iter().map(|&(_, y)| y).min().unwrap();
+        let max_y = self.rooms.iter().map(|&(_, y)| y).max().unwrap();
+
+        let mut lines = vec![];
+        for y in min_y..=max_y {
+            let mut walls = String::from("#");
+            let mut rooms = String::from("#");
+            for x in min_x..=max_x {
+                rooms.push(match (x, y) {
+                    (0, 0) => 'X',
+                    pos if self.rooms.contains(&pos) => '.',
+                    _ => '#',
+                });
+                rooms.push(if self.has_door((x, y), (x + 1, y)) {
+                    '|'
+                } else {
+                    '#'
+                });
+                walls.push(if self.has_door((x, y), (x, y - 1)) {
+                    '-'
+                } else {
+                    '#'
+                });
+                walls.push('#');
+            }
+            lines.push(walls);
+            lines.push(rooms);
+        }
+        lines.push("#".repeat(2 * (max_x - min_x + 1) as usize + 1));
+        lines.join("\n")
     }
 }
 
+#[aoc_generator(day20)]
+fn generator(input: &str) -> anyhow::Result<Map> {
+    Ok(Map::from_route(&parse(input)?))
+}
+
 #[aoc(day20, part1)]
-fn part1(input: &str) -> Option<usize> {
-    let map = Map::from_regex(input);
+fn part1(map: &Map) -> Option<usize> {
+    map.find_distances().into_values().max()
+}
+
+#[aoc(day20, part1, render)]
+fn part1_render(map: &Map) -> String {
+    map.render()
+}
+
+#[aoc(day20, part1, path)]
+fn part1_path(map: &Map) -> Option<String> {
     let distances = map.find_distances();
-    distances.values().max().cloned()
+    let (&room, _) = distances
+        .iter()
+        .max_by_key(|&(&(x, y), &dist)| (dist, y, x))?;
+    let path = map.shortest_path(room)?;
+    Some(path.into_iter().map(Direction::to_char).collect())
 }
 
 #[aoc(day20, part2)]
-fn part2(input: &str) -> usize {
-    let map = Map::from_regex(input);
-    let distances = map.find_distances();
-    distances.values().filter(|&&d| d >= 1000).count()
+fn part2(map: &Map) -> usize {
+    map.find_distances()
+        .into_values()
+        .filter(|&d| d >= 1000)
+        .count()
+}
+
+#[aoc(day20, part2, histogram)]
+fn part2_histogram(map: &Map) -> String {
+    map.histogram()
+        .into_iter()
+        .map(|(dist, rooms)| format!("{dist}: {rooms}"))
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 #[cfg(test)]
@@ -150,30 +306,87 @@ mod tests {
     #[test]
     fn test_example1() {
         let input = "^WNE$";
-        assert_eq!(part1(input).unwrap(), 3);
+        assert_eq!(part1(&generator(input).unwrap()).unwrap(), 3);
     }
 
     #[test]
     fn test_example2() {
         let input = "^ENWWW(NEEE|SSE(EE|N))$";
-        assert_eq!(part1(input).unwrap(), 10);
+        assert_eq!(part1(&generator(input).unwrap()).unwrap(), 10);
     }
 
     #[test]
     fn test_example3() {
         let input = "^ENNWSWW(NEWS|)SSSEEN(WNSE|)EE(SWEN|)NNN$";
-        assert_eq!(part1(input).unwrap(), 18);
+        assert_eq!(part1(&generator(input).unwrap()).unwrap(), 18);
     }
 
     #[test]
     fn test_example4() {
         let input = "^ESSWWN(E|NNENN(EESS(WNSE|)SSS|WWWSSSSE(SW|NNNE)))$";
-        assert_eq!(part1(input).unwrap(), 23);
+        assert_eq!(part1(&generator(input).unwrap()).unwrap(), 23);
     }
 
     #[test]
     fn test_example5() {
         let input = "^WSSEESWWWNW(S|NENNEEEENN(ESSSSW(NWSW|SSEN)|WSWWN(E|WWS(E|SS))))$";
-        assert_eq!(part1(input).unwrap(), 31);
+        assert_eq!(part1(&generator(input).unwrap()).unwrap(), 31);
+    }
+
+    #[test]
+    fn test_parse_tree() {
+        use Direction::*;
+        assert_eq!(
+            parse("^N(E|)W$").unwrap(),
+            vec![
+                Term::Step(North),
+                Term::Branch(vec![vec![Term::Step(East)], vec![]]),
+                Term::Step(West),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("^N(E|W$").is_err());
+        assert!(parse("^NE)W$").is_err());
+        assert!(parse("^N|E$").is_err());
+        assert!(parse("^NXE$").is_err());
+        assert!(parse("NE$").is_err());
+        assert!(parse("^NE").is_err());
+        assert!(parse("^NE$N").is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let map = generator("^ENWWW(NEEE|SSE(EE|N))$").unwrap();
+        assert_eq!(
+            map.render(),
+            "#########
+#.|.|.|.#
+#-#######
+#.|.|.|.#
+#-#####-#
+#.#.#X|.#
+#-#-#####
+#.|.|.|.#
+#########"
+        );
+    }
+
+    #[test]
+    fn test_shortest_path_and_histogram() {
+        let map = generator("^ENWWW(NEEE|SSE(EE|N))$").unwrap();
+        let path = map.shortest_path((1, -2)).unwrap();
+        assert_eq!(
+            path.into_iter().map(Direction::to_char).collect::<String>(),
+            "ENWWWNEEE"
+        );
+        assert_eq!(map.shortest_path((5, 5)), None);
+
+        let histogram = map.histogram();
+        assert_eq!(histogram.values().sum::<usize>(), map.rooms.len());
+        assert_eq!(histogram.keys().last(), Some(&10));
+        assert_eq!(histogram[&0], 1);
     }
 }
@@ -1,9 +1,4 @@
-use std::{
-    cmp::{Ordering, Reverse},
-    collections::HashSet,
-    str::FromStr,
-    sync::LazyLock,
-};
+use std::{cmp::Reverse, collections::HashSet, fmt, str::FromStr, sync::LazyLock};
 
 use anyhow::{Result, anyhow};
 use aoc_runner_derive::{aoc, aoc_generator};
@@ -148,216 +143,269 @@ fn generator(input: &str) -> Result<Armies> {
     })
 }
 
-fn simulate_combat(armies: Armies) -> usize {
-    let (_, count) = simulate_combat_with_boost(armies, 0);
-    count
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Army {
+    ImmuneSystem,
+    Infection,
 }
 
-fn simulate_combat_with_boost(mut armies: Armies, boost: usize) -> (bool, usize) {
-    for group in &mut armies.immune_system {
-        group.attack_damage += boost;
+impl fmt::Display for Army {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Army::ImmuneSystem => write!(f, "Immune System"),
+            Army::Infection => write!(f, "Infection"),
+        }
     }
+}
 
-    loop {
-        if armies.immune_system.is_empty() {
-            return (false, armies.infection.iter().map(|g| g.units).sum());
-        }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    ImmuneSystemWins(usize),
+    InfectionWins(usize),
+    Stalemate,
+}
 
-        if armies.infection.is_empty() {
-            return (true, armies.immune_system.iter().map(|g| g.units).sum());
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::ImmuneSystemWins(units) => {
+                write!(f, "Immune System wins with {units} units left")
+            }
+            Outcome::InfectionWins(units) => write!(f, "Infection wins with {units} units left"),
+            Outcome::Stalemate => write!(f, "Stalemate"),
         }
+    }
+}
 
-        let mut groups: Vec<(bool, usize)> = Vec::new();
-
-        for (i, _) in armies.immune_system.iter().enumerate() {
-            groups.push((true, i));
-        }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Attack {
+    army: Army,
+    attacker: usize,
+    defender: usize,
+    killed: usize,
+}
 
-        for (i, _) in armies.infection.iter().enumerate() {
-            groups.push((false, i));
-        }
+impl fmt::Display for Attack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} group {} attacks defending group {}, killing {} units",
+            self.army, self.attacker, self.defender, self.killed
+        )
+    }
+}
 
-        groups.sort_by(|&(a_is_immune, a_idx), &(b_is_immune, b_idx)| {
-            let a_group = if a_is_immune {
-                &armies.immune_system[a_idx]
-            } else {
-                &armies.infection[a_idx]
-            };
+#[derive(Debug, Clone, Default)]
+struct Round {
+    attacks: Vec<Attack>,
+    immune_system: usize,
+    infection: usize,
+}
 
-            let b_group = if b_is_immune {
-                &armies.immune_system[b_idx]
-            } else {
-                &armies.infection[b_idx]
-            };
+#[derive(Debug, Clone)]
+struct Battle {
+    outcome: Outcome,
+    rounds: Vec<Round>,
+}
 
-            let power_cmp = b_group.effective_power().cmp(&a_group.effective_power());
-            if power_cmp != Ordering::Equal {
-                power_cmp
-            } else {
-                b_group.initiative.cmp(&a_group.initiative)
+impl fmt::Display for Battle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (number, round) in self.rounds.iter().enumerate() {
+            writeln!(f, "Round {}:", number + 1)?;
+            for attack in &round.attacks {
+                writeln!(f, "  {attack}")?;
             }
-        });
+            writeln!(
+                f,
+                "  Immune System: {} units, Infection: {} units",
+                round.immune_system, round.infection
+            )?;
+        }
+        writeln!(f, "{}", self.outcome)
+    }
+}
 
-        let mut targets: Vec<Option<(bool, usize)>> = vec![None; groups.len()];
-        let mut targeted = HashSet::new();
+struct Fighter {
+    army: Army,
+    number: usize,
+    group: Group,
+}
 
-        for (attacker_idx, &(attacker_is_immune, attacker_group_idx)) in groups.iter().enumerate() {
-            let attacker = if attacker_is_immune {
-                &armies.immune_system[attacker_group_idx]
-            } else {
-                &armies.infection[attacker_group_idx]
+fn simulate(armies: &Armies, boost: usize) -> Battle {
+    let mut fighters = armies
+        .immune_system
+        .iter()
+        .map(|group| (Army::ImmuneSystem, group))
+        .chain(
+            armies
+                .infection
+                .iter()
+                .map(|group| (Army::Infection, group)),
+        )
+        .scan((0, 0), |(immune, infection), (army, group)| {
+            let count = match army {
+                Army::ImmuneSystem => immune,
+                Army::Infection => infection,
             };
-
-            let mut best_target = None;
-            let mut best_damage = 0;
-            let mut best_power = 0;
-            let mut best_initiative = 0;
-
-            for &(defender_is_immune, defender_group_idx) in &groups {
-                if attacker_is_immune == defender_is_immune
-                    || targeted.contains(&(defender_is_immune, defender_group_idx))
-                {
-                    continue;
-                }
-
-                let defender = if defender_is_immune {
-                    &armies.immune_system[defender_group_idx]
-                } else {
-                    &armies.infection[defender_group_idx]
-                };
-
-                let damage = attacker.damage_to(defender);
-                if damage == 0 {
-                    continue;
-                }
-
-                if damage > best_damage
-                    || (damage == best_damage && defender.effective_power() > best_power)
-                    || (damage == best_damage
-                        && defender.effective_power() == best_power
-                        && defender.initiative > best_initiative)
-                {
-                    best_target = Some((defender_is_immune, defender_group_idx));
-                    best_damage = damage;
-                    best_power = defender.effective_power();
-                    best_initiative = defender.initiative;
-                }
+            *count += 1;
+            let mut group = group.clone();
+            if army == Army::ImmuneSystem {
+                group.attack_damage += boost;
             }
+            Some(Fighter {
+                army,
+                number: *count,
+                group,
+            })
+        })
+        .collect::<Vec<_>>();
 
-            if let Some(target) = best_target {
-                targets[attacker_idx] = Some(target);
-                targeted.insert(target);
-            }
-        }
+    let units = |fighters: &[Fighter], army: Army| -> usize {
+        fighters
+            .iter()
+            .filter(|fighter| fighter.army == army)
+            .map(|fighter| fighter.group.units)
+            .sum()
+    };
 
-        let mut attack_order: Vec<usize> = (0..groups.len()).collect();
-        attack_order.sort_by_key(|&idx| {
-            let (is_immune, group_idx) = groups[idx];
-            let init = if is_immune {
-                armies.immune_system[group_idx].initiative
+    let mut rounds = Vec::new();
+    loop {
+        let immune_system = units(&fighters, Army::ImmuneSystem);
+        let infection = units(&fighters, Army::Infection);
+        if immune_system == 0 || infection == 0 {
+            let outcome = if infection == 0 {
+                Outcome::ImmuneSystemWins(immune_system)
             } else {
-                armies.infection[group_idx].initiative
+                Outcome::InfectionWins(infection)
             };
+            return Battle { outcome, rounds };
+        }
 
-            Reverse(init)
+        let mut selection_order = (0..fighters.len())
+            .filter(|&i| fighters[i].group.units > 0)
+            .collect::<Vec<_>>();
+        selection_order.sort_by_key(|&i| {
+            let group = &fighters[i].group;
+            Reverse((group.effective_power(), group.initiative))
         });
 
-        let mut any_killed = false;
-
-        for &attacker_idx in &attack_order {
-            if let Some((target_is_immune, target_group_idx)) = targets[attacker_idx] {
-                let (attacker_is_immune, attacker_group_idx) = groups[attacker_idx];
-
-                let attacker_units = if attacker_is_immune {
-                    armies.immune_system[attacker_group_idx].units
-                } else {
-                    armies.infection[attacker_group_idx].units
-                };
-
-                if attacker_units == 0 {
-                    continue;
-                }
-
-                let damage = {
-                    let attacker = if attacker_is_immune {
-                        &armies.immune_system[attacker_group_idx]
-                    } else {
-                        &armies.infection[attacker_group_idx]
-                    };
-
-                    let defender = if target_is_immune {
-                        &armies.immune_system[target_group_idx]
-                    } else {
-                        &armies.infection[target_group_idx]
-                    };
-
-                    attacker.damage_to(defender)
-                };
-
-                let units_before = if target_is_immune {
-                    armies.immune_system[target_group_idx].units
-                } else {
-                    armies.infection[target_group_idx].units
-                };
-
-                if target_is_immune {
-                    armies.immune_system[target_group_idx].take_damage(damage);
-                } else {
-                    armies.infection[target_group_idx].take_damage(damage);
-                }
+        let mut targets = vec![None; fighters.len()];
+        let mut targeted = HashSet::new();
+        for &attacker in &selection_order {
+            let target = selection_order
+                .iter()
+                .copied()
+                .filter(|&defender| {
+                    fighters[defender].army != fighters[attacker].army
+                        && !targeted.contains(&defender)
+                })
+                .map(|defender| {
+                    let group = &fighters[defender].group;
+                    let damage = fighters[attacker].group.damage_to(group);
+                    (damage, group.effective_power(), group.initiative, defender)
+                })
+                .filter(|&(damage, ..)| damage > 0)
+                .max();
+
+            if let Some((.., defender)) = target {
+                targets[attacker] = Some(defender);
+                targeted.insert(defender);
+            }
+        }
 
-                let units_after = if target_is_immune {
-                    armies.immune_system[target_group_idx].units
-                } else {
-                    armies.infection[target_group_idx].units
-                };
+        let mut attack_order = selection_order;
+        attack_order.sort_by_key(|&i| Reverse(fighters[i].group.initiative));
 
-                if units_after < units_before {
-                    any_killed = true;
-                }
+        let mut round = Round::default();
+        for attacker in attack_order {
+            let Some(defender) = targets[attacker] else {
+                continue;
+            };
+            if fighters[attacker].group.units == 0 {
+                continue;
             }
+
+            let damage = fighters[attacker]
+                .group
+                .damage_to(&fighters[defender].group);
+            let before = fighters[defender].group.units;
+            fighters[defender].group.take_damage(damage);
+            round.attacks.push(Attack {
+                army: fighters[attacker].army,
+                attacker: fighters[attacker].number,
+                defender: fighters[defender].number,
+                killed: before - fighters[defender].group.units,
+            });
         }
 
-        armies.immune_system.retain(|g| g.units > 0);
-        armies.infection.retain(|g| g.units > 0);
+        let any_killed = round.attacks.iter().any(|attack| attack.killed > 0);
+        round.immune_system = units(&fighters, Army::ImmuneSystem);
+        round.infection = units(&fighters, Army::Infection);
+        rounds.push(round);
 
         if !any_killed {
-            return (false, 0);
+            return Battle {
+                outcome: Outcome::Stalemate,
+                rounds,
+            };
         }
     }
 }
 
-#[aoc(day24, part1)]
-fn part1(input: &Armies) -> usize {
-    simulate_combat(input.clone())
+fn minimal_boost(armies: &Armies) -> Result<(usize, Battle)> {
+    if let Some(number) = armies.infection.iter().position(|defender| {
+        armies
+            .immune_system
+            .iter()
+            .all(|attacker| defender.immunities.contains(&attacker.attack_type))
+    }) {
+        anyhow::bail!(
+            "Infection group {} is immune to every immune system attack",
+            number + 1
+        );
+    }
+
+    // From this boost on, one unit of any immune group out-damages the whole infection's health.
+    let health = armies
+        .infection
+        .iter()
+        .map(|group| group.units * group.hit_points)
+        .sum::<usize>();
+    let weakest = armies
+        .immune_system
+        .iter()
+        .map(|group| group.attack_damage)
+        .min()
+        .unwrap_or(0);
+    let limit = health.saturating_sub(weakest);
+
+    (0..=limit)
+        .map(|boost| (boost, simulate(armies, boost)))
+        .find(|(_, battle)| matches!(battle.outcome, Outcome::ImmuneSystemWins(_)))
+        .ok_or_else(|| anyhow!("No boost up to {} lets the immune system win", limit))
 }
 
-#[aoc(day24, part2)]
-fn part2(input: &Armies) -> usize {
-    let mut low = 0;
-    let mut high = 10000;
-
-    while high < 100000 {
-        let (immune_won, _) = simulate_combat_with_boost(input.clone(), high);
-        if immune_won {
-            break;
-        }
-        high *= 2;
+#[aoc(day24, part1)]
+fn part1(input: &Armies) -> Result<usize> {
+    match simulate(input, 0).outcome {
+        Outcome::ImmuneSystemWins(units) | Outcome::InfectionWins(units) => Ok(units),
+        Outcome::Stalemate => anyhow::bail!("The battle ends in a stalemate"),
     }
+}
 
-    while low < high {
-        let mid = (low + high) / 2;
-        let (immune_won, units) = simulate_combat_with_boost(input.clone(), mid);
-
-        if immune_won && units > 0 {
-            high = mid;
-        } else {
-            low = mid + 1;
-        }
+#[aoc(day24, part2)]
+fn part2(input: &Armies) -> Result<usize> {
+    let (_, battle) = minimal_boost(input)?;
+    match battle.outcome {
+        Outcome::ImmuneSystemWins(units) => Ok(units),
+        outcome => Err(anyhow!("Unexpected outcome: {}", outcome)),
     }
+}
 
-    let (_, units) = simulate_combat_with_boost(input.clone(), low);
-    units
+#[aoc(day24, part2, log)]
+fn part2_log(input: &Armies) -> Result<String> {
+    let (boost, battle) = minimal_boost(input)?;
+    Ok(format!("Boost {boost}\n{battle}"))
 }
 
 #[cfg(test)]
@@ -539,8 +587,8 @@ Infection:
     #[test]
     fn test_combat_example() {
         let armies = generator(EXAMPLE_INPUT).unwrap();
-        let result = simulate_combat(armies);
-        assert_eq!(result, 5216);
+        let battle = simulate(&armies, 0);
+        assert_eq!(battle.outcome, Outcome::InfectionWins(5216));
     }
 
     #[test]
@@ -554,26 +602,104 @@ Infection:
     }
 
     #[test]
-    fn test_detailed_combat_simulation() {
+    fn test_boost_combat() {
         let armies = generator(EXAMPLE_INPUT).unwrap();
-        let result = simulate_combat(armies);
-        assert_eq!(result, 5216);
+
+        let battle = simulate(&armies, 1570);
+        assert_eq!(battle.outcome, Outcome::ImmuneSystemWins(51));
     }
 
     #[test]
-    fn test_boost_combat() {
+    fn test_part2() {
         let armies = generator(EXAMPLE_INPUT).unwrap();
 
-        let (immune_won, units) = simulate_combat_with_boost(armies, 1570);
-        assert!(immune_won);
-        assert_eq!(units, 51);
+        let result = part2(&armies).unwrap();
+        assert_eq!(result, 51);
+
+        let (boost, _) = minimal_boost(&armies).unwrap();
+        assert_eq!(boost, 1570);
+        assert_ne!(
+            simulate(&armies, 1569).outcome,
+            Outcome::ImmuneSystemWins(51)
+        );
     }
 
     #[test]
-    fn test_part2() {
+    fn test_round_log() {
         let armies = generator(EXAMPLE_INPUT).unwrap();
+        let battle = simulate(&armies, 0);
 
-        let result = part2(&armies);
-        assert_eq!(result, 51);
+        let first = battle.rounds[0]
+            .attacks
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            first,
+            [
+                "Infection group 2 attacks defending group 2, killing 84 units",
+                "Immune System group 2 attacks defending group 1, killing 4 units",
+                "Immune System group 1 attacks defending group 2, killing 51 units",
+                "Infection group 1 attacks defending group 1, killing 17 units",
+            ]
+        );
+        assert_eq!(battle.rounds[0].immune_system, 905);
+        assert_eq!(battle.rounds[0].infection, 797 + 4434);
+        assert_eq!(battle.rounds.len(), 8);
+        assert!(
+            battle
+                .to_string()
+                .ends_with("Infection wins with 5216 units left\n")
+        );
+    }
+
+    #[test]
+    fn test_stalemate() {
+        let armies = generator(
+            "Immune System:
+10 units each with 10 hit points (immune to fire) with an attack that does 5 cold damage at initiative 2
+
+Infection:
+10 units each with 10 hit points (immune to cold) with an attack that does 5 fire damage at initiative 1",
+        )
+        .unwrap();
+
+        let battle = simulate(&armies, 0);
+        assert_eq!(battle.outcome, Outcome::Stalemate);
+        assert_eq!(battle.rounds.len(), 1);
+        assert!(part1(&armies).is_err());
+        assert!(
+            minimal_boost(&armies)
+                .unwrap_err()
+                .to_string()
+                .contains("Infection group 1 is immune")
+        );
+
+        let armies = generator(
+            "Immune System:
+10 units each with 10 hit points (immune to fire) with an attack that does 0 cold damage at initiative 2
+
+Infection:
+10 units each with 10 hit points with an attack that does 1 fire damage at initiative 1",
+        )
+        .unwrap();
+        assert_eq!(simulate(&armies, 0).outcome, Outcome::Stalemate);
+        assert_eq!(minimal_boost(&armies).unwrap().0, 1);
+    }
+
+    #[test]
+    fn test_boost_beyond_single_attack() {
+        let armies = generator(
+            "Immune System:
+10 units each with 1 hit points with an attack that does 1 fire damage at initiative 1
+
+Infection:
+5 units each with 10 hit points with an attack that does 1 fire damage at initiative 2",
+        )
+        .unwrap();
+
+        let (boost, battle) = minimal_boost(&armies).unwrap();
+        assert_eq!(boost, 5);
+        assert_eq!(battle.outcome, Outcome::ImmuneSystemWins(2));
     }
 }
@@ -1,5 +1,6 @@
 use aoc_runner_derive::aoc_lib;
 
+mod coverage;
 mod vm;
 
 mod day1;
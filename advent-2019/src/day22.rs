@@ -1,6 +1,7 @@
 use std::str::FromStr;
 
-use anyhow::{Result, bail};
+use advent_common::modular::{mod_inverse, mul_mod, pow_mod};
+use anyhow::{Context, Result, bail};
 use aoc_runner_derive::{aoc, aoc_generator};
 
 #[derive(Debug, Clone)]
@@ -37,7 +38,7 @@ fn part1(instructions: &[Instruction]) -> Result<usize> {
     const CARD: i64 = 2019;
 
     let (a, b) = compose_operations(instructions, DECK_SIZE);
-    let position = mod_add(mod_mul(a, CARD, DECK_SIZE), b, DECK_SIZE);
+    let position = (mul_mod(a, CARD, DECK_SIZE) + b) % DECK_SIZE;
 
     Ok(position as usize)
 }
@@ -45,107 +46,41 @@ fn part1(instructions: &[Instruction]) -> Result<usize> {
 #[aoc(day22, part2)]
 fn part2(instructions: &[Instruction]) -> Result<i64> {
     const DECK_SIZE: i64 = 119_315_717_514_047;
-    const SHUFFLE_COUNT: i64 = 101_741_582_076_661;
+    const SHUFFLE_COUNT: u64 = 101_741_582_076_661;
     const TARGET_POSITION: i64 = 2020;
 
     let (a, b) = compose_operations(instructions, DECK_SIZE);
-    let (final_a, final_b) = power_transform(a, b, SHUFFLE_COUNT, DECK_SIZE);
+    let (final_a, final_b) = power_transform(a, b, SHUFFLE_COUNT, DECK_SIZE)?;
 
-    let inv_a = mod_inverse(final_a, DECK_SIZE);
-    let result = mod_sub(
-        mod_mul(TARGET_POSITION, inv_a, DECK_SIZE),
-        mod_mul(final_b, inv_a, DECK_SIZE),
-        DECK_SIZE,
-    );
-
-    Ok(result)
+    let inv_a = mod_inverse(final_a, DECK_SIZE).context("Shuffle is not reversible")?;
+    Ok(mul_mod(TARGET_POSITION - final_b, inv_a, DECK_SIZE))
 }
 
 fn compose_operations(instructions: &[Instruction], deck_size: i64) -> (i64, i64) {
     instructions.iter().fold((1, 0), |(a, b), instruction| {
         let (new_a, new_b) = match instruction {
-            Instruction::DealIntoNewStack => (mod_sub(0, 1, deck_size), mod_sub(0, 1, deck_size)),
-            Instruction::CutN(n) => (1, mod_sub(0, *n as i64, deck_size)),
+            Instruction::DealIntoNewStack => (deck_size - 1, deck_size - 1),
+            Instruction::CutN(n) => (1, (-*n as i64).rem_euclid(deck_size)),
             Instruction::DealWithIncrement(n) => (*n as i64, 0),
         };
 
         (
-            mod_mul(new_a, a, deck_size),
-            mod_add(mod_mul(new_a, b, deck_size), new_b, deck_size),
+            mul_mod(new_a, a, deck_size),
+            (mul_mod(new_a, b, deck_size) + new_b) % deck_size,
         )
     })
 }
 
-fn power_transform(a: i64, b: i64, k: i64, m: i64) -> (i64, i64) {
-    match k {
-        0 => (1, 0),
-        1 => (a, b),
-        _ => {
-            let a_k = mod_pow(a, k, m);
-            let b_k = if a == 1 {
-                mod_mul(k, b, m)
-            } else {
-                let numerator = mod_mul(b, mod_sub(a_k, 1, m), m);
-                let denominator = mod_sub(a, 1, m);
-                mod_mul(numerator, mod_inverse(denominator, m), m)
-            };
-            (a_k, b_k)
-        }
-    }
-}
-
-fn mod_add(a: i64, b: i64, m: i64) -> i64 {
-    ((a % m + b % m) % m + m) % m
-}
-
-fn mod_sub(a: i64, b: i64, m: i64) -> i64 {
-    ((a % m - b % m) % m + m) % m
-}
-
-fn mod_mul(a: i64, b: i64, m: i64) -> i64 {
-    let (mut a, mut b) = (a % m, b % m);
-    let mut result = 0;
-
-    while b > 0 {
-        if b & 1 == 1 {
-            result = (result + a) % m;
-        }
-        a = (a * 2) % m;
-        b >>= 1;
-    }
-
-    result
-}
-
-fn mod_pow(base: i64, mut exp: i64, m: i64) -> i64 {
-    let mut result = 1;
-    let mut base = base % m;
-
-    while exp > 0 {
-        if exp & 1 == 1 {
-            result = mod_mul(result, base, m);
-        }
-        base = mod_mul(base, base, m);
-        exp >>= 1;
-    }
-
-    result
-}
-
-fn mod_inverse(a: i64, m: i64) -> i64 {
-    let (mut old_r, mut r) = (a, m);
-    let (mut old_s, mut s) = (1, 0);
-
-    while r != 0 {
-        let quotient = old_r / r;
-        let (new_r, new_s) = (old_r - quotient * r, old_s - quotient * s);
-        old_r = r;
-        r = new_r;
-        old_s = s;
-        s = new_s;
-    }
-
-    ((old_s % m) + m) % m
+fn power_transform(a: i64, b: i64, k: u64, m: i64) -> Result<(i64, i64)> {
+    let a_k = pow_mod(a, k, m);
+    let b_k = if a == 1 {
+        mul_mod(k as i64, b, m)
+    } else {
+        let numerator = mul_mod(b, a_k - 1, m);
+        let denominator = mod_inverse(a - 1, m).context("Shuffle has no closed form")?;
+        mul_mod(numerator, denominator, m)
+    };
+    Ok((a_k, b_k))
 }
 
 #[cfg(test)]
@@ -252,6 +187,28 @@ cut -1";
         assert_eq!(result, vec![9, 2, 5, 8, 1, 4, 7, 0, 3, 6]);
     }
 
+    #[test]
+    fn test_compose_operations() {
+        let m = 10007;
+        let (a1, b1) = (3, 5);
+        let (a2, b2) = (7, 11);
+        assert_eq!(mul_mod(a1, a2, m), 21);
+        assert_eq!((mul_mod(a1, b2, m) + b1) % m, 38);
+
+        let instructions = parse("deal with increment 7\ncut -2\ndeal into new stack").unwrap();
+        let (a, b) = compose_operations(&instructions, m);
+        let deck = instructions
+            .iter()
+            .fold((0..m as usize).collect(), apply_shuffle);
+        for card in [0, 1, 2019, m - 1] {
+            let position = (mul_mod(a, card, m) + b) % m;
+            assert_eq!(deck[position as usize], card as usize);
+        }
+
+        let twice = compose_operations(&[instructions.clone(), instructions].concat(), m);
+        assert_eq!(power_transform(a, b, 2, m).unwrap(), twice);
+    }
+
     fn apply_shuffle(deck: Vec<usize>, instruction: &Instruction) -> Vec<usize> {
         match instruction {
             Instruction::DealIntoNewStack => deck.into_iter().rev().collect(),
@@ -274,23 +231,4 @@ cut -1";
             }
         }
     }
-
-    #[test]
-    fn test_modular_arithmetic() {
-        assert_eq!(mod_pow(3, 4, 7), 4);
-        assert_eq!(mod_pow(2, 10, 1000), 24);
-
-        assert_eq!(mod_inverse(3, 7), 5);
-        assert_eq!(mod_inverse(7, 11), 8);
-
-        assert_eq!(mod_mul(999999999999, 999999999999, 1000000007), 49014001);
-
-        let m = 10007;
-        let (a1, b1) = (3, 5);
-        let (a2, b2) = (7, 11);
-        let expected_a = mod_mul(a1, a2, m);
-        let expected_b = mod_add(mod_mul(a1, b2, m), b1, m);
-        assert_eq!(expected_a, 21);
-        assert_eq!(expected_b, 38);
-    }
 }
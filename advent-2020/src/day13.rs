@@ -1,5 +1,5 @@
+use advent_common::modular::{crt, gcd};
 use anyhow::{Context, Result};
-use num_integer::Integer;
 
 struct State {
     start_time: i64,
@@ -33,7 +33,7 @@ fn part1(state: &State) -> i64 {
                 .busses
                 .iter()
                 .copied()
-                .find(|&(_, b)| t.gcd(&b) != 1)
+                .find(|&(_, b)| gcd(t, b) != 1)
                 .map(|(_, b)| (b, t))
         })
         .expect("No valid bus found");
@@ -42,17 +42,11 @@ fn part1(state: &State) -> i64 {
 }
 
 #[aoc(day13, part2)]
-fn part2(state: &State) -> i64 {
-    let product: i64 = state.busses.iter().map(|(_, b)| b).product();
-    state
+fn part2(state: &State) -> Option<i64> {
+    let congruences = state
         .busses
         .iter()
-        .map(|(i, b)| (b - i, b))
-        .map(|(residue, modulus)| {
-            let p = product / modulus;
-            let g = p.extended_gcd(modulus);
-            residue * (g.x % modulus + modulus) * p
-        })
-        .sum::<i64>()
-        % product
+        .map(|&(offset, bus)| (-offset, bus))
+        .collect::<Vec<_>>();
+    crt(&congruences).map(|(time, _)| time)
 }
@@ -1,123 +1,303 @@
-use std::{collections::HashSet, iter};
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
 
-use anyhow::{Context, Result};
-use itertools::{Itertools, iproduct};
+use anyhow::{Context, Result, bail};
+use itertools::Itertools;
 
-const STEPS: usize = 7;
+const STEPS: usize = 6;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rules {
+    birth: Vec<usize>,
+    survival: Vec<usize>,
+}
+
+impl Rules {
+    fn conway() -> Self {
+        Rules {
+            birth: vec![3],
+            survival: vec![2, 3],
+        }
+    }
+
+    fn next_state(&self, active: bool, neighbors: usize) -> bool {
+        if active {
+            self.survival.contains(&neighbors)
+        } else {
+            self.birth.contains(&neighbors)
+        }
+    }
+}
+
+impl FromStr for Rules {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (birth, survival) = s.split_once('/').context("Rules must look like B3/S23")?;
+        let counts = |part: &str, prefix: char| -> Result<Vec<usize>> {
+            let Some(counts) = part.strip_prefix(prefix) else {
+                bail!("Expected {} in rule {}", prefix, part);
+            };
+            if counts.contains(',') {
+                counts
+                    .split(',')
+                    .map(|count| count.parse().context("Invalid neighbor count"))
+                    .collect()
+            } else {
+                counts
+                    .chars()
+                    .map(|count| {
+                        count
+                            .to_digit(10)
+                            .map(|count| count as usize)
+                            .context("Invalid neighbor count")
+                    })
+                    .collect()
+            }
+        };
+
+        Ok(Rules {
+            birth: counts(birth, 'B')?,
+            survival: counts(survival, 'S')?,
+        })
+    }
+}
 
 #[derive(Debug, Clone)]
-struct Dimension {
-    active_cells: HashSet<(i32, i32, i32, i32)>,
+struct Automaton<const N: usize> {
+    active_cells: HashSet<[i32; N]>,
+    rules: Rules,
+    symmetric_axes: usize,
+    offsets: Vec<[i32; N]>,
 }
 
-impl Dimension {
-    fn step(&self) -> Result<Dimension> {
-        let (min_x, max_x) = self
-            .active_cells
-            .iter()
-            .map(|(x, _, _, _)| x)
-            .minmax()
-            .into_option()
-            .context("No active cells for x dimension")?;
-        let (min_y, max_y) = self
-            .active_cells
+impl<const N: usize> Automaton<N> {
+    fn from_plane(cells: &[(i32, i32)], rules: Rules) -> Self {
+        let active_cells = cells
             .iter()
-            .map(|(_, y, _, _)| y)
-            .minmax()
-            .into_option()
-            .context("No active cells for y dimension")?;
-        let (min_z, max_z) = self
-            .active_cells
+            .map(|&(x, y)| {
+                let mut cell = [0; N];
+                cell[0] = x;
+                cell[1] = y;
+                cell
+            })
+            .collect();
+        let offsets = (0..N)
+            .map(|_| -1..=1)
+            .multi_cartesian_product()
+            .filter(|offset| offset.iter().any(|&d| d != 0))
+            .map(|offset| offset.try_into().unwrap())
+            .collect();
+
+        Automaton {
+            active_cells,
+            rules,
+            symmetric_axes: N.saturating_sub(2),
+            offsets,
+        }
+    }
+
+    fn canonical(&self, mut cell: [i32; N]) -> [i32; N] {
+        let symmetric = &mut cell[N - self.symmetric_axes..];
+        for value in symmetric.iter_mut() {
+            *value = value.abs();
+        }
+        symmetric.sort_unstable();
+        cell
+    }
+
+    fn orbit_size(&self, cell: &[i32; N]) -> usize {
+        let symmetric = &cell[N - self.symmetric_axes..];
+        let factorial = |n: usize| (1..=n).product::<usize>();
+        let arrangements = symmetric
             .iter()
-            .map(|(_, _, z, _)| z)
-            .minmax()
-            .into_option()
-            .context("No active cells for z dimension")?;
-        let (min_w, max_w) = self
+            .counts()
+            .values()
+            .fold(factorial(symmetric.len()), |acc, &count| {
+                acc / factorial(count)
+            });
+        let signs = 1 << symmetric.iter().filter(|&&value| value != 0).count();
+        arrangements * signs
+    }
+
+    fn neighbors<'a>(&'a self, cell: &'a [i32; N]) -> impl Iterator<Item = [i32; N]> + 'a {
+        self.offsets.iter().map(move |offset| {
+            let mut neighbor = *cell;
+            for (value, delta) in neighbor.iter_mut().zip(offset) {
+                *value += delta;
+            }
+            self.canonical(neighbor)
+        })
+    }
+
+    fn step(&mut self) {
+        let mut weighted_counts = self
             .active_cells
             .iter()
-            .map(|(_, _, _, w)| w)
-            .minmax()
-            .into_option()
-            .context("No active cells for w dimension")?;
-
-        let neighbors = iproduct!(-1..=1, -1..=1, -1..=1, -1..=1)
-            .filter(|&point| point != (0, 0, 0, 0))
-            .collect::<Vec<_>>();
-        let active_cells = iproduct!(
-            min_x - 1..=max_x + 1,
-            min_y - 1..=max_y + 1,
-            min_z - 1..=max_z + 1,
-            min_w - 1..=max_w + 1
-        )
-        .filter_map(|point| {
-            let (x, y, z, w) = point;
-            let active = self.active_cells.contains(&point);
-            let active_neighbors = neighbors
-                .iter()
-                .map(|(dx, dy, dz, dw)| (x + dx, y + dy, z + dz, w + dw))
-                .filter(|neighbor| self.active_cells.contains(neighbor))
-                .count();
-            match (active, active_neighbors) {
-                (true, 2) => Some(point),
-                (_, 3) => Some(point),
-                _ => None,
+            .map(|&cell| (cell, 0))
+            .collect::<HashMap<_, _>>();
+        for cell in &self.active_cells {
+            let weight = self.orbit_size(cell);
+            for neighbor in self.neighbors(cell) {
+                *weighted_counts.entry(neighbor).or_default() += weight;
             }
-        })
-        .collect();
+        }
 
-        Ok(Dimension { active_cells })
+        self.active_cells = weighted_counts
+            .into_iter()
+            .filter(|(cell, weighted_count)| {
+                let count = weighted_count / self.orbit_size(cell);
+                self.rules
+                    .next_state(self.active_cells.contains(cell), count)
+            })
+            .map(|(cell, _)| cell)
+            .collect();
+    }
+
+    fn run(&mut self, steps: usize) {
+        for _ in 0..steps {
+            self.step();
+        }
+    }
+
+    fn active_count(&self) -> usize {
+        self.active_cells
+            .iter()
+            .map(|cell| self.orbit_size(cell))
+            .sum()
     }
 }
 
 #[aoc_generator(day17)]
-fn generator(input: &str) -> Dimension {
-    let active_cells = input
+fn generator(input: &str) -> Vec<(i32, i32)> {
+    input
         .lines()
         .enumerate()
         .flat_map(|(y, line)| {
             line.chars()
                 .enumerate()
                 .filter_map(move |(x, ch)| match ch {
-                    '#' => Some((x as i32, y as i32, 0, 0)),
+                    '#' => Some((x as i32, y as i32)),
                     _ => None,
                 })
         })
-        .collect();
-
-    Dimension { active_cells }
+        .collect()
 }
 
 #[aoc(day17, part1)]
-fn part1(start: &Dimension) -> Result<usize> {
-    let start = start.clone();
-    let dimension = iter::successors(Some(Ok(start)), |prev| {
-        prev.as_ref().ok().map(|p| {
-            p.step().map(|dim| {
-                let active_cells = dim
-                    .active_cells
-                    .iter()
-                    .copied()
-                    .filter(|&(_, _, _, w)| w == 0)
-                    .collect();
-                Dimension { active_cells }
-            })
-        })
-    })
-    .take(STEPS)
-    .last()
-    .context("Failed to run simulation")??;
-
-    Ok(dimension.active_cells.len())
+fn part1(start: &[(i32, i32)]) -> usize {
+    let mut automaton = Automaton::<3>::from_plane(start, Rules::conway());
+    automaton.run(STEPS);
+    automaton.active_count()
 }
 
 #[aoc(day17, part2)]
-fn part2(start: &Dimension) -> Result<usize> {
-    let start = start.clone();
-    let dimension = iter::successors(Some(Ok(start)), |prev| prev.as_ref().ok().map(|p| p.step()))
-        .take(STEPS)
-        .last()
-        .context("Failed to run simulation")??;
-
-    Ok(dimension.active_cells.len())
+fn part2(start: &[(i32, i32)]) -> usize {
+    let mut automaton = Automaton::<4>::from_plane(start, Rules::conway());
+    automaton.run(STEPS);
+    automaton.active_count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = ".#.
+..#
+###";
+
+    impl<const N: usize> Automaton<N> {
+        fn without_symmetry(mut self) -> Self {
+            self.active_cells = self
+                .active_cells
+                .iter()
+                .flat_map(|cell| self.orbit(cell))
+                .collect();
+            self.symmetric_axes = 0;
+            self
+        }
+
+        fn orbit(&self, cell: &[i32; N]) -> Vec<[i32; N]> {
+            let fixed = N - self.symmetric_axes;
+            cell[fixed..]
+                .iter()
+                .copied()
+                .permutations(self.symmetric_axes)
+                .flat_map(|values| {
+                    values
+                        .into_iter()
+                        .map(|value| {
+                            if value == 0 {
+                                vec![0]
+                            } else {
+                                vec![value, -value]
+                            }
+                        })
+                        .multi_cartesian_product()
+                })
+                .map(|values| {
+                    let mut point = *cell;
+                    point[fixed..].copy_from_slice(&values);
+                    point
+                })
+                .unique()
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_part1() {
+        assert_eq!(part1(&generator(EXAMPLE)), 112);
+    }
+
+    #[test]
+    fn test_part2() {
+        assert_eq!(part2(&generator(EXAMPLE)), 848);
+    }
+
+    #[test]
+    fn test_symmetry_matches_full_simulation() {
+        let start = generator(EXAMPLE);
+        let mut symmetric = Automaton::<5>::from_plane(&start, Rules::conway());
+        let mut full = Automaton::<5>::from_plane(&start, Rules::conway()).without_symmetry();
+        for _ in 0..3 {
+            symmetric.step();
+            full.step();
+            assert_eq!(symmetric.active_count(), full.active_count());
+            assert_eq!(
+                symmetric.clone().without_symmetry().active_cells,
+                full.active_cells
+            );
+        }
+    }
+
+    #[test]
+    fn test_six_dimensions() {
+        let mut automaton = Automaton::<6>::from_plane(&generator(EXAMPLE), Rules::conway());
+        automaton.run(STEPS);
+        assert_eq!(automaton.active_count(), 35936);
+    }
+
+    #[test]
+    fn test_rules() {
+        assert_eq!("B3/S23".parse::<Rules>().unwrap(), Rules::conway());
+        assert_eq!("B3/S2,3".parse::<Rules>().unwrap(), Rules::conway());
+        assert!("S23".parse::<Rules>().is_err());
+
+        let mut automaton = Automaton::<2>::from_plane(&[(0, 0), (1, 0), (2, 0)], Rules::conway());
+        automaton.run(2);
+        assert_eq!(
+            automaton.active_cells,
+            HashSet::from([[0, 0], [1, 0], [2, 0]])
+        );
+
+        let mut seeds = Automaton::<2>::from_plane(&[(0, 0), (1, 0)], "B2/S".parse().unwrap());
+        seeds.step();
+        assert_eq!(
+            seeds.active_cells,
+            HashSet::from([[0, -1], [1, -1], [0, 1], [1, 1]])
+        );
+    }
 }
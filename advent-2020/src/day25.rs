@@ -1,22 +1,19 @@
-use std::iter;
-
+use advent_common::modular::{discrete_log, pow_mod};
 use anyhow::Result;
 
-const MODULUS: u64 = 20_201_227;
-const SUBJECT_NUMBER: u64 = 7;
+const MODULUS: i64 = 20_201_227;
+const SUBJECT_NUMBER: i64 = 7;
 
-fn transform(subject_number: u64, loop_size: usize) -> u64 {
-    (0..loop_size).fold(1, |value, _| (value * subject_number) % MODULUS)
+fn transform(subject_number: i64, loop_size: u64) -> i64 {
+    pow_mod(subject_number, loop_size, MODULUS)
 }
 
-fn find_loop_size(public_key: u64) -> usize {
-    iter::successors(Some(1), |&value| Some((value * SUBJECT_NUMBER) % MODULUS))
-        .position(|value| value == public_key)
-        .unwrap()
+fn find_loop_size(public_key: i64) -> Option<u64> {
+    discrete_log(SUBJECT_NUMBER, public_key, MODULUS)
 }
 
 #[aoc_generator(day25)]
-fn generator(input: &str) -> Result<(u64, u64)> {
+fn generator(input: &str) -> Result<(i64, i64)> {
     let mut lines = input.lines();
     let card_public_key = lines.next().unwrap().parse()?;
     let door_public_key = lines.next().unwrap().parse()?;
@@ -24,9 +21,9 @@ fn generator(input: &str) -> Result<(u64, u64)> {
 }
 
 #[aoc(day25, part1)]
-fn part1(&(card_public_key, door_public_key): &(u64, u64)) -> u64 {
-    let card_loop_size = find_loop_size(card_public_key);
-    transform(door_public_key, card_loop_size)
+fn part1(&(card_public_key, door_public_key): &(i64, i64)) -> Option<i64> {
+    let card_loop_size = find_loop_size(card_public_key)?;
+    Some(transform(door_public_key, card_loop_size))
 }
 
 #[cfg(test)]
@@ -43,13 +40,13 @@ mod tests {
 
     #[test]
     fn test_find_loop_size() {
-        assert_eq!(find_loop_size(5764801), 8);
-        assert_eq!(find_loop_size(17807724), 11);
+        assert_eq!(find_loop_size(5764801), Some(8));
+        assert_eq!(find_loop_size(17807724), Some(11));
     }
 
     #[test]
     fn test_part1_example() {
         let input = (5764801, 17807724);
-        assert_eq!(part1(&input), 14897079);
+        assert_eq!(part1(&input), Some(14897079));
     }
 }
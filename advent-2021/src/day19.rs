@@ -1,105 +1,205 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+    sync::LazyLock,
+};
 
-use anyhow::Context;
+use anyhow::{Context, bail};
 use itertools::{Itertools, iproduct};
-use nalgebra::{Matrix4, Point3, Transform3, Vector3, point};
+use nalgebra::{Matrix3, Vector3};
 
-type Point = Point3<f64>;
+const OVERLAP_THRESHOLD: usize = 12;
+
+type Point = Vector3<i32>;
+type Rotation = Matrix3<i32>;
+
+fn determinant(m: &Rotation) -> i32 {
+    m[(0, 0)] * (m[(1, 1)] * m[(2, 2)] - m[(1, 2)] * m[(2, 1)])
+        - m[(0, 1)] * (m[(1, 0)] * m[(2, 2)] - m[(1, 2)] * m[(2, 0)])
+        + m[(0, 2)] * (m[(1, 0)] * m[(2, 1)] - m[(1, 1)] * m[(2, 0)])
+}
+
+static ROTATIONS: LazyLock<Vec<Rotation>> = LazyLock::new(|| {
+    (0..3)
+        .permutations(3)
+        .cartesian_product(iproduct!([-1, 1], [-1, 1], [-1, 1]))
+        .map(|(axes, (a, b, c))| {
+            let signs = [a, b, c];
+            Rotation::from_fn(|row, col| if axes[row] == col { signs[row] } else { 0 })
+        })
+        .filter(|rotation| determinant(rotation) == 1)
+        .collect()
+});
 
 #[derive(Debug, Default, Clone)]
 struct Scanner {
     points: Vec<Point>,
-    unique_distances: Vec<HashSet<u32>>,
+    distances: HashMap<i32, usize>,
 }
 
 impl Scanner {
-    fn find_transform(&self, other: &Scanner) -> Option<Transform3<f64>> {
-        let overlaps = self.overlaps(other).take(4).collect::<Vec<_>>();
-        if overlaps.len() < 4 {
+    fn shared_distances(&self, other: &Scanner) -> usize {
+        self.distances
+            .iter()
+            .map(|(distance, &count)| {
+                count.min(other.distances.get(distance).copied().unwrap_or(0))
+            })
+            .sum()
+    }
+
+    fn find_transform(&self, other: &Scanner, threshold: usize) -> Option<Placement> {
+        if self.shared_distances(other) < threshold * threshold.saturating_sub(1) / 2 {
             return None;
         }
 
-        let self_columns = overlaps
+        ROTATIONS.iter().find_map(|&rotation| {
+            let rotated = other
+                .points
+                .iter()
+                .map(|p| rotation * p)
+                .collect::<Vec<_>>();
+            let mut votes = HashMap::<Point, usize>::new();
+            for (known, candidate) in iproduct!(&self.points, &rotated) {
+                let offset = known - candidate;
+                let count = votes.entry(offset).or_default();
+                *count += 1;
+                if *count >= threshold {
+                    return Some(Placement {
+                        position: offset,
+                        orientation: rotation,
+                    });
+                }
+            }
+            None
+        })
+    }
+}
+
+impl FromIterator<Point> for Scanner {
+    fn from_iter<T: IntoIterator<Item = Point>>(iter: T) -> Self {
+        let points = iter.into_iter().collect::<Vec<Point>>();
+        let distances = points
             .iter()
-            .map(|&(i, _)| self.points[i].to_homogeneous())
-            .collect::<Vec<_>>();
-        let mut self_matrix = Matrix4::from_columns(&self_columns);
-        if !self_matrix.try_inverse_mut() {
-            return None;
+            .tuple_combinations()
+            .map(|(left, right)| (left - right).map(|n| n * n).sum())
+            .counts();
+
+        Self { points, distances }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Placement {
+    position: Point,
+    orientation: Rotation,
+}
+
+impl Placement {
+    fn identity() -> Self {
+        Placement {
+            position: Point::zeros(),
+            orientation: Rotation::identity(),
         }
+    }
 
-        let other_columns = overlaps
-            .iter()
-            .map(|&(_, j)| other.points[j].to_homogeneous())
-            .collect::<Vec<_>>();
-        let other_matrix = Matrix4::from_columns(&other_columns);
+    fn apply(&self, point: &Point) -> Point {
+        self.orientation * point + self.position
+    }
 
-        let transform_matrix = other_matrix * self_matrix;
-        let transform = Transform3::from_matrix_unchecked(transform_matrix);
-        Some(transform)
+    fn then(&self, inner: &Placement) -> Placement {
+        Placement {
+            position: self.apply(&inner.position),
+            orientation: self.orientation * inner.orientation,
+        }
     }
+}
 
-    fn overlaps<'a>(&'a self, other: &'a Scanner) -> impl Iterator<Item = (usize, usize)> + 'a {
-        iproduct!(
-            self.unique_distances.iter().enumerate(),
-            other.unique_distances.iter().enumerate()
-        )
-        .filter_map(
-            |((self_index, self_distances), (other_index, other_distances))| {
-                let overlap_distances = self_distances & other_distances;
-                if overlap_distances.len() >= 11 {
-                    Some((self_index, other_index))
+impl fmt::Display for Placement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let axes = (0..3)
+            .map(|row| {
+                let col = (0..3)
+                    .find(|&col| self.orientation[(row, col)] != 0)
+                    .unwrap();
+                let sign = if self.orientation[(row, col)] > 0 {
+                    '+'
                 } else {
-                    None
-                }
-            },
+                    '-'
+                };
+                format!("{sign}{}", ['x', 'y', 'z'][col])
+            })
+            .join(",");
+        write!(
+            f,
+            "{},{},{} facing ({axes})",
+            self.position.x, self.position.y, self.position.z
         )
     }
 }
 
-impl FromIterator<Point> for Scanner {
-    fn from_iter<T: IntoIterator<Item = Point>>(iter: T) -> Self {
-        let points = iter.into_iter().collect::<Vec<Point>>();
-        let distances = points.iter().enumerate().tuple_combinations().fold(
-            HashMap::<(usize, u32), HashSet<usize>>::new(),
-            |mut acc, ((left_index, left), (right_index, right))| {
-                let distance = (left - right).norm_squared() as u32;
-                acc.entry((left_index, distance))
-                    .or_default()
-                    .insert(right_index);
-                acc.entry((right_index, distance))
-                    .or_default()
-                    .insert(left_index);
-                acc
-            },
-        );
+#[derive(Debug, Clone)]
+struct Alignment {
+    placements: Vec<Option<Placement>>,
+}
 
-        let unique_distances = distances.iter().fold(
-            vec![HashSet::new(); points.len()],
-            |mut acc, (&(i, d), v)| {
-                if v.len() == 1 {
-                    acc[i].insert(d);
-                }
+impl Alignment {
+    fn unconnected(&self) -> Vec<usize> {
+        self.placements.iter().positions(Option::is_none).collect()
+    }
 
-                acc
-            },
-        );
+    fn connected(&self) -> anyhow::Result<Vec<Placement>> {
+        let unconnected = self.unconnected();
+        if !unconnected.is_empty() {
+            bail!(
+                "scanners {} could not be connected to scanner 0",
+                unconnected.iter().join(", ")
+            );
+        }
+
+        Ok(self.placements.iter().flatten().copied().collect())
+    }
+}
 
-        Self {
-            points,
-            unique_distances,
+impl fmt::Display for Alignment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, placement) in self.placements.iter().enumerate() {
+            match placement {
+                Some(placement) => writeln!(f, "scanner {i} at {placement}")?,
+                None => writeln!(f, "scanner {i} could not be connected")?,
+            }
         }
+        Ok(())
     }
 }
 
-struct Input {
-    scanners: Vec<Scanner>,
-    transforms: Vec<Transform3<f64>>,
+fn align(scanners: &[Scanner], threshold: usize) -> Alignment {
+    let mut placements = vec![None; scanners.len()];
+    if scanners.is_empty() {
+        return Alignment { placements };
+    }
+
+    placements[0] = Some(Placement::identity());
+    let mut search = VecDeque::from([0]);
+    while let Some(known) = search.pop_front() {
+        let known_placement = placements[known].unwrap();
+        for unknown in 0..scanners.len() {
+            if placements[unknown].is_some() {
+                continue;
+            }
+
+            if let Some(placement) = scanners[known].find_transform(&scanners[unknown], threshold) {
+                placements[unknown] = Some(known_placement.then(&placement));
+                search.push_back(unknown);
+            }
+        }
+    }
+
+    Alignment { placements }
 }
 
 #[aoc_generator(day19)]
-fn generator(input: &str) -> anyhow::Result<Input> {
-    let scanners: Vec<Scanner> = input
+fn generator(input: &str) -> anyhow::Result<Vec<Scanner>> {
+    let scanners = input
         .lines()
         .try_fold::<_, _, anyhow::Result<Vec<Vec<Point>>>>(vec![], |mut acc, line| {
             if line.is_empty() {
@@ -111,94 +211,133 @@ fn generator(input: &str) -> anyhow::Result<Input> {
                 return Ok(acc);
             }
 
-            let point = Point::from(Vector3::from_iterator(
-                line.split(',').filter_map(|part| part.parse().ok()),
-            ));
+            let coordinates = line
+                .split(',')
+                .map(|part| part.trim().parse())
+                .collect::<Result<Vec<i32>, _>>()
+                .with_context(|| format!("invalid beacon {line}"))?;
+            let &[x, y, z] = coordinates.as_slice() else {
+                bail!("expected three coordinates in {line}");
+            };
 
             let last = acc.last_mut().context("unable to get last")?;
-            last.push(point);
+            last.push(Point::new(x, y, z));
 
             Ok(acc)
-        })
-        .map(|scanners| {
-            scanners
-                .into_iter()
-                .map(|points| points.into_iter().collect())
-                .collect()
         })?;
 
-    let mut transforms = HashMap::new();
-    transforms.insert(0, Transform3::identity());
+    Ok(scanners
+        .into_iter()
+        .map(|points| points.into_iter().collect())
+        .collect())
+}
 
-    let mut ignore = HashSet::new();
+fn beacon_count(scanners: &[Scanner], threshold: usize) -> anyhow::Result<usize> {
+    let placements = align(scanners, threshold).connected()?;
+    let beacons = scanners
+        .iter()
+        .zip(&placements)
+        .flat_map(|(scanner, placement)| scanner.points.iter().map(|p| placement.apply(p)))
+        .collect::<HashSet<Point>>();
 
-    let mut search = (1..scanners.len()).collect::<VecDeque<usize>>();
-    while let Some(i) = search.pop_front() {
-        let known_scanners = transforms.keys();
-        for &j in known_scanners {
-            if ignore.contains(&(i, j)) {
-                continue;
-            }
+    Ok(beacons.len())
+}
 
-            match scanners[i].find_transform(&scanners[j]) {
-                Some(transform) => {
-                    let other_transform = transforms[&j];
-                    transforms.insert(i, other_transform * transform);
-                    break;
-                }
-                _ => {
-                    ignore.insert((i, j));
-                }
-            }
-        }
+fn largest_distance(scanners: &[Scanner], threshold: usize) -> anyhow::Result<i32> {
+    let placements = align(scanners, threshold).connected()?;
+    placements
+        .iter()
+        .tuple_combinations()
+        .map(|(left, right)| (left.position - right.position).abs().sum())
+        .max()
+        .context("no scanners")
+}
 
-        if !transforms.contains_key(&i) {
-            search.push_back(i);
-        }
-    }
+#[aoc(day19, part1)]
+fn part1(scanners: &[Scanner]) -> anyhow::Result<usize> {
+    beacon_count(scanners, OVERLAP_THRESHOLD)
+}
 
-    let transforms = transforms.into_iter().fold(
-        vec![Transform3::identity(); scanners.len()],
-        |mut acc, (index, transform)| {
-            acc[index] = transform;
-            acc
-        },
-    );
+#[aoc(day19, part2)]
+fn part2(scanners: &[Scanner]) -> anyhow::Result<i32> {
+    largest_distance(scanners, OVERLAP_THRESHOLD)
+}
 
-    Ok(Input {
-        scanners,
-        transforms,
-    })
+#[aoc(day19, part2, report)]
+fn part2_report(scanners: &[Scanner]) -> String {
+    align(scanners, OVERLAP_THRESHOLD).to_string()
 }
 
-#[aoc(day19, part1)]
-fn part1(input: &Input) -> usize {
-    let beacons = input
-        .scanners
-        .iter()
-        .enumerate()
-        .flat_map(|(i, scanner)| {
-            let transform = input.transforms[i];
-            scanner.points.iter().copied().map(move |local_point| {
-                let global_point = transform * local_point;
-                Vector3::from_iterator(global_point.coords.iter().map(|n| n.round() as i32)).into()
-            })
-        })
-        .collect::<HashSet<Point3<i32>>>();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    beacons.len()
-}
+    const THRESHOLD: usize = 3;
 
-#[aoc(day19, part2)]
-fn part2(input: &Input) -> Option<i32> {
-    (0..input.scanners.len())
-        .map(|i| {
-            let origin = input.transforms[i] * point![0., 0., 0.];
-            Point3::from(Vector3::<i32>::from_iterator(
-                origin.coords.iter().map(|x| x.round() as i32),
-            ))
-        })
-        .tuple_combinations()
-        .map(|(left, right)| (left - right).abs().sum())
-        .max()
+    const EXAMPLE: &str = "--- scanner 0 ---
+1,2,3
+5,-1,2
+-3,4,7
+8,8,-2
+0,-6,1
+
+--- scanner 1 ---
+-1,15,2
+4,23,7
+8,12,-2
+-6,20,1
+3,-5,3
+10,-2,6
+
+--- scanner 2 ---
+5,-2,12
+2,1,5
+-20,-4,21
+10,5,-5";
+
+    #[test]
+    fn test_rotations() {
+        assert_eq!(ROTATIONS.len(), 24);
+        assert!(ROTATIONS.iter().all_unique());
+        assert!(ROTATIONS.contains(&Rotation::identity()));
+    }
+
+    #[test]
+    fn test_alignment() {
+        let scanners = generator(EXAMPLE).unwrap();
+        let alignment = align(&scanners, THRESHOLD);
+        assert!(alignment.unconnected().is_empty());
+        assert_eq!(
+            alignment.to_string(),
+            "scanner 0 at 0,0,0 facing (+x,+y,+z)
+scanner 1 at 20,0,0 facing (-y,+x,+z)
+scanner 2 at 20,15,5 facing (+x,-z,+y)
+"
+        );
+        assert_eq!(beacon_count(&scanners, THRESHOLD).unwrap(), 8);
+        assert_eq!(largest_distance(&scanners, THRESHOLD).unwrap(), 40);
+    }
+
+    #[test]
+    fn test_unconnected() {
+        let mut scanners = generator(EXAMPLE).unwrap();
+        scanners.push(
+            [Point::new(1, 2, 3), Point::new(4, 5, 6)]
+                .into_iter()
+                .collect(),
+        );
+
+        let alignment = align(&scanners, THRESHOLD);
+        assert_eq!(alignment.unconnected(), [3]);
+        assert!(
+            alignment
+                .to_string()
+                .contains("scanner 3 could not be connected")
+        );
+        assert!(beacon_count(&scanners, THRESHOLD).is_err());
+        assert!(part1(&scanners).is_err());
+
+        let alignment = align(&scanners, 10_000);
+        assert_eq!(alignment.unconnected(), [1, 2, 3]);
+    }
 }
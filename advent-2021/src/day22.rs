@@ -1,14 +1,15 @@
-use std::collections::HashSet;
-
+use advent_common::cuboid::{Cuboid, CuboidSet, Point};
 use anyhow::Context;
-use itertools::iproduct;
 use regex::Regex;
 
-type Point = (i64, i64, i64);
-type Input = Vec<(bool, (Point, Point))>;
+#[derive(Clone, Copy, Debug)]
+struct Step {
+    on: bool,
+    cuboid: Cuboid,
+}
 
 #[aoc_generator(day22)]
-fn generator(input: &str) -> anyhow::Result<Input> {
+fn generator(input: &str) -> anyhow::Result<Vec<Step>> {
     let re = Regex::new(
         r"(?P<state>on|off) x=(?P<x1>-?\d+)..(?P<x2>-?\d+),y=(?P<y1>-?\d+)..(?P<y2>-?\d+),z=(?P<z1>-?\d+)..(?P<z2>-?\d+)",
     )?;
@@ -17,118 +18,108 @@ fn generator(input: &str) -> anyhow::Result<Input> {
         .lines()
         .map(|line| {
             let captures = re.captures(line)?;
-            let state = &captures["state"] == "on";
+            let on = &captures["state"] == "on";
             let x1 = captures["x1"].parse().ok()?;
             let x2 = captures["x2"].parse().ok()?;
             let y1 = captures["y1"].parse().ok()?;
             let y2 = captures["y2"].parse().ok()?;
             let z1 = captures["z1"].parse().ok()?;
             let z2 = captures["z2"].parse().ok()?;
-            Some((state, ((x1, y1, z1), (x2, y2, z2))))
+            let cuboid = Cuboid::new([x1, y1, z1], [x2, y2, z2]);
+            Some(Step { on, cuboid })
         })
-        .collect::<Option<Input>>()
+        .collect::<Option<Vec<_>>>()
         .context("unable to parse input")
 }
 
+fn reboot(steps: &[Step]) -> (CuboidSet, Vec<i64>) {
+    let mut reactor = CuboidSet::new();
+    let mut deltas = Vec::with_capacity(steps.len());
+    for step in steps {
+        let before = reactor.volume();
+        if step.on {
+            reactor.insert(step.cuboid);
+        } else {
+            reactor.remove(&step.cuboid);
+        }
+        deltas.push(reactor.volume() as i64 - before as i64);
+    }
+    (reactor, deltas)
+}
+
+fn is_on(steps: &[Step], point: Point, after: usize) -> bool {
+    steps[..after.min(steps.len())]
+        .iter()
+        .rev()
+        .find(|step| step.cuboid.contains(point))
+        .is_some_and(|step| step.on)
+}
+
 #[aoc(day22, part1)]
-fn part1(input: &Input) -> usize {
-    let cubes = input
+fn part1(steps: &[Step]) -> u64 {
+    let region = Cuboid::new([-50; 3], [50; 3]);
+    let steps = steps
         .iter()
-        .filter(|&&(_, ((x1, y1, z1), (x2, y2, z2)))| {
-            x1 >= -50 && x2 <= 50 && y1 >= -50 && y2 <= 50 && z1 >= -50 && z2 <= 50
+        .filter_map(|step| {
+            let cuboid = step.cuboid.intersection(&region)?;
+            Some(Step { cuboid, ..*step })
         })
-        .fold(
-            HashSet::new(),
-            |mut acc, &(state, ((x1, y1, z1), (x2, y2, z2)))| {
-                for point in iproduct!(x1..=x2, y1..=y2, z1..=z2) {
-                    if state {
-                        acc.insert(point);
-                    } else {
-                        acc.remove(&point);
-                    }
-                }
-
-                acc
-            },
-        );
+        .collect::<Vec<_>>();
+    reboot(&steps).0.volume()
+}
 
-    cubes.len()
+#[aoc(day22, part2)]
+fn part2(steps: &[Step]) -> u64 {
+    reboot(steps).0.volume()
 }
 
-#[derive(Debug, PartialEq)]
-struct Cube {
-    min: Point,
-    max: Point,
-    subtracted: Vec<Cube>,
+#[aoc(day22, part2, deltas)]
+fn part2_deltas(steps: &[Step]) -> String {
+    let (_, deltas) = reboot(steps);
+    deltas
+        .iter()
+        .enumerate()
+        .map(|(index, delta)| format!("step {}: {delta:+}", index + 1))
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
-impl Cube {
-    fn new(min: Point, max: Point) -> Self {
-        Self {
-            min,
-            max,
-            subtracted: vec![],
-        }
-    }
+#[aoc(day22, part2, origin)]
+fn part2_origin(steps: &[Step]) -> bool {
+    is_on(steps, [0; 3], steps.len())
+}
 
-    fn volume(&self) -> usize {
-        let (x1, y1, z1) = self.min;
-        let (x2, y2, z2) = self.max;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let volume = ((x2 - x1 + 1) * (y2 - y1 + 1) * (z2 - z1 + 1)) as usize;
-        let subtracted_volume = self.subtracted.iter().map(Cube::volume).sum::<usize>();
+    const EXAMPLE: &str = "on x=10..12,y=10..12,z=10..12
+on x=11..13,y=11..13,z=11..13
+off x=9..11,y=9..11,z=9..11
+on x=10..10,y=10..10,z=10..10";
 
-        volume - subtracted_volume
+    #[test]
+    fn test_part1() {
+        let steps = generator(EXAMPLE).unwrap();
+        assert_eq!(part1(&steps), 39);
     }
 
-    fn subtract(&mut self, other: &Cube) {
-        if let Some(intersection) = self.intersection(other) {
-            for sub in self.subtracted.iter_mut() {
-                sub.subtract(&intersection);
-            }
-
-            self.subtracted.push(intersection);
-        }
+    #[test]
+    fn test_deltas() {
+        let steps = generator(EXAMPLE).unwrap();
+        let (reactor, deltas) = reboot(&steps);
+        assert_eq!(deltas, [27, 19, -8, 1]);
+        assert_eq!(reactor.volume(), 39);
     }
 
-    fn intersection(&self, other: &Cube) -> Option<Cube> {
-        let (x1, y1, z1) = self.min;
-        let (x2, y2, z2) = self.max;
-        let (ox1, oy1, oz1) = other.min;
-        let (ox2, oy2, oz2) = other.max;
-
-        let ix1 = x1.max(ox1);
-        let ix2 = x2.min(ox2);
-        let iy1 = y1.max(oy1);
-        let iy2 = y2.min(oy2);
-        let iz1 = z1.max(oz1);
-        let iz2 = z2.min(oz2);
-
-        if ix2 - ix1 < 0 || iy2 - iy1 < 0 || iz2 - iz1 < 0 {
-            None
-        } else {
-            Some(Cube::new((ix1, iy1, iz1), (ix2, iy2, iz2)))
-        }
+    #[test]
+    fn test_is_on() {
+        let steps = generator(EXAMPLE).unwrap();
+        assert!(!is_on(&steps, [10, 10, 10], 0));
+        assert!(is_on(&steps, [10, 10, 10], 1));
+        assert!(!is_on(&steps, [10, 10, 10], 3));
+        assert!(is_on(&steps, [10, 10, 10], 4));
+        assert!(is_on(&steps, [13, 13, 13], 4));
+        assert!(!is_on(&steps, [9, 9, 9], 4));
     }
 }
-
-#[aoc(day22, part2)]
-fn part2(input: &Input) -> usize {
-    input
-        .iter()
-        .map(|&(state, (min, max))| (state, Cube::new(min, max)))
-        .fold(Vec::<Cube>::new(), |mut acc, (state, cube)| {
-            for existing in acc.iter_mut() {
-                existing.subtract(&cube);
-            }
-
-            if state {
-                acc.push(cube);
-            }
-
-            acc
-        })
-        .iter()
-        .map(Cube::volume)
-        .sum()
-}
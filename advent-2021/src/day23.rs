@@ -1,232 +1,422 @@
 use std::{
     cmp::Ordering,
     collections::{BinaryHeap, HashMap},
+    fmt,
+    str::FromStr,
 };
 
+use anyhow::{Context, bail, ensure};
 use aoc_runner_derive::{aoc, aoc_generator};
+use itertools::Itertools;
+
+const UNFOLDED_ROWS: [&str; 2] = ["DCBA", "DBAC"];
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-enum Amphipod {
-    Amber,
-    Bronze,
-    Copper,
-    Desert,
-}
+struct Amphipod(u8);
 
 impl Amphipod {
     fn from_char(c: char) -> Option<Self> {
-        use Amphipod::*;
-        match c {
-            'A' => Some(Amber),
-            'B' => Some(Bronze),
-            'C' => Some(Copper),
-            'D' => Some(Desert),
-            _ => None,
-        }
+        c.is_ascii_uppercase().then(|| Amphipod(c as u8 - b'A'))
     }
 
-    fn energy_per_step(&self) -> usize {
-        use Amphipod::*;
-        match self {
-            Amber => 1,
-            Bronze => 10,
-            Copper => 100,
-            Desert => 1000,
-        }
+    fn target_room(&self) -> usize {
+        self.0 as usize
     }
+}
 
-    fn target_room(&self) -> usize {
-        use Amphipod::*;
-        match self {
-            Amber => 0,
-            Bronze => 1,
-            Copper => 2,
-            Desert => 3,
-        }
+impl fmt::Display for Amphipod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", (b'A' + self.0) as char)
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 struct State {
-    hallway: [Option<Amphipod>; 11],
+    hallway: Vec<Option<Amphipod>>,
     rooms: Vec<Vec<Option<Amphipod>>>,
 }
 
-const VALID_HALLWAY_POSITIONS: [usize; 7] = [0, 1, 3, 5, 7, 9, 10];
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Location {
+    Hallway(usize),
+    Room { room: usize, slot: usize },
+}
 
-impl State {
-    fn room_entrance(room_idx: usize) -> usize {
-        2 + room_idx * 2
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::Hallway(position) => write!(f, "hallway {position}"),
+            Location::Room { room, slot } => {
+                write!(f, "room {} slot {}", Amphipod(*room as u8), slot + 1)
+            }
+        }
     }
+}
 
-    fn is_complete(&self) -> bool {
-        self.hallway.iter().all(|&slot| slot.is_none())
-            && self.rooms.iter().enumerate().all(|(room_idx, room)| {
-                room.iter().all(|&slot| {
-                    slot.map(|amphipod| amphipod.target_room() == room_idx)
-                        .unwrap_or(false)
-                })
-            })
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Move {
+    amphipod: Amphipod,
+    from: Location,
+    to: Location,
+    energy: usize,
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} from {} to {} ({} energy)",
+            self.amphipod, self.from, self.to, self.energy
+        )
     }
+}
 
-    fn can_move_from_room(&self, room_idx: usize, amphipod: Amphipod, top_idx: usize) -> bool {
-        amphipod.target_room() != room_idx
-            || self.rooms[room_idx][(top_idx + 1)..]
-                .iter()
-                .any(|&slot| slot.map(|a| a.target_room() != room_idx).unwrap_or(false))
+#[derive(Debug, Clone, Default)]
+struct Solution {
+    energy: usize,
+    moves: Vec<Move>,
+}
+
+impl fmt::Display for Solution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in &self.moves {
+            writeln!(f, "{step}")?;
+        }
+        writeln!(f, "Total energy: {}", self.energy)
     }
+}
 
-    fn can_enter_room(&self, target_room: usize) -> bool {
-        self.rooms[target_room]
-            .iter()
-            .all(|&slot| slot.map(|a| a.target_room() == target_room).unwrap_or(true))
+#[derive(Debug, Clone)]
+struct Burrow {
+    entrances: Vec<usize>,
+    costs: Vec<usize>,
+    start: State,
+}
+
+impl Burrow {
+    fn new(entrances: Vec<usize>, costs: Vec<usize>, start: State) -> anyhow::Result<Self> {
+        ensure!(
+            costs.len() == start.rooms.len() && entrances.len() == start.rooms.len(),
+            "expected {} room costs and entrances",
+            start.rooms.len()
+        );
+        Ok(Burrow {
+            entrances,
+            costs,
+            start,
+        })
     }
 
-    fn is_path_clear(&self, from: usize, to: usize) -> bool {
-        let (start, end) = if from < to { (from, to) } else { (to, from) };
-        (start..=end).all(|i| self.hallway[i].is_none())
+    fn is_entrance(&self, position: usize) -> bool {
+        self.entrances.contains(&position)
     }
 
-    fn get_movable_amphipod(&self, room_idx: usize) -> Option<(usize, Amphipod)> {
-        self.rooms[room_idx]
+    fn settled(&self, state: &State, room: usize) -> usize {
+        state.rooms[room]
             .iter()
-            .position(|slot| slot.is_some())
-            .and_then(|top_idx| {
-                self.rooms[room_idx][top_idx]
-                    .filter(|&amphipod| self.can_move_from_room(room_idx, amphipod, top_idx))
-                    .map(|amphipod| (top_idx, amphipod))
-            })
+            .rev()
+            .take_while(|slot| slot.is_some_and(|a| a.target_room() == room))
+            .count()
     }
 
-    fn create_room_to_hallway_move(
-        &self,
-        room_idx: usize,
-        top_idx: usize,
-        hallway_pos: usize,
-        amphipod: Amphipod,
-    ) -> (State, usize) {
-        let mut new_state = self.clone();
-        new_state.rooms[room_idx][top_idx] = None;
-        new_state.hallway[hallway_pos] = Some(amphipod);
-
-        let steps = hallway_pos.abs_diff(Self::room_entrance(room_idx)) + top_idx + 1;
-        (new_state, steps * amphipod.energy_per_step())
-    }
-
-    fn room_to_hallway_moves(&self) -> impl Iterator<Item = (State, usize)> + '_ {
-        (0..4).flat_map(move |room_idx| {
-            self.get_movable_amphipod(room_idx)
-                .into_iter()
-                .flat_map(move |(top_idx, amphipod)| {
-                    let room_entrance = Self::room_entrance(room_idx);
-
-                    VALID_HALLWAY_POSITIONS
-                        .iter()
-                        .copied()
-                        .filter(move |&hallway_pos| self.is_path_clear(hallway_pos, room_entrance))
-                        .map(move |hallway_pos| {
-                            self.create_room_to_hallway_move(
-                                room_idx,
-                                top_idx,
-                                hallway_pos,
-                                amphipod,
-                            )
-                        })
-                })
-        })
+    fn is_complete(&self, state: &State) -> bool {
+        (0..state.rooms.len()).all(|room| self.settled(state, room) == state.rooms[room].len())
     }
 
-    fn get_amphipods_in_hallway(&self) -> impl Iterator<Item = (usize, Amphipod)> + '_ {
-        self.hallway
+    fn can_enter(&self, state: &State, room: usize) -> Option<usize> {
+        state.rooms[room]
             .iter()
-            .enumerate()
-            .filter_map(|(pos, &slot)| slot.map(|amphipod| (pos, amphipod)))
+            .all(|slot| slot.is_none_or(|a| a.target_room() == room))
+            .then(|| state.rooms[room].iter().rposition(Option::is_none))
+            .flatten()
     }
 
-    fn find_room_slot(&self, room_idx: usize) -> Option<usize> {
-        self.rooms[room_idx]
-            .iter()
-            .rposition(|&slot| slot.is_none())
+    fn is_path_clear(&self, state: &State, from: usize, to: usize) -> bool {
+        let range = if from < to {
+            from + 1..=to
+        } else {
+            to..=from - 1
+        };
+        state.hallway[range].iter().all(Option::is_none)
     }
 
-    fn calculate_hallway_path(&self, hallway_pos: usize, room_entrance: usize) -> (usize, usize) {
-        if hallway_pos < room_entrance {
-            (hallway_pos + 1, room_entrance)
-        } else {
-            (room_entrance, hallway_pos - 1)
-        }
+    fn energy(&self, amphipod: Amphipod, steps: usize) -> usize {
+        steps * self.costs[amphipod.target_room()]
     }
 
-    fn create_hallway_to_room_move(
-        &self,
-        hallway_pos: usize,
-        target_room: usize,
-        target_slot: usize,
-        amphipod: Amphipod,
-    ) -> (State, usize) {
-        let mut new_state = self.clone();
-        new_state.hallway[hallway_pos] = None;
-        new_state.rooms[target_room][target_slot] = Some(amphipod);
+    fn leaving(&self, state: &State, room: usize) -> Option<(usize, Amphipod)> {
+        let slot = state.rooms[room].iter().position(Option::is_some)?;
+        let depth = state.rooms[room].len();
+        (slot < depth - self.settled(state, room)).then(|| (slot, state.rooms[room][slot].unwrap()))
+    }
+
+    fn apply(&self, state: &State, amphipod: Amphipod, from: Location, to: Location) -> State {
+        let mut next = state.clone();
+        for (location, value) in [(from, None), (to, Some(amphipod))] {
+            match location {
+                Location::Hallway(position) => next.hallway[position] = value,
+                Location::Room { room, slot } => next.rooms[room][slot] = value,
+            }
+        }
+        next
+    }
+
+    fn moves(&self, state: &State) -> Vec<(State, Move)> {
+        let mut moves = vec![];
+        let mut push = |amphipod: Amphipod, from: Location, to: Location, steps: usize| {
+            moves.push((
+                self.apply(state, amphipod, from, to),
+                Move {
+                    amphipod,
+                    from,
+                    to,
+                    energy: self.energy(amphipod, steps),
+                },
+            ));
+        };
+
+        for (room, &entrance) in self.entrances.iter().enumerate() {
+            let Some((slot, amphipod)) = self.leaving(state, room) else {
+                continue;
+            };
+            let from = Location::Room { room, slot };
+
+            let target = amphipod.target_room();
+            let target_entrance = self.entrances[target];
+            if target != room
+                && let Some(target_slot) = self.can_enter(state, target)
+                && self.is_path_clear(state, entrance, target_entrance)
+            {
+                let steps = slot + 1 + entrance.abs_diff(target_entrance) + target_slot + 1;
+                let to = Location::Room {
+                    room: target,
+                    slot: target_slot,
+                };
+                push(amphipod, from, to, steps);
+            }
+
+            for position in 0..state.hallway.len() {
+                if !self.is_entrance(position) && self.is_path_clear(state, entrance, position) {
+                    let steps = slot + 1 + entrance.abs_diff(position);
+                    push(amphipod, from, Location::Hallway(position), steps);
+                }
+            }
+        }
 
-        let steps = hallway_pos.abs_diff(Self::room_entrance(target_room)) + target_slot + 1;
-        (new_state, steps * amphipod.energy_per_step())
+        for (position, amphipod) in state
+            .hallway
+            .iter()
+            .enumerate()
+            .filter_map(|(position, slot)| slot.map(|a| (position, a)))
+        {
+            let target = amphipod.target_room();
+            let entrance = self.entrances[target];
+            if let Some(slot) = self.can_enter(state, target)
+                && self.is_path_clear(state, position, entrance)
+            {
+                let steps = position.abs_diff(entrance) + slot + 1;
+                let to = Location::Room { room: target, slot };
+                push(amphipod, Location::Hallway(position), to, steps);
+            }
+        }
+
+        moves
     }
 
-    fn try_move_to_room(&self, hallway_pos: usize, amphipod: Amphipod) -> Option<(State, usize)> {
-        let target_room = amphipod.target_room();
-        let room_entrance = Self::room_entrance(target_room);
+    fn heuristic(&self, state: &State) -> usize {
+        let hallway = state
+            .hallway
+            .iter()
+            .enumerate()
+            .filter_map(|(position, slot)| slot.map(|a| (position, a)))
+            .map(|(position, amphipod)| {
+                let steps = position.abs_diff(self.entrances[amphipod.target_room()]) + 1;
+                self.energy(amphipod, steps)
+            })
+            .sum::<usize>();
 
-        self.can_enter_room(target_room)
-            .then(|| self.find_room_slot(target_room))
-            .flatten()
-            .and_then(|target_slot| {
-                let (path_start, path_end) =
-                    self.calculate_hallway_path(hallway_pos, room_entrance);
-
-                self.is_path_clear(path_start, path_end).then(|| {
-                    self.create_hallway_to_room_move(
-                        hallway_pos,
-                        target_room,
-                        target_slot,
-                        amphipod,
-                    )
-                })
+        let rooms = state
+            .rooms
+            .iter()
+            .enumerate()
+            .map(|(room, slots)| {
+                let unsettled = slots.len() - self.settled(state, room);
+                let leaving = slots[..unsettled]
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(slot, amphipod)| amphipod.map(|a| (slot, a)))
+                    .map(|(slot, amphipod)| {
+                        let target = amphipod.target_room();
+                        let across = if target == room {
+                            2
+                        } else {
+                            self.entrances[room].abs_diff(self.entrances[target])
+                        };
+                        self.energy(amphipod, slot + 1 + across + 1)
+                    })
+                    .sum::<usize>();
+                let filling = unsettled * unsettled.saturating_sub(1) / 2 * self.costs[room];
+                leaving + filling
             })
-    }
+            .sum::<usize>();
 
-    fn hallway_to_room_moves(&self) -> impl Iterator<Item = (State, usize)> + '_ {
-        self.get_amphipods_in_hallway()
-            .filter_map(move |(hallway_pos, amphipod)| self.try_move_to_room(hallway_pos, amphipod))
+        hallway + rooms
     }
 
-    fn generate_moves(&self) -> Vec<(State, usize)> {
-        self.room_to_hallway_moves()
-            .chain(self.hallway_to_room_moves())
-            .collect()
+    fn solve(&self) -> Option<Solution> {
+        let mut heap = BinaryHeap::new();
+        let mut costs = HashMap::from([(self.start.clone(), 0)]);
+        let mut previous = HashMap::<State, (State, Move)>::new();
+
+        heap.push(SearchState {
+            priority: self.heuristic(&self.start),
+            cost: 0,
+            state: self.start.clone(),
+        });
+
+        while let Some(SearchState { cost, state, .. }) = heap.pop() {
+            if self.is_complete(&state) {
+                let mut moves = vec![];
+                let mut current = &state;
+                while let Some((before, step)) = previous.get(current) {
+                    moves.push(*step);
+                    current = before;
+                }
+                moves.reverse();
+                return Some(Solution {
+                    energy: cost,
+                    moves,
+                });
+            }
+
+            if cost > costs[&state] {
+                continue;
+            }
+
+            for (next_state, step) in self.moves(&state) {
+                let next_cost = cost + step.energy;
+                if next_cost < costs.get(&next_state).copied().unwrap_or(usize::MAX) {
+                    costs.insert(next_state.clone(), next_cost);
+                    previous.insert(next_state.clone(), (state.clone(), step));
+                    heap.push(SearchState {
+                        priority: next_cost + self.heuristic(&next_state),
+                        cost: next_cost,
+                        state: next_state,
+                    });
+                }
+            }
+        }
+
+        None
+    }
+
+    fn unfold(&self, rows: &[&str]) -> anyhow::Result<Burrow> {
+        let mut burrow = self.clone();
+        for (offset, row) in rows.iter().enumerate() {
+            let amphipods = row
+                .chars()
+                .map(|c| Amphipod::from_char(c).with_context(|| format!("invalid amphipod {c}")))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            ensure!(
+                amphipods.len() == burrow.start.rooms.len(),
+                "row {row} does not match {} rooms",
+                burrow.start.rooms.len()
+            );
+            for (room, amphipod) in burrow.start.rooms.iter_mut().zip(amphipods) {
+                room.insert(1 + offset, Some(amphipod));
+            }
+        }
+        Ok(burrow)
     }
 }
 
-#[aoc_generator(day23)]
-fn generator(input: &str) -> State {
-    let lines: Vec<&str> = input.lines().collect();
+impl FromStr for Burrow {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lines = s.lines().collect_vec();
+        let cell = |line: &str, x: usize| -> anyhow::Result<Option<Option<Amphipod>>> {
+            match line.chars().nth(x) {
+                Some('.') => Ok(Some(None)),
+                Some(c) if c.is_ascii_uppercase() => Ok(Some(Amphipod::from_char(c))),
+                Some('#' | ' ') | None => Ok(None),
+                Some(c) => bail!("unexpected {c} in burrow"),
+            }
+        };
 
-    let rooms = (2..lines.len() - 1).fold(vec![vec![]; 4], |mut rooms, row| {
-        [3, 5, 7, 9].iter().enumerate().for_each(|(room_idx, &x)| {
-            if let Some(amphipod) = lines[row].chars().nth(x).and_then(Amphipod::from_char) {
-                rooms[room_idx].push(Some(amphipod));
+        let corridor = lines.get(1).context("missing hallway")?;
+        let open = (0..corridor.len())
+            .map(|x| cell(corridor, x))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let first = open
+            .iter()
+            .position(Option::is_some)
+            .context("hallway has no open cells")?;
+        let last = open.iter().rposition(Option::is_some).unwrap();
+        let hallway = open[first..=last]
+            .iter()
+            .map(|slot| slot.context("hallway must be contiguous"))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mut room_rows = vec![];
+        for line in &lines[2..] {
+            let cells = (0..line.len())
+                .map(|x| cell(line, x))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let columns = cells.iter().positions(Option::is_some).collect_vec();
+            if !columns.is_empty() {
+                room_rows.push((columns, cells));
             }
-        });
-        rooms
-    });
+        }
 
-    State {
-        hallway: [None; 11],
-        rooms,
+        let Some((columns, _)) = room_rows.first() else {
+            bail!("burrow has no rooms");
+        };
+        let columns = columns.clone();
+        ensure!(
+            columns.iter().all(|x| (first..=last).contains(x)),
+            "rooms must open onto the hallway"
+        );
+        ensure!(columns.len() <= 26, "too many rooms");
+
+        let mut rooms = vec![vec![]; columns.len()];
+        for (row_columns, cells) in &room_rows {
+            ensure!(
+                *row_columns == columns,
+                "rooms must all have the same depth"
+            );
+            for (room, &x) in rooms.iter_mut().zip(&columns) {
+                room.push(cells[x].unwrap());
+            }
+        }
+
+        let start = State { hallway, rooms };
+        if let Some(stray) = start
+            .hallway
+            .iter()
+            .chain(start.rooms.iter().flatten())
+            .flatten()
+            .find(|a| a.target_room() >= columns.len())
+        {
+            bail!("amphipod {stray} has no room");
+        }
+
+        let costs = (0..columns.len() as u32)
+            .map(|i| 10usize.checked_pow(i))
+            .collect::<Option<_>>()
+            .with_context(|| format!("energy costs overflow for {} rooms", columns.len()))?;
+        Burrow::new(columns.iter().map(|x| x - first).collect(), costs, start)
     }
 }
 
+#[aoc_generator(day23)]
+fn generator(input: &str) -> anyhow::Result<Burrow> {
+    input.parse()
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 struct SearchState {
+    priority: usize,
     cost: usize,
     state: State,
 }
@@ -234,7 +424,10 @@ struct SearchState {
 impl Ord for SearchState {
     fn cmp(&self, other: &Self) -> Ordering {
         // Reverse ordering for min-heap
-        other.cost.cmp(&self.cost)
+        other
+            .priority
+            .cmp(&self.priority)
+            .then(self.cost.cmp(&other.cost))
     }
 }
 
@@ -244,75 +437,28 @@ impl PartialOrd for SearchState {
     }
 }
 
-fn solve(state: &State) -> usize {
-    let mut heap = BinaryHeap::new();
-    let mut costs = HashMap::new();
-
-    heap.push(SearchState {
-        cost: 0,
-        state: state.clone(),
-    });
-    costs.insert(state.clone(), 0);
-
-    while let Some(SearchState { cost, state }) = heap.pop() {
-        if state.is_complete() {
-            return cost;
-        }
-
-        if cost > *costs.get(&state).unwrap_or(&usize::MAX) {
-            continue;
-        }
-
-        for (next_state, move_cost) in state.generate_moves() {
-            let next_cost = cost + move_cost;
-
-            if next_cost < *costs.get(&next_state).unwrap_or(&usize::MAX) {
-                costs.insert(next_state.clone(), next_cost);
-                heap.push(SearchState {
-                    cost: next_cost,
-                    state: next_state,
-                });
-            }
-        }
-    }
-
-    0
-}
-
 #[aoc(day23, part1)]
-fn part1(input: &State) -> usize {
-    solve(input)
+fn part1(input: &Burrow) -> anyhow::Result<usize> {
+    let solution = input.solve().context("burrow cannot be organised")?;
+    Ok(solution.energy)
 }
 
 #[aoc(day23, part2)]
-fn part2(input: &State) -> usize {
-    use Amphipod::*;
-
-    let extra_amphipods = [
-        [Desert, Desert],
-        [Copper, Bronze],
-        [Bronze, Amber],
-        [Amber, Copper],
-    ];
-
-    let expanded_state = State {
-        hallway: input.hallway,
-        rooms: input
-            .rooms
-            .iter()
-            .zip(extra_amphipods)
-            .map(|(room, extra)| {
-                room.iter()
-                    .take(1)
-                    .chain(extra.iter().map(|&a| Some(a)).collect::<Vec<_>>().iter())
-                    .chain(room.iter().skip(1))
-                    .cloned()
-                    .collect()
-            })
-            .collect(),
-    };
+fn part2(input: &Burrow) -> anyhow::Result<usize> {
+    let solution = input
+        .unfold(&UNFOLDED_ROWS)?
+        .solve()
+        .context("burrow cannot be organised")?;
+    Ok(solution.energy)
+}
 
-    solve(&expanded_state)
+#[aoc(day23, part2, moves)]
+fn part2_moves(input: &Burrow) -> anyhow::Result<String> {
+    let solution = input
+        .unfold(&UNFOLDED_ROWS)?
+        .solve()
+        .context("burrow cannot be organised")?;
+    Ok(solution.to_string())
 }
 
 #[cfg(test)]
@@ -325,108 +471,114 @@ mod tests {
   #A#D#C#A#
   #########";
 
-    #[test]
-    fn test_parse() {
-        let state = generator(EXAMPLE);
-
-        assert_eq!(state.hallway, [None; 11]);
+    fn a(c: char) -> Option<Amphipod> {
+        Amphipod::from_char(c)
+    }
 
-        assert_eq!(state.rooms[0].len(), 2);
-        assert_eq!(state.rooms[0][0], Some(Amphipod::Bronze));
-        assert_eq!(state.rooms[0][1], Some(Amphipod::Amber));
-        assert_eq!(state.rooms[1][0], Some(Amphipod::Copper));
-        assert_eq!(state.rooms[1][1], Some(Amphipod::Desert));
-        assert_eq!(state.rooms[2][0], Some(Amphipod::Bronze));
-        assert_eq!(state.rooms[2][1], Some(Amphipod::Copper));
-        assert_eq!(state.rooms[3][0], Some(Amphipod::Desert));
-        assert_eq!(state.rooms[3][1], Some(Amphipod::Amber));
+    fn replay(burrow: &Burrow, solution: &Solution) -> State {
+        solution
+            .moves
+            .iter()
+            .fold(burrow.start.clone(), |state, step| {
+                let (next, _) = burrow
+                    .moves(&state)
+                    .into_iter()
+                    .find(|(_, candidate)| candidate == step)
+                    .expect("move should be legal");
+                next
+            })
     }
 
     #[test]
-    fn test_is_complete() {
-        let mut state = State {
-            hallway: [None; 11],
-            rooms: vec![vec![None, None]; 4],
-        };
-
-        state.rooms[0][0] = Some(Amphipod::Bronze);
-        assert!(!state.is_complete());
+    fn test_parse() {
+        let burrow = generator(EXAMPLE).unwrap();
+
+        assert_eq!(burrow.start.hallway, vec![None; 11]);
+        assert_eq!(burrow.entrances, [2, 4, 6, 8]);
+        assert_eq!(burrow.costs, [1, 10, 100, 1000]);
+        assert_eq!(
+            burrow.start.rooms,
+            [
+                [a('B'), a('A')],
+                [a('C'), a('D')],
+                [a('B'), a('C')],
+                [a('D'), a('A')]
+            ]
+        );
 
-        state.rooms[0][0] = Some(Amphipod::Amber);
-        state.rooms[0][1] = Some(Amphipod::Amber);
-        state.rooms[1][0] = Some(Amphipod::Bronze);
-        state.rooms[1][1] = Some(Amphipod::Bronze);
-        state.rooms[2][0] = Some(Amphipod::Copper);
-        state.rooms[2][1] = Some(Amphipod::Copper);
-        state.rooms[3][0] = Some(Amphipod::Desert);
-        state.rooms[3][1] = Some(Amphipod::Desert);
-        assert!(state.is_complete());
+        assert!("#####\n#...#\n###E#\n  ###".parse::<Burrow>().is_err());
+        assert!(
+            "#######\n#.....#\n###A#B#\n  #A###"
+                .parse::<Burrow>()
+                .is_err()
+        );
     }
 
     #[test]
-    fn test_amphipod_properties() {
-        assert_eq!(Amphipod::Amber.energy_per_step(), 1);
-        assert_eq!(Amphipod::Bronze.energy_per_step(), 10);
-        assert_eq!(Amphipod::Copper.energy_per_step(), 100);
-        assert_eq!(Amphipod::Desert.energy_per_step(), 1000);
+    fn test_is_complete() {
+        let mut burrow = generator(EXAMPLE).unwrap();
+        assert!(!burrow.is_complete(&burrow.start));
 
-        assert_eq!(Amphipod::Amber.target_room(), 0);
-        assert_eq!(Amphipod::Bronze.target_room(), 1);
-        assert_eq!(Amphipod::Copper.target_room(), 2);
-        assert_eq!(Amphipod::Desert.target_room(), 3);
+        burrow.start.rooms = (0..4).map(|room| vec![Some(Amphipod(room)); 2]).collect();
+        assert!(burrow.is_complete(&burrow.start));
+        assert_eq!(burrow.solve().unwrap().energy, 0);
     }
 
     #[test]
     fn test_generate_moves() {
-        let state = generator(EXAMPLE);
-        let moves = state.generate_moves();
+        let burrow = generator(EXAMPLE).unwrap();
+        let moves = burrow.moves(&burrow.start);
         assert_eq!(moves.len(), 28);
     }
 
     #[test]
     fn test_step_counting() {
-        let state = State {
-            hallway: [None; 11],
-            rooms: vec![vec![Some(Amphipod::Bronze)]; 4],
-        };
+        let burrow = generator(
+            "#############
+#...........#
+###B#B#B#B###
+  #########",
+        )
+        .unwrap();
 
-        let moves = state.generate_moves();
+        let moves = burrow.moves(&burrow.start);
 
         let move_to_0 = moves.iter().find(|(s, _)| s.hallway[0].is_some()).unwrap();
-        assert_eq!(move_to_0.1, 30);
+        assert_eq!(move_to_0.1.energy, 30);
 
         let move_to_3 = moves.iter().find(|(s, _)| s.hallway[3].is_some()).unwrap();
-        assert_eq!(move_to_3.1, 20);
+        assert_eq!(move_to_3.1.energy, 20);
     }
 
     #[test]
     fn test_hallway_to_room_move() {
-        let mut state = State {
-            hallway: [None; 11],
-            rooms: vec![vec![None, None]; 4],
-        };
-
-        state.hallway[3] = Some(Amphipod::Amber);
+        let burrow = generator(
+            "#############
+#...A.......#
+###.#.#.#.###
+  #.#.#.#.#
+  #########",
+        )
+        .unwrap();
 
-        let moves = state.generate_moves();
+        let moves = burrow.moves(&burrow.start);
         let move_to_room = moves.iter().find(|(s, _)| s.rooms[0][1].is_some()).unwrap();
-        assert_eq!(move_to_room.1, 3);
+        assert_eq!(move_to_room.1.energy, 3);
+        assert_eq!(move_to_room.1.to.to_string(), "room A slot 2");
     }
 
     #[test]
     fn test_simple_scenario() {
-        let state = State {
-            hallway: [None; 11],
-            rooms: vec![
-                vec![Some(Amphipod::Amber), Some(Amphipod::Bronze)],
-                vec![Some(Amphipod::Bronze), Some(Amphipod::Amber)],
-                vec![Some(Amphipod::Copper), Some(Amphipod::Copper)],
-                vec![Some(Amphipod::Desert), Some(Amphipod::Desert)],
-            ],
-        };
+        let burrow = generator(
+            "#############
+#...........#
+###A#B#C#D###
+  #B#A#C#D#
+  #########",
+        )
+        .unwrap();
 
-        let result = part1(&state);
-        assert_eq!(result, 112);
+        assert_eq!(part1(&burrow).unwrap(), 112);
     }
 
     #[test]
@@ -437,24 +589,92 @@ mod tests {
 ###D#D#B#A###
   #B#C#A#C#
   #########",
-        );
-        let result = part1(&input);
-        assert_eq!(result, 16244);
+        )
+        .unwrap();
+        assert_eq!(part1(&input).unwrap(), 16244);
     }
 
     #[test]
     fn test_part1() {
-        let input = generator(EXAMPLE);
-        let result = part1(&input);
-        println!("Found cost: {}", result);
-        assert_eq!(result, 12521);
+        let input = generator(EXAMPLE).unwrap();
+        assert_eq!(part1(&input).unwrap(), 12521);
     }
 
     #[test]
     fn test_part2() {
-        let input = generator(EXAMPLE);
-        let result = part2(&input);
-        println!("Part 2 cost: {}", result);
-        assert_eq!(result, 44169);
+        let input = generator(EXAMPLE).unwrap();
+        assert_eq!(part2(&input).unwrap(), 44169);
+    }
+
+    #[test]
+    fn test_move_sequence() {
+        let burrow = generator(EXAMPLE).unwrap();
+        let solution = burrow.solve().unwrap();
+        assert_eq!(
+            solution.moves.iter().map(|step| step.energy).sum::<usize>(),
+            12521
+        );
+        assert!(burrow.is_complete(&replay(&burrow, &solution)));
+        assert!(solution.to_string().ends_with("Total energy: 12521\n"));
+
+        let unfolded = burrow.unfold(&UNFOLDED_ROWS).unwrap();
+        let solution = unfolded.solve().unwrap();
+        assert!(unfolded.is_complete(&replay(&unfolded, &solution)));
+    }
+
+    #[test]
+    fn test_other_shapes() {
+        let burrow = generator(
+            "#########
+#.......#
+###B#A###
+  #A#B#
+  #A#B#
+  #####",
+        )
+        .unwrap();
+        assert_eq!(burrow.entrances, [2, 4]);
+        assert_eq!(part1(&burrow).unwrap(), 46);
+
+        let narrow = generator(
+            "#######
+#.....#
+###BA##
+  #AB#
+  ####",
+        )
+        .unwrap();
+        assert_eq!(narrow.entrances, [2, 3]);
+        let solution = narrow.solve().unwrap();
+        assert!(narrow.is_complete(&replay(&narrow, &solution)));
+
+        let narrow = Burrow::new(narrow.entrances, vec![5, 5], narrow.start).unwrap();
+        let solution = narrow.solve().unwrap();
+        assert!(narrow.is_complete(&replay(&narrow, &solution)));
+        assert_eq!(solution.energy % 5, 0);
+        assert!(Burrow::new(vec![2, 3], vec![5], narrow.start).is_err());
+
+        let unsolvable = generator(
+            "#######
+#.....#
+###A#A#
+  #A#A#
+  #####",
+        )
+        .unwrap();
+        assert!(part1(&unsolvable).is_err());
+    }
+
+    #[test]
+    fn test_cost_overflow() {
+        let kinds = ('A'..='U').join("#");
+        let input = format!(
+            "{}\n#{}#\n###{kinds}###\n  #{kinds}#\n  {}",
+            "#".repeat(47),
+            ".".repeat(45),
+            "#".repeat(43)
+        );
+        let error = generator(&input).unwrap_err();
+        assert!(error.to_string().contains("overflow"), "{error}");
     }
 }
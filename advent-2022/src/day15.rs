@@ -1,5 +1,6 @@
 use std::collections::{HashMap, HashSet};
 
+use advent_common::interval::IntervalSet;
 use anyhow::anyhow;
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::{Itertools, iproduct};
@@ -74,26 +75,26 @@ fn generator(input: &str) -> anyhow::Result<HashMap<Point, Point>> {
         .map(|(_, o)| o)
 }
 
-#[aoc(day15, part1)]
-fn part1(input: &HashMap<Point, Point>) -> usize {
-    let beacons = input.iter().map(|(_, &b)| b).collect::<HashSet<Point>>();
-    let distances = input
-        .iter()
-        .map(|(&s, &b)| (s, s.distance(b)))
-        .collect::<HashMap<Point, i64>>();
-    let (minx, maxx) = distances
+fn row_coverage(input: &HashMap<Point, Point>, row: i64) -> IntervalSet<i64> {
+    let covered = input
         .iter()
-        .flat_map(|(Point(x, _), d)| [x - d, x + d])
-        .minmax()
-        .into_option()
-        .unwrap();
-
-    (minx..=maxx)
-        .filter(|&tx| {
-            let t = Point(tx, TARGET_ROW);
-            !beacons.contains(&t) && distances.iter().any(|(&s, &d)| s.distance(t) <= d)
+        .filter_map(|(&s, &b)| {
+            let Point(sx, sy) = s;
+            let reach = s.distance(b) - (sy - row).abs();
+            (reach >= 0).then(|| sx - reach..=sx + reach)
         })
-        .count()
+        .collect::<IntervalSet<_>>();
+    let beacons = input
+        .values()
+        .filter(|&&Point(_, by)| by == row)
+        .map(|&Point(bx, _)| bx..=bx)
+        .collect();
+    covered.difference(&beacons)
+}
+
+#[aoc(day15, part1)]
+fn part1(input: &HashMap<Point, Point>) -> u128 {
+    row_coverage(input, TARGET_ROW).len()
 }
 
 #[aoc(day15, part2)]
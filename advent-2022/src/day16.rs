@@ -1,6 +1,6 @@
-use std::{collections::HashMap, iter};
+use std::{collections::HashMap, fmt, iter};
 
-use anyhow::anyhow;
+use anyhow::{Context, anyhow, ensure};
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::{Itertools, iproduct};
 use nom::{
@@ -11,7 +11,7 @@ use nom::{
     combinator::map,
     multi::separated_list1,
 };
-use smallvec::{SmallVec, smallvec};
+use smallvec::SmallVec;
 
 const START: &str = "AA";
 
@@ -90,65 +90,301 @@ fn generator(input: &str) -> anyhow::Result<Input> {
     Ok(result)
 }
 
-fn search(
-    input: &Input,
-    current: usize,
-    candidates: Vec<usize>,
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Opening {
+    minute: u8,
+    agent: usize,
+    valve: String,
+}
+
+#[derive(Debug, Default)]
+struct Schedule {
+    pressure: u32,
+    openings: Vec<Opening>,
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.pressure)?;
+        for opening in &self.openings {
+            writeln!(
+                f,
+                "== Minute {} == agent {} opens valve {}",
+                opening.minute, opening.agent, opening.valve
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Agent {
     time: u8,
-    elephant: bool,
-    cache: &mut HashMap<(usize, Vec<usize>, bool, u8), u32>,
-) -> u32 {
-    if let Some(&value) = cache.get(&(current, candidates.clone(), elephant, time)) {
-        return value;
+    position: usize,
+}
+
+struct Scheduler {
+    flow_rates: Vec<u32>,
+    distances: Vec<Vec<u8>>,
+    start: usize,
+    best: u32,
+    best_plan: Vec<(usize, usize, u8)>,
+    plan: Vec<(usize, usize, u8)>,
+    seen: HashMap<(u64, SmallVec<[Agent; 4]>), u32>,
+}
+
+impl Scheduler {
+    fn upper_bound(&self, closed: u64, agents: &[Agent]) -> u32 {
+        let count = closed.count_ones() as usize;
+        let mut slots = agents
+            .iter()
+            .filter(|agent| agent.time > 0)
+            .flat_map(|agent| {
+                let nearest = bits(closed)
+                    .map(|valve| self.distances[agent.position][valve])
+                    .min()
+                    .unwrap_or(u8::MAX);
+                let first = agent.time.saturating_sub(nearest.saturating_add(1));
+                iter::successors(Some(first), |&t| Some(t.saturating_sub(2)))
+                    .take_while(|&t| t > 0)
+                    .take(count)
+            })
+            .collect::<SmallVec<[u8; 64]>>();
+        slots.sort_unstable_by(|a, b| b.cmp(a));
+
+        bits(closed)
+            .zip(slots)
+            .map(|(valve, time)| self.flow_rates[valve] * time as u32)
+            .sum()
     }
 
-    let result = candidates
-        .iter()
-        .positions(|&c| input.distances[&(current, c)] < time)
-        .flat_map(|i| {
-            let mut results: SmallVec<[u32; 2]> = smallvec![];
-            let mut next_candidates = candidates.clone();
-            let next = next_candidates.swap_remove(i);
-            let next_time_remaining = time - input.distances[&(current, next)] - 1;
-            let next_rate = input.flow_rates[next] * next_time_remaining as u32;
-            let future_rate = search(
-                input,
-                next,
-                next_candidates,
-                next_time_remaining,
-                elephant,
-                cache,
-            );
+    fn search(&mut self, closed: u64, agents: &mut [Agent], pressure: u32) {
+        if pressure > self.best {
+            self.best = pressure;
+            self.best_plan.clone_from(&self.plan);
+        }
 
-            let answer = next_rate + future_rate;
-            results.push(answer);
+        if pressure + self.upper_bound(closed, agents) <= self.best {
+            return;
+        }
 
-            if elephant {
-                let start = input.names.iter().position(|n| n == "AA").unwrap();
-                let answer = search(input, start, candidates.clone(), 26, false, cache);
-                results.push(answer);
+        let mut key = agents.iter().copied().collect::<SmallVec<[Agent; 4]>>();
+        key.sort_unstable();
+        match self.seen.get(&(closed, key.clone())) {
+            Some(&seen) if seen >= pressure => return,
+            _ => {
+                self.seen.insert((closed, key), pressure);
             }
+        }
+
+        let (index, agent) = agents
+            .iter()
+            .copied()
+            .enumerate()
+            .max_by(|(i, a), (j, b)| a.time.cmp(&b.time).then(j.cmp(i)))
+            .unwrap();
+
+        let moves = bits(closed)
+            .filter_map(|valve| {
+                let travel = self.distances[agent.position][valve].checked_add(1)?;
+                let remaining = agent.time.checked_sub(travel)?;
+                (remaining > 0).then_some((valve, remaining))
+            })
+            .sorted_by_key(|&(valve, remaining)| {
+                std::cmp::Reverse(self.flow_rates[valve] * remaining as u32)
+            })
+            .collect::<SmallVec<[(usize, u8); 64]>>();
+
+        for (valve, remaining) in moves {
+            agents[index] = Agent {
+                time: remaining,
+                position: valve,
+            };
+            self.plan.push((index, valve, remaining));
+            self.search(
+                closed & !(1 << valve),
+                agents,
+                pressure + self.flow_rates[valve] * remaining as u32,
+            );
+            self.plan.pop();
+        }
+
+        agents[index] = Agent {
+            time: 0,
+            position: self.start,
+        };
+        self.search(closed, agents, pressure);
+        agents[index] = agent;
+    }
+}
+
+fn bits(mask: u64) -> impl Iterator<Item = usize> {
+    iter::successors(Some(mask), |&m| Some(m & m.wrapping_sub(1)))
+        .take_while(|&m| m != 0)
+        .map(|m| m.trailing_zeros() as usize)
+}
 
-            results
+fn schedule(input: &Input, budgets: &[u8]) -> anyhow::Result<Schedule> {
+    let start = input
+        .names
+        .iter()
+        .position(|n| n == START)
+        .context("Missing start valve")?;
+    let valves = (0..input.names.len())
+        .filter(|&i| input.flow_rates[i] > 0)
+        .sorted_by_key(|&i| std::cmp::Reverse(input.flow_rates[i]))
+        .collect_vec();
+    ensure!(
+        valves.len() <= 64,
+        "Too many valves with flow: {}",
+        valves.len()
+    );
+
+    let nodes = valves
+        .iter()
+        .copied()
+        .chain(iter::once(start))
+        .collect_vec();
+    let distances = nodes
+        .iter()
+        .map(|&from| {
+            nodes
+                .iter()
+                .map(|&to| input.distances[&(from, to)])
+                .collect_vec()
+        })
+        .collect_vec();
+
+    let mut scheduler = Scheduler {
+        flow_rates: valves.iter().map(|&i| input.flow_rates[i]).collect(),
+        distances,
+        start: valves.len(),
+        best: 0,
+        best_plan: vec![],
+        plan: vec![],
+        seen: HashMap::new(),
+    };
+    let mut agents = budgets
+        .iter()
+        .map(|&time| Agent {
+            time,
+            position: scheduler.start,
         })
-        .max()
-        .unwrap_or_default();
-    cache.insert((current, candidates, elephant, time), result);
-    result
+        .collect_vec();
+    let closed = (0..valves.len()).fold(0u64, |mask, i| mask | 1 << i);
+    scheduler.search(closed, &mut agents, 0);
+
+    let openings = scheduler
+        .best_plan
+        .iter()
+        .map(|&(agent, valve, remaining)| Opening {
+            minute: budgets[agent] - remaining,
+            agent,
+            valve: input.names[valves[valve]].clone(),
+        })
+        .sorted_by_key(|opening| (opening.minute, opening.agent))
+        .collect();
+
+    Ok(Schedule {
+        pressure: scheduler.best,
+        openings,
+    })
 }
 
 #[aoc(day16, part1)]
-fn part1(input: &Input) -> u32 {
-    let candidates = input.flow_rates.iter().positions(|&r| r > 0).collect_vec();
-    let start = input.names.iter().position(|n| n == START).unwrap();
-    let mut cache = HashMap::new();
-    search(input, start, candidates, 30, false, &mut cache)
+fn part1(input: &Input) -> anyhow::Result<u32> {
+    schedule(input, &[30]).map(|schedule| schedule.pressure)
 }
 
 #[aoc(day16, part2)]
-fn part2(input: &Input) -> u32 {
-    let candidates = input.flow_rates.iter().positions(|&r| r > 0).collect_vec();
-    let start = input.names.iter().position(|n| n == START).unwrap();
-    let mut cache = HashMap::new();
-    search(input, start, candidates, 26, true, &mut cache)
+fn part2(input: &Input) -> anyhow::Result<u32> {
+    schedule(input, &[26, 26]).map(|schedule| schedule.pressure)
+}
+
+#[aoc(day16, part2, schedule)]
+fn part2_schedule(input: &Input) -> anyhow::Result<Schedule> {
+    schedule(input, &[26, 26])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "Valve AA has flow rate=0; tunnels lead to valves DD, II, BB
+Valve BB has flow rate=13; tunnels lead to valves CC, AA
+Valve CC has flow rate=2; tunnels lead to valves DD, BB
+Valve DD has flow rate=20; tunnels lead to valves CC, AA, EE
+Valve EE has flow rate=3; tunnels lead to valves FF, DD
+Valve FF has flow rate=0; tunnels lead to valves EE, GG
+Valve GG has flow rate=0; tunnels lead to valves FF, HH
+Valve HH has flow rate=22; tunnel leads to valve GG
+Valve II has flow rate=0; tunnels lead to valves AA, JJ
+Valve JJ has flow rate=21; tunnel leads to valve II";
+
+    #[test]
+    fn test_part1() {
+        let input = generator(EXAMPLE).unwrap();
+        assert_eq!(part1(&input).unwrap(), 1651);
+    }
+
+    #[test]
+    fn test_part2() {
+        let input = generator(EXAMPLE).unwrap();
+        assert_eq!(part2(&input).unwrap(), 1707);
+    }
+
+    #[test]
+    fn test_schedule_openings() {
+        let input = generator(EXAMPLE).unwrap();
+        let schedule = schedule(&input, &[30]).unwrap();
+        let openings = schedule
+            .openings
+            .iter()
+            .map(|opening| (opening.minute, opening.valve.as_str()))
+            .collect_vec();
+        assert_eq!(
+            openings,
+            [
+                (2, "DD"),
+                (5, "BB"),
+                (9, "JJ"),
+                (17, "HH"),
+                (21, "EE"),
+                (24, "CC")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_schedule_agents() {
+        let input = generator(EXAMPLE).unwrap();
+        let schedule = schedule(&input, &[26, 26, 20]).unwrap();
+        let pressure = schedule
+            .openings
+            .iter()
+            .map(|opening| {
+                let budget = [26, 26, 20][opening.agent];
+                let valve = input
+                    .names
+                    .iter()
+                    .position(|n| *n == opening.valve)
+                    .unwrap();
+                input.flow_rates[valve] * (budget - opening.minute) as u32
+            })
+            .sum::<u32>();
+        assert_eq!(pressure, schedule.pressure);
+        assert!(schedule.pressure >= 1707);
+    }
+
+    #[test]
+    fn test_unreachable_valves() {
+        let input = generator(
+            "Valve AA has flow rate=0; tunnel leads to valve BB
+Valve BB has flow rate=5; tunnel leads to valve AA
+Valve CC has flow rate=7; tunnel leads to valve DD
+Valve DD has flow rate=0; tunnel leads to valve CC",
+        )
+        .unwrap();
+        assert_eq!(part1(&input).unwrap(), 140);
+    }
 }
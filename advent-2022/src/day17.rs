@@ -1,5 +1,6 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 
+use advent_common::cycle::find_cycle;
 use aoc_runner_derive::{aoc, aoc_generator};
 
 #[derive(Debug, Clone, Copy)]
@@ -47,6 +48,8 @@ struct Chamber {
     occupied: HashSet<(i64, i64)>,
     max_height: i64,
     width: i64,
+    rocks: usize,
+    jet_index: usize,
 }
 
 impl Chamber {
@@ -55,6 +58,8 @@ impl Chamber {
             occupied: HashSet::new(),
             max_height: 0,
             width: 7,
+            rocks: 0,
+            jet_index: 0,
         }
     }
 
@@ -88,37 +93,23 @@ fn part2(jets: &[char]) -> usize {
 }
 
 fn simulate_rocks(jets: &[char], num_rocks: usize) -> usize {
-    let mut chamber = Chamber::new();
-    let mut jet_index = 0;
-    let mut seen: HashMap<(usize, usize, Vec<i64>), (usize, i64)> = HashMap::new();
-
-    for rock_count in 0..num_rocks {
-        let rock_index = rock_count % Rock::SHAPES.len();
-        let state_key = (
-            rock_index,
-            jet_index % jets.len(),
-            get_top_profile(&chamber),
-        );
-
-        if let Some(&(prev_rock_count, prev_height)) = seen.get(&state_key) {
-            let cycle_length = rock_count - prev_rock_count;
-            let height_per_cycle = chamber.max_height - prev_height;
-            let remaining_rocks = num_rocks - rock_count;
-            let full_cycles = remaining_rocks / cycle_length;
-            let partial_cycle = remaining_rocks % cycle_length;
-
-            (0..partial_cycle).for_each(|i| {
-                drop_rock(&mut chamber, jets, &mut jet_index, rock_count + i);
-            });
-
-            return (chamber.max_height + full_cycles as i64 * height_per_cycle) as usize;
-        }
-
-        seen.insert(state_key, (rock_count, chamber.max_height));
-        drop_rock(&mut chamber, jets, &mut jet_index, rock_count);
-    }
-
-    chamber.max_height as usize
+    let cycle = find_cycle(
+        Chamber::new(),
+        |mut chamber| {
+            drop_rock(&mut chamber, jets);
+            chamber
+        },
+        |chamber| {
+            (
+                chamber.rocks % Rock::SHAPES.len(),
+                chamber.jet_index % jets.len(),
+                get_top_profile(chamber),
+            )
+        },
+        |chamber| chamber.max_height,
+    );
+
+    cycle.extrapolate(num_rocks) as usize
 }
 
 fn get_top_profile(chamber: &Chamber) -> Vec<i64> {
@@ -133,13 +124,14 @@ fn get_top_profile(chamber: &Chamber) -> Vec<i64> {
         .collect()
 }
 
-fn drop_rock(chamber: &mut Chamber, jets: &[char], jet_index: &mut usize, rock_count: usize) {
-    let rock = Rock::SHAPES[rock_count % Rock::SHAPES.len()];
+fn drop_rock(chamber: &mut Chamber, jets: &[char]) {
+    let rock = Rock::SHAPES[chamber.rocks % Rock::SHAPES.len()];
+    chamber.rocks += 1;
     let (mut x, mut y) = (2, chamber.max_height + 4);
 
     loop {
-        let jet = jets[*jet_index % jets.len()];
-        *jet_index += 1;
+        let jet = jets[chamber.jet_index % jets.len()];
+        chamber.jet_index += 1;
 
         let new_x = x + if jet == '<' { -1 } else { 1 };
         let horizontal_positions = rock.get_shape(new_x, y);
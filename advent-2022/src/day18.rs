@@ -1,7 +1,8 @@
 use std::collections::{HashSet, VecDeque};
 
+use advent_common::cuboid::{Cuboid, CuboidSet};
 use aoc_runner_derive::{aoc, aoc_generator};
-use itertools::Itertools;
+use itertools::{Itertools, iproduct};
 
 type Point = (i32, i32, i32);
 
@@ -30,21 +31,22 @@ fn generator(input: &str) -> anyhow::Result<HashSet<Point>> {
         .collect()
 }
 
+fn voxel((x, y, z): Point) -> Cuboid {
+    Cuboid::point([x, y, z].map(i64::from))
+}
+
 #[aoc(day18, part1)]
-fn part1(cubes: &HashSet<Point>) -> usize {
+fn part1(cubes: &HashSet<Point>) -> u64 {
     cubes
         .iter()
-        .flat_map(|&(x, y, z)| {
-            DIRECTIONS
-                .iter()
-                .map(move |(dx, dy, dz)| (x + dx, y + dy, z + dz))
-        })
-        .filter(|neighbor| !cubes.contains(neighbor))
-        .count()
+        .copied()
+        .map(voxel)
+        .collect::<CuboidSet>()
+        .surface_area()
 }
 
 #[aoc(day18, part2)]
-fn part2(cubes: &HashSet<Point>) -> usize {
+fn part2(cubes: &HashSet<Point>) -> u64 {
     let (min_x, max_x) = cubes
         .iter()
         .map(|&(x, _, _)| x)
@@ -72,15 +74,11 @@ fn part2(cubes: &HashSet<Point>) -> usize {
 
     let reachable = flood_fill(cubes, bounds);
 
-    cubes
-        .iter()
-        .flat_map(|&(x, y, z)| {
-            DIRECTIONS
-                .iter()
-                .map(move |(dx, dy, dz)| (x + dx, y + dy, z + dz))
-        })
-        .filter(|neighbor| reachable.contains(neighbor))
-        .count()
+    iproduct!(min_x..=max_x, min_y..=max_y, min_z..=max_z)
+        .filter(|point| !reachable.contains(point))
+        .map(voxel)
+        .collect::<CuboidSet>()
+        .surface_area()
 }
 
 fn flood_fill(
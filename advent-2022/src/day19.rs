@@ -1,208 +1,295 @@
-use std::str::FromStr;
+use std::{fmt, str::FromStr};
 
-use anyhow::{Context, ensure};
+use anyhow::{Context, bail, ensure};
 use aoc_runner_derive::{aoc, aoc_generator};
+use itertools::Itertools;
+use rayon::prelude::*;
 
-#[derive(Debug, Clone, Copy)]
+const MAX_RESOURCES: usize = 8;
+
+type Resources = [u64; MAX_RESOURCES];
+
+#[derive(Debug, Clone)]
 struct Blueprint {
     id: usize,
-    ore_robot_ore: u8,
-    clay_robot_ore: u8,
-    obsidian_robot_ore: u8,
-    obsidian_robot_clay: u8,
-    geode_robot_ore: u8,
-    geode_robot_obsidian: u8,
+    resources: Vec<String>,
+    costs: Vec<Resources>,
+    max_needed: Resources,
 }
 
 impl Blueprint {
-    fn max_ore_needed(&self) -> u16 {
-        [
-            self.ore_robot_ore,
-            self.clay_robot_ore,
-            self.obsidian_robot_ore,
-            self.geode_robot_ore,
-        ]
-        .into_iter()
-        .max()
-        .unwrap() as u16
+    fn target(&self) -> usize {
+        self.resources.len() - 1
     }
 }
 
+fn parse_robot(sentence: &str) -> anyhow::Result<(&str, Vec<(u16, &str)>)> {
+    let (robot, costs) = sentence
+        .strip_prefix("Each ")
+        .and_then(|s| s.split_once(" robot costs "))
+        .with_context(|| format!("Invalid robot description: {sentence}"))?;
+    let costs = costs
+        .split(" and ")
+        .map(|cost| {
+            let (amount, resource) = cost
+                .split_once(' ')
+                .with_context(|| format!("Invalid cost: {cost}"))?;
+            let amount = amount
+                .parse()
+                .with_context(|| format!("Invalid cost amount: {amount}"))?;
+            Ok((amount, resource))
+        })
+        .collect::<anyhow::Result<_>>()?;
+    Ok((robot, costs))
+}
+
 impl FromStr for Blueprint {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let parts: Vec<&str> = s.split_whitespace().collect();
-        ensure!(parts.len() >= 32, "Invalid blueprint format");
+        let (id, robots) = s
+            .trim()
+            .strip_prefix("Blueprint ")
+            .and_then(|s| s.split_once(':'))
+            .context("Invalid blueprint format")?;
+        let id = id.parse().context("Invalid ID")?;
+        let robots = robots
+            .split('.')
+            .map(|sentence| sentence.split_whitespace().join(" "))
+            .filter(|sentence| !sentence.is_empty())
+            .collect_vec();
+        let robots = robots
+            .iter()
+            .map(|sentence| parse_robot(sentence))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        ensure!(!robots.is_empty(), "Blueprint {id} has no robots");
+        ensure!(
+            robots.len() <= MAX_RESOURCES,
+            "Blueprint {id} has more than {MAX_RESOURCES} resources"
+        );
+
+        let resources = robots
+            .iter()
+            .map(|&(robot, _)| robot.to_string())
+            .collect_vec();
+        ensure!(
+            resources.iter().all_unique(),
+            "Blueprint {id} describes a robot twice"
+        );
+
+        let mut costs = vec![[0; MAX_RESOURCES]; robots.len()];
+        for (robot, (_, robot_costs)) in robots.iter().enumerate() {
+            for &(amount, resource) in robot_costs {
+                let Some(index) = resources.iter().position(|r| r == resource) else {
+                    bail!("Blueprint {id} uses unknown resource {resource}");
+                };
+                costs[robot][index] += u64::from(amount);
+            }
+        }
+
+        let mut max_needed = [0; MAX_RESOURCES];
+        for cost in &costs {
+            for (needed, &amount) in max_needed.iter_mut().zip(cost) {
+                *needed = (*needed).max(amount);
+            }
+        }
 
         Ok(Blueprint {
-            id: parts[1]
-                .trim_end_matches(':')
-                .parse()
-                .context("Invalid ID")?,
-            ore_robot_ore: parts[6].parse().context("Invalid ore robot ore cost")?,
-            clay_robot_ore: parts[12].parse().context("Invalid clay robot ore cost")?,
-            obsidian_robot_ore: parts[18]
-                .parse()
-                .context("Invalid obsidian robot ore cost")?,
-            obsidian_robot_clay: parts[21]
-                .parse()
-                .context("Invalid obsidian robot clay cost")?,
-            geode_robot_ore: parts[27].parse().context("Invalid geode robot ore cost")?,
-            geode_robot_obsidian: parts[30]
-                .parse()
-                .context("Invalid geode robot obsidian cost")?,
+            id,
+            resources,
+            costs,
+            max_needed,
         })
     }
 }
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
 struct State {
-    time: u8,
-    ore: u16,
-    clay: u16,
-    obsidian: u16,
-    geodes: u16,
-    ore_robots: u16,
-    clay_robots: u16,
-    obsidian_robots: u16,
-    geode_robots: u16,
+    time: u16,
+    stock: Resources,
+    robots: Resources,
 }
 
 impl State {
-    fn advance_time(&self) -> Self {
-        Self {
-            time: self.time + 1,
-            ore: self.ore + self.ore_robots,
-            clay: self.clay + self.clay_robots,
-            obsidian: self.obsidian + self.obsidian_robots,
-            geodes: self.geodes + self.geode_robots,
-            ..*self
-        }
-    }
-
-    fn build_ore_robot(&self, cost: u16) -> Self {
-        let mut next = self.advance_time();
-        next.ore -= cost;
-        next.ore_robots += 1;
-        next
+    fn wait_for(&self, cost: &Resources) -> Option<u16> {
+        self.stock
+            .iter()
+            .zip(&self.robots)
+            .zip(cost)
+            .map(|((&stock, &robots), &cost)| {
+                if stock >= cost {
+                    Some(0)
+                } else if robots == 0 {
+                    None
+                } else {
+                    Some((cost - stock).div_ceil(robots))
+                }
+            })
+            .try_fold(0, |acc, wait| Some(acc.max(wait?)))
+            .and_then(|wait| u16::try_from(wait).ok())
     }
 
-    fn build_clay_robot(&self, cost: u16) -> Self {
-        let mut next = self.advance_time();
-        next.ore -= cost;
-        next.clay_robots += 1;
+    fn build(&self, robot: usize, cost: &Resources, wait: u16) -> Self {
+        let mut next = *self;
+        let minutes = u64::from(wait) + 1;
+        next.time += wait + 1;
+        for ((stock, &robots), &cost) in next.stock.iter_mut().zip(&self.robots).zip(cost) {
+            *stock = *stock + robots * minutes - cost;
+        }
+        next.robots[robot] += 1;
         next
     }
+}
 
-    fn build_obsidian_robot(&self, ore_cost: u16, clay_cost: u16) -> Self {
-        let mut next = self.advance_time();
-        next.ore -= ore_cost;
-        next.clay -= clay_cost;
-        next.obsidian_robots += 1;
-        next
-    }
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Build {
+    minute: u16,
+    robot: String,
+}
 
-    fn build_geode_robot(&self, ore_cost: u16, obsidian_cost: u16) -> Self {
-        let mut next = self.advance_time();
-        next.ore -= ore_cost;
-        next.obsidian -= obsidian_cost;
-        next.geode_robots += 1;
-        next
-    }
+#[derive(Debug, Clone, Default)]
+struct Plan {
+    id: usize,
+    target: u64,
+    builds: Vec<Build>,
+}
 
-    fn max_possible_geodes(&self, time_left: u8) -> u16 {
-        self.geodes
-            + self.geode_robots * time_left as u16
-            + (time_left as u16 * (time_left as u16 - 1)) / 2
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Blueprint {}: {}", self.id, self.target)?;
+        for build in &self.builds {
+            writeln!(
+                f,
+                "== Minute {} == Start building {}-collecting robot.",
+                build.minute, build.robot
+            )?;
+        }
+        Ok(())
     }
 }
 
-#[aoc_generator(day19)]
-fn generator(input: &str) -> anyhow::Result<Vec<Blueprint>> {
-    input.lines().map(|line| line.parse()).collect()
+struct Search<'a> {
+    blueprint: &'a Blueprint,
+    max_time: u16,
+    best: u64,
+    best_builds: Vec<(u16, usize)>,
+    builds: Vec<(u16, usize)>,
 }
 
-fn max_geodes(blueprint: &Blueprint, max_time: u8) -> u16 {
-    let mut best = 0;
-    let mut stack = vec![State {
-        time: 0,
-        ore_robots: 1,
-        ..Default::default()
-    }];
+impl Search<'_> {
+    fn final_target(&self, state: &State) -> u64 {
+        let target = self.blueprint.target();
+        state.stock[target] + state.robots[target] * u64::from(self.max_time - state.time)
+    }
 
-    let max_ore_needed = blueprint.max_ore_needed();
+    fn max_possible_target(&self, state: &State) -> u64 {
+        let time_left = u64::from(self.max_time - state.time);
+        self.final_target(state) + time_left * time_left.saturating_sub(1) / 2
+    }
 
-    while let Some(state) = stack.pop() {
-        if state.time == max_time {
-            best = best.max(state.geodes);
-            continue;
+    fn search(&mut self, state: State) {
+        let final_target = self.final_target(&state);
+        if final_target > self.best {
+            self.best = final_target;
+            self.best_builds.clone_from(&self.builds);
         }
 
-        let time_left = max_time - state.time;
-
-        if state.max_possible_geodes(time_left) <= best {
-            continue;
+        if self.max_possible_target(&state) <= self.best {
+            return;
         }
 
-        if state.ore >= blueprint.geode_robot_ore as u16
-            && state.obsidian >= blueprint.geode_robot_obsidian as u16
-        {
-            stack.push(state.build_geode_robot(
-                blueprint.geode_robot_ore as u16,
-                blueprint.geode_robot_obsidian as u16,
-            ));
-            continue;
-        }
+        let count = self.blueprint.resources.len();
+        let target = self.blueprint.target();
+        let time_left = u64::from(self.max_time - state.time);
 
-        if state.ore >= blueprint.obsidian_robot_ore as u16
-            && state.clay >= blueprint.obsidian_robot_clay as u16
-            && state.obsidian_robots < blueprint.geode_robot_obsidian as u16
-        {
-            stack.push(state.build_obsidian_robot(
-                blueprint.obsidian_robot_ore as u16,
-                blueprint.obsidian_robot_clay as u16,
-            ));
-        }
+        for robot in (0..count).rev() {
+            if robot != target {
+                let needed = self.blueprint.max_needed[robot];
+                if state.robots[robot] >= needed
+                    || state.stock[robot] + state.robots[robot] * time_left >= needed * time_left
+                {
+                    continue;
+                }
+            }
 
-        if state.ore >= blueprint.clay_robot_ore as u16
-            && state.clay_robots < blueprint.obsidian_robot_clay as u16
-        {
-            stack.push(state.build_clay_robot(blueprint.clay_robot_ore as u16));
-        }
+            let cost = &self.blueprint.costs[robot];
+            let Some(wait) = state.wait_for(cost) else {
+                continue;
+            };
+            if u32::from(state.time) + u32::from(wait) + 1 >= u32::from(self.max_time) {
+                continue;
+            }
 
-        if state.ore >= blueprint.ore_robot_ore as u16 && state.ore_robots < max_ore_needed {
-            stack.push(state.build_ore_robot(blueprint.ore_robot_ore as u16));
+            self.builds.push((state.time + wait + 1, robot));
+            self.search(state.build(robot, cost, wait));
+            self.builds.pop();
         }
+    }
+}
 
-        if state.ore < max_ore_needed * 2 || state.clay < blueprint.obsidian_robot_clay as u16 * 2 {
-            stack.push(state.advance_time());
-        }
+fn optimise(blueprint: &Blueprint, max_time: u16) -> Plan {
+    let mut search = Search {
+        blueprint,
+        max_time,
+        best: 0,
+        best_builds: vec![],
+        builds: vec![],
+    };
+
+    let mut start = State::default();
+    start.robots[0] = 1;
+    search.search(start);
+
+    Plan {
+        id: blueprint.id,
+        target: search.best,
+        builds: search
+            .best_builds
+            .into_iter()
+            .map(|(minute, robot)| Build {
+                minute,
+                robot: blueprint.resources[robot].clone(),
+            })
+            .collect(),
     }
+}
 
-    best
+#[aoc_generator(day19)]
+fn generator(input: &str) -> anyhow::Result<Vec<Blueprint>> {
+    input
+        .split("Blueprint")
+        .filter(|chunk| !chunk.trim().is_empty())
+        .map(|chunk| format!("Blueprint{chunk}").parse())
+        .collect()
 }
 
 #[aoc(day19, part1)]
 fn part1(blueprints: &[Blueprint]) -> usize {
     blueprints
-        .iter()
-        .map(|blueprint| {
-            let geodes = max_geodes(blueprint, 24) as usize;
-            blueprint.id * geodes
-        })
+        .par_iter()
+        .map(|blueprint| blueprint.id * optimise(blueprint, 24).target as usize)
         .sum()
 }
 
 #[aoc(day19, part2)]
 fn part2(blueprints: &[Blueprint]) -> usize {
     blueprints
-        .iter()
+        .par_iter()
         .take(3)
-        .map(|blueprint| max_geodes(blueprint, 32) as usize)
+        .map(|blueprint| optimise(blueprint, 32).target as usize)
         .product()
 }
 
+#[aoc(day19, part2, plans)]
+fn part2_plans(blueprints: &[Blueprint]) -> String {
+    blueprints
+        .par_iter()
+        .take(3)
+        .map(|blueprint| optimise(blueprint, 32).to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -210,6 +297,35 @@ mod tests {
     const EXAMPLE: &str = "Blueprint 1: Each ore robot costs 4 ore. Each clay robot costs 2 ore. Each obsidian robot costs 3 ore and 14 clay. Each geode robot costs 2 ore and 7 obsidian.
 Blueprint 2: Each ore robot costs 2 ore. Each clay robot costs 3 ore. Each obsidian robot costs 3 ore and 8 clay. Each geode robot costs 3 ore and 12 obsidian.";
 
+    fn replay(blueprint: &Blueprint, plan: &Plan, max_time: u16) -> u64 {
+        let mut state = State::default();
+        state.robots[0] = 1;
+        let mut builds = plan.builds.iter().peekable();
+        for minute in 1..=max_time {
+            match builds.next_if(|build| build.minute == minute) {
+                Some(build) => {
+                    let robot = blueprint
+                        .resources
+                        .iter()
+                        .position(|r| *r == build.robot)
+                        .unwrap();
+                    let cost = &blueprint.costs[robot];
+                    assert_eq!(state.wait_for(cost), Some(0));
+                    state = state.build(robot, cost, 0);
+                }
+                None => {
+                    let robots = state.robots;
+                    for (stock, robots) in state.stock.iter_mut().zip(robots) {
+                        *stock += robots;
+                    }
+                    state.time += 1;
+                }
+            }
+        }
+        assert!(builds.next().is_none());
+        state.stock[blueprint.target()]
+    }
+
     #[test]
     fn test_part1() {
         let input = generator(EXAMPLE).unwrap();
@@ -221,4 +337,55 @@ Blueprint 2: Each ore robot costs 2 ore. Each clay robot costs 3 ore. Each obsid
         let input = generator(EXAMPLE).unwrap();
         assert_eq!(part2(&input), 56 * 62);
     }
+
+    #[test]
+    fn test_wrapped_example() {
+        let wrapped = EXAMPLE.replace(". ", ".\n  ");
+        let input = generator(&wrapped).unwrap();
+        assert_eq!(part1(&input), 33);
+    }
+
+    #[test]
+    fn test_plan_replays() {
+        let input = generator(EXAMPLE).unwrap();
+        for max_time in [10, 24, 28] {
+            for blueprint in &input {
+                let plan = optimise(blueprint, max_time);
+                assert_eq!(replay(blueprint, &plan, max_time), plan.target);
+            }
+        }
+        let plan = optimise(&input[0], 24);
+        assert_eq!(plan.builds[0].minute, 3);
+        assert_eq!(plan.builds[0].robot, "clay");
+    }
+
+    #[test]
+    fn test_extra_tier() {
+        let blueprint: Blueprint = "Blueprint 7: Each ore robot costs 4 ore. Each clay robot costs 2 ore. Each obsidian robot costs 3 ore and 14 clay. Each geode robot costs 2 ore and 7 obsidian. Each diamond robot costs 3 ore and 4 geode."
+            .parse()
+            .unwrap();
+        assert_eq!(blueprint.resources.len(), 5);
+        let plan = optimise(&blueprint, 32);
+        assert!(plan.target > 0);
+        assert_eq!(replay(&blueprint, &plan, 32), plan.target);
+    }
+
+    #[test]
+    fn test_unknown_resource() {
+        let result = "Blueprint 1: Each ore robot costs 4 gold.".parse::<Blueprint>();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_long_time_limit() {
+        let blueprint: Blueprint = "Blueprint 1: Each ore robot costs 60000 ore."
+            .parse()
+            .unwrap();
+        assert_eq!(optimise(&blueprint, 1000).target, 1000);
+
+        let blueprint: Blueprint = "Blueprint 2: Each ore robot costs 2 ore.".parse().unwrap();
+        let plan = optimise(&blueprint, 400);
+        assert!(plan.target > u64::from(u16::MAX));
+        assert_eq!(replay(&blueprint, &plan, 400), plan.target);
+    }
 }
@@ -1,7 +1,8 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt};
 
-use anyhow::{Result, bail};
+use anyhow::{Context, Result, bail, ensure};
 use aoc_runner_derive::{aoc, aoc_generator};
+use num::{BigInt, BigRational, One, Signed, ToPrimitive, Zero};
 
 #[derive(Debug, Clone)]
 enum Job {
@@ -64,87 +65,206 @@ enum Expr {
     Div(Box<Expr>, Box<Expr>),
 }
 
-fn build_expr(monkeys: &HashMap<String, Job>, name: &str) -> Expr {
+fn build_expr(monkeys: &HashMap<String, Job>, name: &str) -> Result<Expr> {
     if name == "humn" {
-        Expr::Human
-    } else {
-        match &monkeys[name] {
-            Job::Number(n) => Expr::Num(*n),
-            Job::Add(a, b) => Expr::Add(
-                Box::new(build_expr(monkeys, a)),
-                Box::new(build_expr(monkeys, b)),
-            ),
-            Job::Sub(a, b) => Expr::Sub(
-                Box::new(build_expr(monkeys, a)),
-                Box::new(build_expr(monkeys, b)),
-            ),
-            Job::Mul(a, b) => Expr::Mul(
-                Box::new(build_expr(monkeys, a)),
-                Box::new(build_expr(monkeys, b)),
-            ),
-            Job::Div(a, b) => Expr::Div(
-                Box::new(build_expr(monkeys, a)),
-                Box::new(build_expr(monkeys, b)),
-            ),
+        return Ok(Expr::Human);
+    }
+
+    let build = |a: &str, b: &str| -> Result<(Box<Expr>, Box<Expr>)> {
+        Ok((
+            Box::new(build_expr(monkeys, a)?),
+            Box::new(build_expr(monkeys, b)?),
+        ))
+    };
+
+    let expr = match monkeys
+        .get(name)
+        .context(format!("Unknown monkey {name}"))?
+    {
+        Job::Number(n) => Expr::Num(*n),
+        Job::Add(a, b) => build(a, b).map(|(a, b)| Expr::Add(a, b))?,
+        Job::Sub(a, b) => build(a, b).map(|(a, b)| Expr::Sub(a, b))?,
+        Job::Mul(a, b) => build(a, b).map(|(a, b)| Expr::Mul(a, b))?,
+        Job::Div(a, b) => build(a, b).map(|(a, b)| Expr::Div(a, b))?,
+    };
+    Ok(expr)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Poly(Vec<BigRational>);
+
+impl Poly {
+    fn constant(value: BigRational) -> Self {
+        Poly(vec![value]).trim()
+    }
+
+    fn unknown() -> Self {
+        Poly(vec![BigRational::zero(), BigRational::one()])
+    }
+
+    fn trim(mut self) -> Self {
+        while self.0.last().is_some_and(Zero::is_zero) {
+            self.0.pop();
+        }
+        self
+    }
+
+    fn degree(&self) -> Option<usize> {
+        self.0.len().checked_sub(1)
+    }
+
+    fn coefficient(&self, degree: usize) -> BigRational {
+        self.0
+            .get(degree)
+            .cloned()
+            .unwrap_or_else(BigRational::zero)
+    }
+
+    fn add(&self, rhs: &Poly) -> Poly {
+        let len = self.0.len().max(rhs.0.len());
+        Poly(
+            (0..len)
+                .map(|i| self.coefficient(i) + rhs.coefficient(i))
+                .collect(),
+        )
+        .trim()
+    }
+
+    fn neg(&self) -> Poly {
+        Poly(self.0.iter().map(|c| -c).collect())
+    }
+
+    fn sub(&self, rhs: &Poly) -> Poly {
+        self.add(&rhs.neg())
+    }
+
+    fn mul(&self, rhs: &Poly) -> Poly {
+        if self.0.is_empty() || rhs.0.is_empty() {
+            return Poly(vec![]);
+        }
+
+        let mut result = vec![BigRational::zero(); self.0.len() + rhs.0.len() - 1];
+        for (i, a) in self.0.iter().enumerate() {
+            for (j, b) in rhs.0.iter().enumerate() {
+                result[i + j] += a * b;
+            }
+        }
+        Poly(result).trim()
+    }
+
+    fn div(&self, rhs: &Poly) -> Result<Poly> {
+        match rhs.degree() {
+            None => bail!("Division by zero in ({self}) / ({rhs})"),
+            Some(0) => Ok(Poly(self.0.iter().map(|c| c / &rhs.0[0]).collect())),
+            Some(_) => bail!("Non-linear expression: division by ({rhs}) which depends on humn"),
         }
     }
 }
 
-fn eval_expr(expr: &Expr) -> Option<i64> {
-    match expr {
-        Expr::Num(n) => Some(*n),
-        Expr::Human => None,
-        Expr::Add(a, b) => Some(eval_expr(a)? + eval_expr(b)?),
-        Expr::Sub(a, b) => Some(eval_expr(a)? - eval_expr(b)?),
-        Expr::Mul(a, b) => Some(eval_expr(a)? * eval_expr(b)?),
-        Expr::Div(a, b) => Some(eval_expr(a)? / eval_expr(b)?),
-    }
-}
-
-fn solve_for_human(expr: &Expr, target: i64) -> Result<i64> {
-    match expr {
-        Expr::Human => Ok(target),
-        Expr::Add(a, b) => match (eval_expr(a), eval_expr(b)) {
-            (Some(a_val), None) => solve_for_human(b, target - a_val),
-            (None, Some(b_val)) => solve_for_human(a, target - b_val),
-            _ => bail!("Invalid expression state in Add"),
-        },
-        Expr::Sub(a, b) => match (eval_expr(a), eval_expr(b)) {
-            (Some(a_val), None) => solve_for_human(b, a_val - target),
-            (None, Some(b_val)) => solve_for_human(a, target + b_val),
-            _ => bail!("Invalid expression state in Sub"),
-        },
-        Expr::Mul(a, b) => match (eval_expr(a), eval_expr(b)) {
-            (Some(a_val), None) => solve_for_human(b, target / a_val),
-            (None, Some(b_val)) => solve_for_human(a, target / b_val),
-            _ => bail!("Invalid expression state in Mul"),
-        },
-        Expr::Div(a, b) => match (eval_expr(a), eval_expr(b)) {
-            (Some(a_val), None) => solve_for_human(b, a_val / target),
-            (None, Some(b_val)) => solve_for_human(a, target * b_val),
-            _ => bail!("Invalid expression state in Div"),
-        },
-        _ => bail!("Cannot solve for non-human expression"),
+impl fmt::Display for Poly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "0");
+        }
+
+        for (i, (degree, coefficient)) in self
+            .0
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, c)| !c.is_zero())
+            .enumerate()
+        {
+            let magnitude = coefficient.abs();
+            match (i, coefficient.is_negative()) {
+                (0, true) => write!(f, "-")?,
+                (0, false) => {}
+                (_, true) => write!(f, " - ")?,
+                (_, false) => write!(f, " + ")?,
+            }
+
+            match degree {
+                0 => write!(f, "{magnitude}")?,
+                _ if magnitude.is_one() => write!(f, "humn")?,
+                _ => write!(f, "{magnitude} * humn")?,
+            }
+
+            if degree > 1 {
+                write!(f, "^{degree}")?;
+            }
+        }
+        Ok(())
     }
 }
 
-#[aoc(day21, part2)]
-fn part2(monkeys: &HashMap<String, Job>) -> Result<i64> {
-    let (left, right) = match &monkeys["root"] {
-        Job::Add(a, b) | Job::Sub(a, b) | Job::Mul(a, b) | Job::Div(a, b) => (a, b),
-        _ => bail!("Root must be an operation"),
+fn simplify(expr: &Expr) -> Result<Poly> {
+    let poly = match expr {
+        Expr::Num(n) => Poly::constant(BigRational::from_integer(BigInt::from(*n))),
+        Expr::Human => Poly::unknown(),
+        Expr::Add(a, b) => simplify(a)?.add(&simplify(b)?),
+        Expr::Sub(a, b) => simplify(a)?.sub(&simplify(b)?),
+        Expr::Mul(a, b) => simplify(a)?.mul(&simplify(b)?),
+        Expr::Div(a, b) => simplify(a)?.div(&simplify(b)?)?,
     };
+    Ok(poly)
+}
+
+#[derive(Debug, Clone)]
+struct Equation {
+    left: Poly,
+    right: Poly,
+}
 
-    let left_expr = build_expr(monkeys, left);
-    let right_expr = build_expr(monkeys, right);
+impl Equation {
+    fn solve(&self) -> Result<BigRational> {
+        let difference = self.left.sub(&self.right);
+        match difference.degree() {
+            None => bail!("Every value of humn satisfies {self}"),
+            Some(0) => bail!("No value of humn satisfies {self}"),
+            Some(1) => Ok(-difference.coefficient(0) / difference.coefficient(1)),
+            Some(degree) => bail!("Non-linear equation of degree {degree}: {self}"),
+        }
+    }
+}
 
-    match (eval_expr(&left_expr), eval_expr(&right_expr)) {
-        (None, Some(val)) => solve_for_human(&left_expr, val),
-        (Some(val), None) => solve_for_human(&right_expr, val),
-        _ => bail!("Invalid root expression state"),
+impl fmt::Display for Equation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} = {}", self.left, self.right)
     }
 }
 
+fn root_equation(monkeys: &HashMap<String, Job>) -> Result<Equation> {
+    let (left, right) = match monkeys.get("root") {
+        Some(Job::Add(a, b) | Job::Sub(a, b) | Job::Mul(a, b) | Job::Div(a, b)) => (a, b),
+        _ => bail!("Root must be an operation"),
+    };
+
+    Ok(Equation {
+        left: simplify(&build_expr(monkeys, left)?)?,
+        right: simplify(&build_expr(monkeys, right)?)?,
+    })
+}
+
+#[aoc(day21, part2)]
+fn part2(monkeys: &HashMap<String, Job>) -> Result<i64> {
+    let solution = root_equation(monkeys)?.solve()?;
+    ensure!(
+        solution.is_integer(),
+        "humn must be {solution}, which is not an integer"
+    );
+    solution
+        .to_integer()
+        .to_i64()
+        .context(format!("humn = {solution} does not fit in i64"))
+}
+
+#[aoc(day21, part2, equation)]
+fn part2_equation(monkeys: &HashMap<String, Job>) -> Result<String> {
+    let equation = root_equation(monkeys)?;
+    let solution = equation.solve()?;
+    Ok(format!("{equation}\nhumn = {solution}"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -190,4 +310,72 @@ hmdt: 32";
         let parsed = generator(input).unwrap();
         assert_eq!(part2(&parsed).unwrap(), 301);
     }
+
+    fn equation(input: &str) -> Result<Equation> {
+        root_equation(&generator(input).unwrap())
+    }
+
+    #[test]
+    fn test_repeated_unknown() {
+        let input = "root: aaaa + bbbb
+aaaa: humn * cccc
+cccc: 3
+bbbb: dddd - humn
+dddd: 20";
+        let equation = equation(input).unwrap();
+        assert_eq!(equation.to_string(), "3 * humn = -humn + 20");
+        assert_eq!(
+            equation.solve().unwrap(),
+            BigRational::from_integer(5.into())
+        );
+        assert_eq!(part2(&generator(input).unwrap()).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_fractional_coefficients() {
+        let input = "root: aaaa + bbbb
+aaaa: humn / cccc
+cccc: 3
+bbbb: eeee + humn
+eeee: 4";
+        let equation = equation(input).unwrap();
+        assert_eq!(equation.to_string(), "1/3 * humn = humn + 4");
+        assert_eq!(part2(&generator(input).unwrap()).unwrap(), -6);
+    }
+
+    #[test]
+    fn test_non_linear() {
+        let input = "root: aaaa + bbbb
+aaaa: humn * humn
+bbbb: 4";
+        let error = equation(input).unwrap().solve().unwrap_err();
+        assert!(error.to_string().contains("Non-linear"));
+
+        let input = "root: aaaa + bbbb
+aaaa: cccc / humn
+cccc: 4
+bbbb: 4";
+        let error = equation(input).unwrap_err();
+        assert!(error.to_string().contains("Non-linear"));
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        let input = "root: aaaa + bbbb
+aaaa: humn / cccc
+cccc: dddd - dddd
+dddd: 7
+bbbb: 4";
+        let error = equation(input).unwrap_err();
+        assert!(error.to_string().contains("Division by zero"));
+    }
+
+    #[test]
+    fn test_cancelling_unknown() {
+        let input = "root: aaaa + bbbb
+aaaa: humn - humn
+bbbb: 4";
+        let error = equation(input).unwrap().solve().unwrap_err();
+        assert!(error.to_string().contains("No value"));
+    }
 }
@@ -1,142 +1,260 @@
-use std::iter;
+use std::{
+    cmp::Ordering,
+    fmt,
+    iter::Sum,
+    marker::PhantomData,
+    ops::{Add, Mul, Neg, Sub},
+    str::FromStr,
+};
 
 use anyhow::{Result, bail, ensure};
 use aoc_runner_derive::{aoc, aoc_generator};
 
+trait Radix: Clone + Eq {
+    const DIGITS: &'static [char];
+
+    fn radix() -> i64 {
+        const { assert!(Self::DIGITS.len() % 2 == 1, "balanced radix must be odd") };
+        Self::DIGITS.len() as i64
+    }
+
+    fn half() -> i64 {
+        Self::radix() / 2
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+struct Quinary;
+
+impl Radix for Quinary {
+    const DIGITS: &'static [char] = &['=', '-', '0', '1', '2'];
+}
+
+type Snafu = Balanced<Quinary>;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+struct Balanced<R> {
+    digits: Vec<i8>,
+    radix: PhantomData<R>,
+}
+
+impl<R: Radix> Balanced<R> {
+    fn from_digits(mut digits: Vec<i64>) -> Self {
+        let radix = R::radix();
+        let half = R::half();
+        let mut carry = 0;
+        let mut i = 0;
+        while i < digits.len() || carry != 0 {
+            if i == digits.len() {
+                digits.push(0);
+            }
+
+            let value = digits[i] + carry;
+            carry = (value + half).div_euclid(radix);
+            digits[i] = value - carry * radix;
+            i += 1;
+        }
+
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+
+        Self {
+            digits: digits.into_iter().map(|d| d as i8).collect(),
+            radix: PhantomData,
+        }
+    }
+
+    fn digit(&self, place: usize) -> i64 {
+        self.digits.get(place).copied().unwrap_or_default() as i64
+    }
+}
+
+impl<R: Radix> FromStr for Balanced<R> {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        ensure!(!s.is_empty(), "empty balanced number");
+        let digits = s
+            .chars()
+            .rev()
+            .map(|ch| match R::DIGITS.iter().position(|&d| d == ch) {
+                Some(position) => Ok(position as i64 - R::half()),
+                None => bail!("invalid digit: {}", ch),
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self::from_digits(digits))
+    }
+}
+
+impl<R: Radix> fmt::Display for Balanced<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.digits.is_empty() {
+            return write!(f, "{}", R::DIGITS[R::half() as usize]);
+        }
+
+        self.digits
+            .iter()
+            .rev()
+            .try_for_each(|&d| write!(f, "{}", R::DIGITS[(d as i64 + R::half()) as usize]))
+    }
+}
+
+impl<R: Radix> From<i64> for Balanced<R> {
+    fn from(value: i64) -> Self {
+        let radix = R::radix() as i128;
+        let half = R::half() as i128;
+        let mut value = value as i128;
+        let mut digits = vec![];
+        while value != 0 {
+            let digit = (value + half).rem_euclid(radix) - half;
+            digits.push(digit as i64);
+            value = (value - digit) / radix;
+        }
+        Self::from_digits(digits)
+    }
+}
+
+impl<R: Radix> TryFrom<&Balanced<R>> for i64 {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &Balanced<R>) -> Result<Self> {
+        value
+            .digits
+            .iter()
+            .rev()
+            .try_fold(0i64, |acc, &d| {
+                acc.checked_mul(R::radix())?.checked_add(d as i64)
+            })
+            .ok_or_else(|| anyhow::anyhow!("{} does not fit in i64", value))
+    }
+}
+
+impl<R: Radix> Add for Balanced<R> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        let len = self.digits.len().max(rhs.digits.len());
+        Self::from_digits((0..len).map(|i| self.digit(i) + rhs.digit(i)).collect())
+    }
+}
+
+impl<R: Radix> Neg for Balanced<R> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            digits: self.digits.into_iter().map(|d| -d).collect(),
+            radix: PhantomData,
+        }
+    }
+}
+
+impl<R: Radix> Sub for Balanced<R> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        self + -rhs
+    }
+}
+
+impl<R: Radix> Mul for Balanced<R> {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        if self.digits.is_empty() || rhs.digits.is_empty() {
+            return Self::from_digits(vec![]);
+        }
+
+        let mut digits = vec![0; self.digits.len() + rhs.digits.len()];
+        for (i, &a) in self.digits.iter().enumerate() {
+            for (j, &b) in rhs.digits.iter().enumerate() {
+                digits[i + j] += a as i64 * b as i64;
+            }
+        }
+        Self::from_digits(digits)
+    }
+}
+
+impl<R: Radix> Ord for Balanced<R> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let len = self.digits.len().max(other.digits.len());
+        (0..len)
+            .rev()
+            .map(|i| self.digit(i).cmp(&other.digit(i)))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl<R: Radix> PartialOrd for Balanced<R> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<R: Radix> Sum for Balanced<R> {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::from_digits(vec![]), Add::add)
+    }
+}
+
 #[aoc_generator(day25)]
-fn parse(input: &str) -> Result<Vec<String>> {
+fn parse(input: &str) -> Result<Vec<Snafu>> {
     input
         .lines()
         .map(str::trim)
         .filter(|line| !line.is_empty())
-        .map(|line| {
-            ensure!(
-                line.chars()
-                    .all(|c| matches!(c, '=' | '-' | '0' | '1' | '2')),
-                "invalid SNAFU string: {}",
-                line
-            );
-            Ok(line.to_string())
-        })
+        .map(str::parse)
         .collect()
 }
 
-fn snafu_to_decimal(snafu: &str) -> Result<i64> {
-    snafu
-        .chars()
-        .rev()
-        .enumerate()
-        .map(|(i, ch)| {
-            let digit = match ch {
-                '2' => 2,
-                '1' => 1,
-                '0' => 0,
-                '-' => -1,
-                '=' => -2,
-                _ => bail!("invalid SNAFU character: {}", ch),
-            };
-            Ok(digit * 5_i64.pow(i as u32))
-        })
-        .sum()
-}
-
-fn decimal_to_snafu(decimal: i64) -> String {
-    if decimal == 0 {
-        return "0".to_string();
-    }
-
-    iter::successors(Some(decimal), |&n| {
-        let next = (n + 2) / 5;
-        (next > 0).then_some(next)
-    })
-    .map(|n| match n.rem_euclid(5) {
-        0 => '0',
-        1 => '1',
-        2 => '2',
-        3 => '=',
-        4 => '-',
-        _ => unreachable!(),
-    })
-    .collect::<String>()
-    .chars()
-    .rev()
-    .collect()
-}
-
 #[aoc(day25, part1)]
-fn part1(input: &[String]) -> Result<String> {
-    input
-        .iter()
-        .map(|snafu| snafu_to_decimal(snafu))
-        .sum::<anyhow::Result<i64>>()
-        .map(decimal_to_snafu)
+fn part1(input: &[Snafu]) -> String {
+    input.iter().cloned().sum::<Snafu>().to_string()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_snafu_to_decimal() {
-        assert_eq!(snafu_to_decimal("1=-0-2").unwrap(), 1747);
-        assert_eq!(snafu_to_decimal("12111").unwrap(), 906);
-        assert_eq!(snafu_to_decimal("2=0=").unwrap(), 198);
-        assert_eq!(snafu_to_decimal("21").unwrap(), 11);
-        assert_eq!(snafu_to_decimal("2=01").unwrap(), 201);
-        assert_eq!(snafu_to_decimal("111").unwrap(), 31);
-        assert_eq!(snafu_to_decimal("20012").unwrap(), 1257);
-        assert_eq!(snafu_to_decimal("112").unwrap(), 32);
-        assert_eq!(snafu_to_decimal("1=-1=").unwrap(), 353);
-        assert_eq!(snafu_to_decimal("1-12").unwrap(), 107);
-        assert_eq!(snafu_to_decimal("12").unwrap(), 7);
-        assert_eq!(snafu_to_decimal("1=").unwrap(), 3);
-        assert_eq!(snafu_to_decimal("122").unwrap(), 37);
-        assert_eq!(snafu_to_decimal("1").unwrap(), 1);
-        assert_eq!(snafu_to_decimal("2").unwrap(), 2);
-        assert_eq!(snafu_to_decimal("1=").unwrap(), 3);
-        assert_eq!(snafu_to_decimal("1-").unwrap(), 4);
-        assert_eq!(snafu_to_decimal("10").unwrap(), 5);
-        assert_eq!(snafu_to_decimal("11").unwrap(), 6);
-        assert_eq!(snafu_to_decimal("12").unwrap(), 7);
-        assert_eq!(snafu_to_decimal("2=").unwrap(), 8);
-        assert_eq!(snafu_to_decimal("2-").unwrap(), 9);
-        assert_eq!(snafu_to_decimal("20").unwrap(), 10);
-        assert_eq!(snafu_to_decimal("1=0").unwrap(), 15);
-        assert_eq!(snafu_to_decimal("1-0").unwrap(), 20);
-        assert_eq!(snafu_to_decimal("1=11-2").unwrap(), 2022);
-        assert_eq!(snafu_to_decimal("1-0---0").unwrap(), 12345);
-        assert_eq!(snafu_to_decimal("1121-1110-1=0").unwrap(), 314159265);
-    }
+    const TABLE: &[(i64, &str)] = &[
+        (1, "1"),
+        (2, "2"),
+        (3, "1="),
+        (4, "1-"),
+        (5, "10"),
+        (6, "11"),
+        (7, "12"),
+        (8, "2="),
+        (9, "2-"),
+        (10, "20"),
+        (15, "1=0"),
+        (20, "1-0"),
+        (2022, "1=11-2"),
+        (12345, "1-0---0"),
+        (314159265, "1121-1110-1=0"),
+        (1747, "1=-0-2"),
+        (906, "12111"),
+        (198, "2=0="),
+        (11, "21"),
+        (201, "2=01"),
+        (31, "111"),
+        (1257, "20012"),
+        (32, "112"),
+        (353, "1=-1="),
+        (107, "1-12"),
+        (37, "122"),
+        (4890, "2=-1=0"),
+    ];
 
     #[test]
-    fn test_decimal_to_snafu() {
-        assert_eq!(decimal_to_snafu(1), "1");
-        assert_eq!(decimal_to_snafu(2), "2");
-        assert_eq!(decimal_to_snafu(3), "1=");
-        assert_eq!(decimal_to_snafu(4), "1-");
-        assert_eq!(decimal_to_snafu(5), "10");
-        assert_eq!(decimal_to_snafu(6), "11");
-        assert_eq!(decimal_to_snafu(7), "12");
-        assert_eq!(decimal_to_snafu(8), "2=");
-        assert_eq!(decimal_to_snafu(9), "2-");
-        assert_eq!(decimal_to_snafu(10), "20");
-        assert_eq!(decimal_to_snafu(15), "1=0");
-        assert_eq!(decimal_to_snafu(20), "1-0");
-        assert_eq!(decimal_to_snafu(2022), "1=11-2");
-        assert_eq!(decimal_to_snafu(12345), "1-0---0");
-        assert_eq!(decimal_to_snafu(314159265), "1121-1110-1=0");
-        assert_eq!(decimal_to_snafu(1747), "1=-0-2");
-        assert_eq!(decimal_to_snafu(906), "12111");
-        assert_eq!(decimal_to_snafu(198), "2=0=");
-        assert_eq!(decimal_to_snafu(11), "21");
-        assert_eq!(decimal_to_snafu(201), "2=01");
-        assert_eq!(decimal_to_snafu(31), "111");
-        assert_eq!(decimal_to_snafu(1257), "20012");
-        assert_eq!(decimal_to_snafu(32), "112");
-        assert_eq!(decimal_to_snafu(353), "1=-1=");
-        assert_eq!(decimal_to_snafu(107), "1-12");
-        assert_eq!(decimal_to_snafu(37), "122");
-        assert_eq!(decimal_to_snafu(4890), "2=-1=0");
+    fn test_snafu_table() {
+        for &(decimal, snafu) in TABLE {
+            let parsed = snafu.parse::<Snafu>().unwrap();
+            assert_eq!(i64::try_from(&parsed).unwrap(), decimal, "{snafu}");
+            assert_eq!(Snafu::from(decimal).to_string(), snafu, "{decimal}");
+        }
     }
 
     #[test]
@@ -155,8 +273,60 @@ mod tests {
             "12".to_string(),
             "1=".to_string(),
             "122".to_string(),
-        ];
+        ]
+        .iter()
+        .map(|line| line.parse().unwrap())
+        .collect::<Vec<Snafu>>();
+
+        assert_eq!(part1(&input), "2=-1=0");
+    }
 
-        assert_eq!(part1(&input).unwrap(), "2=-1=0");
+    #[test]
+    fn test_arithmetic() {
+        for a in -130..=130 {
+            for b in -130..=130 {
+                let (x, y) = (Snafu::from(a), Snafu::from(b));
+                assert_eq!(x.clone() + y.clone(), Snafu::from(a + b));
+                assert_eq!(x.clone() - y.clone(), Snafu::from(a - b));
+                assert_eq!(x.clone() * y.clone(), Snafu::from(a * b));
+                assert_eq!(x.cmp(&y), a.cmp(&b));
+            }
+        }
+        assert_eq!(-Snafu::from(2022), Snafu::from(-2022));
+        assert_eq!(Snafu::from(0).to_string(), "0");
+        assert_eq!("000".parse::<Snafu>().unwrap().to_string(), "0");
+        assert!("1=3".parse::<Snafu>().is_err());
+    }
+
+    #[test]
+    fn test_beyond_i64() {
+        let big = "2".repeat(40).parse::<Snafu>().unwrap();
+        assert!(i64::try_from(&big).is_err());
+        let square = big.clone() * big.clone();
+        assert_eq!(square.clone() - big.clone() * big.clone(), Snafu::from(0));
+        assert!(square > big);
+        assert_eq!(
+            format!("{}", big.clone() + Snafu::from(1)),
+            format!("1{}", "=".repeat(40))
+        );
+    }
+
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+    struct Ternary;
+
+    impl Radix for Ternary {
+        const DIGITS: &'static [char] = &['-', '0', '+'];
+    }
+
+    #[test]
+    fn test_balanced_ternary() {
+        type BalancedTernary = Balanced<Ternary>;
+        assert_eq!(BalancedTernary::from(8).to_string(), "+0-");
+        assert_eq!(BalancedTernary::from(-8).to_string(), "-0+");
+        let sum = ["+-0", "++", "-"]
+            .iter()
+            .map(|s| s.parse::<BalancedTernary>().unwrap())
+            .sum::<BalancedTernary>();
+        assert_eq!(i64::try_from(&sum).unwrap(), 6 + 4 - 1);
     }
 }
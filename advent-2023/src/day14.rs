@@ -1,5 +1,6 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::BTreeMap;
 
+use advent_common::cycle::find_cycle;
 use aoc_runner_derive::{aoc, aoc_generator};
 
 type Point = (i16, i16);
@@ -119,23 +120,11 @@ fn part1(input: &Map) -> usize {
 
 #[aoc(day14, part2)]
 fn part2(input: &Map) -> usize {
-    let mut cycles = HashMap::new();
-    let mut map = input.clone();
-    let count: usize = 1_000_000_000;
-    for i in 0..count {
-        map = map.cycle();
-        if let Some(&prev) = cycles.get(&map) {
-            let size = i - prev;
-            let remaining = count - i - 1;
-            let extra = remaining % size;
-            for _ in 0..extra {
-                map = map.cycle();
-            }
-
-            return map.total_load();
-        }
-        cycles.insert(map.clone(), i);
-    }
-
-    unreachable!()
+    let cycle = find_cycle(
+        input.clone(),
+        |map| map.cycle(),
+        Map::clone,
+        Map::total_load,
+    );
+    *cycle.at(1_000_000_000)
 }
@@ -1,31 +1,22 @@
-use std::{collections::HashMap, str::FromStr};
+use std::{collections::HashMap, fmt, str::FromStr};
 
-use anyhow::{Context, anyhow};
+use anyhow::{Context, anyhow, bail, ensure};
 use aoc_runner_derive::{aoc, aoc_generator};
+use itertools::Itertools;
+
+const CATEGORIES: &str = "xmas";
+const MIN_RATING: u32 = 1;
+const MAX_RATING: u32 = 4000;
+
+fn category_index(category: char) -> Option<usize> {
+    CATEGORIES.find(category)
+}
 
 #[derive(Clone, Copy)]
 enum Condition {
     Immediate,
-    LessThan(char, u32),
-    GreaterThan(char, u32),
-}
-
-impl Condition {
-    fn apply(&self, part: &Part) -> bool {
-        match self {
-            Condition::Immediate => true,
-            Condition::LessThan(property, value) => part
-                .properties
-                .get(property)
-                .map(|n| n < value)
-                .unwrap_or_default(),
-            Condition::GreaterThan(property, value) => part
-                .properties
-                .get(property)
-                .map(|n| n > value)
-                .unwrap_or_default(),
-        }
-    }
+    LessThan(usize, u32),
+    GreaterThan(usize, u32),
 }
 
 enum Action {
@@ -51,18 +42,6 @@ struct Rule {
     workflow: Vec<(Condition, Action)>,
 }
 
-impl Rule {
-    fn apply(&self, part: &Part) -> Option<&Action> {
-        self.workflow.iter().find_map(|(condition, action)| {
-            if condition.apply(part) {
-                Some(action)
-            } else {
-                None
-            }
-        })
-    }
-}
-
 impl FromStr for Rule {
     type Err = anyhow::Error;
 
@@ -72,14 +51,18 @@ impl FromStr for Rule {
             branch::alt,
             bytes::complete::take_till1,
             character::complete::{alpha1, anychar, char, u32},
-            combinator::{map, map_res},
+            combinator::{map, map_opt, map_res},
             multi::separated_list1,
             sequence::{delimited, pair, separated_pair},
         };
 
         fn condition(input: &str) -> IResult<&str, Condition> {
             map(
-                (anychar, alt((char('<'), char('>'))), u32),
+                (
+                    map_opt(anychar, category_index),
+                    alt((char('<'), char('>'))),
+                    u32,
+                ),
                 |(property, operator, value)| match operator {
                     '<' => Condition::LessThan(property, value),
                     '>' => Condition::GreaterThan(property, value),
@@ -122,7 +105,7 @@ impl FromStr for Rule {
 }
 
 struct Part {
-    properties: HashMap<char, u32>,
+    ratings: [u32; 4],
 }
 
 impl FromStr for Part {
@@ -132,7 +115,7 @@ impl FromStr for Part {
         use nom::{
             Finish, Parser,
             character::complete::{anychar, char, u32},
-            combinator::map,
+            combinator::{map, map_opt},
             error::Error,
             multi::separated_list1,
             sequence::{delimited, separated_pair},
@@ -141,11 +124,18 @@ impl FromStr for Part {
         map(
             delimited(
                 char::<_, Error<_>>('{'),
-                separated_list1(char(','), separated_pair(anychar, char('='), u32)),
+                separated_list1(
+                    char(','),
+                    separated_pair(map_opt(anychar, category_index), char('='), u32),
+                ),
                 char('}'),
             ),
-            |pairs| Part {
-                properties: pairs.into_iter().collect(),
+            |pairs| {
+                let mut ratings = [0; 4];
+                for (category, value) in pairs {
+                    ratings[category] = value;
+                }
+                Part { ratings }
             },
         )
         .parse(input)
@@ -170,78 +160,406 @@ fn generator(input: &str) -> anyhow::Result<(HashMap<String, Rule>, Vec<Part>)>
     Ok((rules, parts))
 }
 
-#[aoc(day19, part1)]
-fn part1((rules, parts): &(HashMap<String, Rule>, Vec<Part>)) -> u32 {
-    let mut accepted = vec![];
-    for part in parts {
-        let mut name = "in";
-        while let Some(rule) = rules.get(name) {
-            match rule.apply(part) {
-                Some(Action::Continue(next)) => name = next.as_str(),
-                Some(Action::Accept) => {
-                    accepted.push(part);
-                    break;
-                }
-                _ => break,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Hyperrect {
+    ranges: [(u32, u32); 4],
+}
+
+impl Hyperrect {
+    fn full() -> Self {
+        Hyperrect {
+            ranges: [(MIN_RATING, MAX_RATING); 4],
+        }
+    }
+
+    fn volume(&self) -> u64 {
+        self.ranges
+            .iter()
+            .map(|&(low, high)| (high - low + 1) as u64)
+            .product()
+    }
+
+    fn split(&self, category: usize, threshold: u32) -> (Option<Self>, Option<Self>) {
+        let (low, high) = self.ranges[category];
+        let below = (low < threshold).then(|| {
+            let mut below = *self;
+            below.ranges[category].1 = high.min(threshold - 1);
+            below
+        });
+        let above = (high >= threshold).then(|| {
+            let mut above = *self;
+            above.ranges[category].0 = low.max(threshold);
+            above
+        });
+        (below, above)
+    }
+
+    fn intersection(&self, other: &Self) -> Option<Self> {
+        let mut ranges = self.ranges;
+        for (range, &(low, high)) in ranges.iter_mut().zip(&other.ranges) {
+            *range = (range.0.max(low), range.1.min(high));
+            if range.0 > range.1 {
+                return None;
             }
         }
+        Some(Hyperrect { ranges })
     }
+}
 
-    accepted
-        .iter()
-        .flat_map(|part| part.properties.values())
-        .sum()
+fn split_condition(
+    region: &Hyperrect,
+    condition: Condition,
+) -> (Option<Hyperrect>, Option<Hyperrect>) {
+    match condition {
+        Condition::Immediate => (Some(*region), None),
+        Condition::LessThan(category, value) => region.split(category, value),
+        Condition::GreaterThan(category, value) => {
+            let (below, above) = region.split(category, value + 1);
+            (above, below)
+        }
+    }
 }
 
-#[aoc(day19, part2)]
-fn part2((rules, _): &(HashMap<String, Rule>, Vec<Part>)) -> u64 {
-    let mut properties = HashMap::new();
-    for name in "xmas".chars() {
-        properties.insert(name, 1..4000);
+#[derive(Debug, Clone, Copy)]
+enum Node {
+    Leaf(bool),
+    Split {
+        category: usize,
+        threshold: u32,
+        below: usize,
+        above: usize,
+    },
+}
+
+const REJECT: usize = 0;
+const ACCEPT: usize = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct RuleRef<'a> {
+    workflow: &'a str,
+    step: usize,
+}
+
+impl fmt::Display for RuleRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} rule {}", self.workflow, self.step + 1)
     }
+}
 
-    let mut ranges = HashMap::new();
-    ranges.insert("in", properties);
+struct DecisionTree<'a> {
+    nodes: Vec<Node>,
+    root: usize,
+    accepted: Vec<Hyperrect>,
+    fired: HashMap<RuleRef<'a>, Vec<Hyperrect>>,
+    reached: HashMap<RuleRef<'a>, u64>,
+}
 
-    let mut paths = vec![];
-    let mut search = vec!["in"];
-    while let Some(name) = search.pop() {
-        let rule = &rules[name];
-        let mut rule_range = ranges[name].clone();
+impl<'a> DecisionTree<'a> {
+    fn compile(rules: &'a HashMap<String, Rule>) -> anyhow::Result<Self> {
+        let mut tree = DecisionTree {
+            nodes: vec![Node::Leaf(false), Node::Leaf(true)],
+            root: REJECT,
+            accepted: vec![],
+            fired: HashMap::new(),
+            reached: HashMap::new(),
+        };
+        let mut path = vec!["in".to_string()];
+        tree.root = tree.compile_step(rules, "in", 0, Hyperrect::full(), &mut path)?;
+        Ok(tree)
+    }
 
-        for (condition, action) in &rule.workflow {
-            let mut workflow_range = rule_range.clone();
+    fn compile_step(
+        &mut self,
+        rules: &'a HashMap<String, Rule>,
+        name: &str,
+        step: usize,
+        region: Hyperrect,
+        path: &mut Vec<String>,
+    ) -> anyhow::Result<usize> {
+        let (name, rule) = rules
+            .get_key_value(name)
+            .with_context(|| format!("undefined workflow {name}"))?;
+        let Some(&(condition, ref action)) = rule.workflow.get(step) else {
+            bail!("workflow {name} has no matching rule for some parts");
+        };
 
-            match *condition {
-                Condition::Immediate => {}
-                Condition::LessThan(property, n) => {
-                    rule_range.entry(property).or_default().start = n;
-                    workflow_range.entry(property).or_default().end = n - 1;
-                }
-                Condition::GreaterThan(property, n) => {
-                    workflow_range.entry(property).or_default().start = n + 1;
-                    rule_range.entry(property).or_default().end = n;
-                }
+        let rule_ref = RuleRef {
+            workflow: name,
+            step,
+        };
+        *self.reached.entry(rule_ref).or_default() += region.volume();
+
+        let (matched, unmatched) = split_condition(&region, condition);
+        let matched = match matched {
+            Some(matched) => {
+                self.fired.entry(rule_ref).or_default().push(matched);
+                Some(self.compile_action(rules, action, matched, path)?)
+            }
+            None => None,
+        };
+        let unmatched = match unmatched {
+            Some(unmatched) => Some(self.compile_step(rules, name, step + 1, unmatched, path)?),
+            None => None,
+        };
+
+        match (matched, unmatched, condition) {
+            (Some(node), None, _) | (None, Some(node), _) => Ok(node),
+            (Some(matched), Some(unmatched), Condition::LessThan(category, value)) => {
+                Ok(self.push_split(category, value, matched, unmatched))
+            }
+            (Some(matched), Some(unmatched), Condition::GreaterThan(category, value)) => {
+                Ok(self.push_split(category, value + 1, unmatched, matched))
             }
+            _ => unreachable!(),
+        }
+    }
 
-            match action {
-                Action::Accept => paths.push(workflow_range),
-                Action::Reject => continue,
-                Action::Continue(destination) => {
-                    ranges.insert(destination, workflow_range);
-                    search.push(destination);
+    fn compile_action(
+        &mut self,
+        rules: &'a HashMap<String, Rule>,
+        action: &Action,
+        region: Hyperrect,
+        path: &mut Vec<String>,
+    ) -> anyhow::Result<usize> {
+        match action {
+            Action::Accept => {
+                self.accepted.push(region);
+                Ok(ACCEPT)
+            }
+            Action::Reject => Ok(REJECT),
+            Action::Continue(next) => {
+                ensure!(
+                    !path.contains(next),
+                    "workflow cycle: {} -> {next}",
+                    path.join(" -> ")
+                );
+                path.push(next.clone());
+                let node = self.compile_step(rules, next, 0, region, path);
+                path.pop();
+                node
+            }
+        }
+    }
+
+    fn push_split(&mut self, category: usize, threshold: u32, below: usize, above: usize) -> usize {
+        if below == above {
+            return below;
+        }
+
+        self.nodes.push(Node::Split {
+            category,
+            threshold,
+            below,
+            above,
+        });
+        self.nodes.len() - 1
+    }
+
+    fn accepts(&self, part: &Part) -> bool {
+        let mut node = self.root;
+        loop {
+            match self.nodes[node] {
+                Node::Leaf(accepted) => return accepted,
+                Node::Split {
+                    category,
+                    threshold,
+                    below,
+                    above,
+                } => {
+                    node = if part.ratings[category] < threshold {
+                        below
+                    } else {
+                        above
+                    };
                 }
             }
         }
     }
 
-    paths
+    fn accepted_volume(&self) -> u64 {
+        self.accepted.iter().map(Hyperrect::volume).sum()
+    }
+
+    fn unreachable_rules(&self, rules: &'a HashMap<String, Rule>) -> Vec<RuleRef<'a>> {
+        rules
+            .iter()
+            .flat_map(|(name, rule)| {
+                (0..rule.workflow.len()).map(move |step| RuleRef {
+                    workflow: name,
+                    step,
+                })
+            })
+            .filter(|rule_ref| !self.reached.contains_key(rule_ref))
+            .sorted()
+            .collect()
+    }
+
+    fn redundant_rules(&self, rules: &'a HashMap<String, Rule>) -> Vec<RuleRef<'a>> {
+        self.reached
+            .keys()
+            .filter(|rule_ref| {
+                let workflow = &rules[rule_ref.workflow].workflow;
+                if rule_ref.step + 1 == workflow.len() {
+                    return false;
+                }
+
+                let action = &workflow[rule_ref.step].1;
+                self.fired.get(*rule_ref).is_none_or(|regions| {
+                    regions.iter().all(|region| {
+                        let taken = accepted_regions(rules, action, *region);
+                        let skipped = step_accepted_regions(
+                            rules,
+                            rule_ref.workflow,
+                            rule_ref.step + 1,
+                            *region,
+                        );
+                        same_regions(&taken, &skipped)
+                    })
+                })
+            })
+            .copied()
+            .sorted()
+            .collect()
+    }
+}
+
+fn step_accepted_regions(
+    rules: &HashMap<String, Rule>,
+    name: &str,
+    step: usize,
+    region: Hyperrect,
+) -> Vec<Hyperrect> {
+    let Some(&(condition, ref action)) = rules[name].workflow.get(step) else {
+        return vec![];
+    };
+
+    let (matched, unmatched) = split_condition(&region, condition);
+    matched
+        .map(|matched| accepted_regions(rules, action, matched))
         .into_iter()
-        .map(|ranges| {
-            ranges
-                .into_values()
-                .map(|range| range.len() as u64 + 1)
-                .product::<u64>()
-        })
-        .sum()
+        .chain(unmatched.map(|unmatched| step_accepted_regions(rules, name, step + 1, unmatched)))
+        .flatten()
+        .collect()
+}
+
+fn accepted_regions(
+    rules: &HashMap<String, Rule>,
+    action: &Action,
+    region: Hyperrect,
+) -> Vec<Hyperrect> {
+    match action {
+        Action::Accept => vec![region],
+        Action::Reject => vec![],
+        Action::Continue(next) => step_accepted_regions(rules, next, 0, region),
+    }
+}
+
+fn same_regions(a: &[Hyperrect], b: &[Hyperrect]) -> bool {
+    let volume = |regions: &[Hyperrect]| regions.iter().map(Hyperrect::volume).sum::<u64>();
+    let overlap = a
+        .iter()
+        .cartesian_product(b)
+        .filter_map(|(x, y)| x.intersection(y))
+        .map(|region| region.volume())
+        .sum::<u64>();
+    volume(a) == overlap && volume(b) == overlap
+}
+
+#[aoc(day19, part1)]
+fn part1((rules, parts): &(HashMap<String, Rule>, Vec<Part>)) -> anyhow::Result<u32> {
+    let tree = DecisionTree::compile(rules)?;
+    Ok(parts
+        .iter()
+        .filter(|part| tree.accepts(part))
+        .flat_map(|part| part.ratings)
+        .sum())
+}
+
+#[aoc(day19, part2)]
+fn part2((rules, _): &(HashMap<String, Rule>, Vec<Part>)) -> anyhow::Result<u64> {
+    DecisionTree::compile(rules).map(|tree| tree.accepted_volume())
+}
+
+#[aoc(day19, part2, analysis)]
+fn part2_analysis((rules, _): &(HashMap<String, Rule>, Vec<Part>)) -> anyhow::Result<String> {
+    let tree = DecisionTree::compile(rules)?;
+    let mut report = format!(
+        "accepted {} with {} decision nodes",
+        tree.accepted_volume(),
+        tree.nodes.len()
+    );
+    for rule_ref in tree.unreachable_rules(rules) {
+        report += &format!("\nunreachable: {rule_ref}");
+    }
+    for rule_ref in tree.redundant_rules(rules) {
+        report += &format!("\nredundant: {rule_ref}");
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "px{a<2006:qkq,m>2090:A,rfg}
+pv{a>1716:R,A}
+lnx{m>1548:A,A}
+rfg{s<537:gd,x>2440:R,A}
+qs{s>3448:A,lnx}
+qkq{x<1416:A,crn}
+crn{x>2662:A,R}
+in{s<1351:px,qqz}
+qqz{s>2770:qs,m<1801:hdj,R}
+gd{a>3333:R,R}
+hdj{m>838:A,pv}
+
+{x=787,m=2655,a=1222,s=2876}
+{x=1679,m=44,a=2067,s=496}
+{x=2036,m=264,a=79,s=2244}
+{x=2461,m=1339,a=466,s=291}
+{x=2127,m=1623,a=2188,s=1013}";
+
+    #[test]
+    fn test_part1() {
+        let input = generator(EXAMPLE).unwrap();
+        assert_eq!(part1(&input).unwrap(), 19114);
+    }
+
+    #[test]
+    fn test_part2() {
+        let input = generator(EXAMPLE).unwrap();
+        assert_eq!(part2(&input).unwrap(), 167409079868000);
+    }
+
+    #[test]
+    fn test_redundant_and_unreachable() {
+        let input = EXAMPLE.replace("\n\n", "\nzz{x<5:A,R}\nyy{x>4000:R,A}\n\n");
+        let (rules, _) = generator(&input).unwrap();
+        let tree = DecisionTree::compile(&rules).unwrap();
+        let names = |refs: Vec<RuleRef>| refs.iter().map(ToString::to_string).collect_vec();
+        assert_eq!(
+            names(tree.unreachable_rules(&rules)),
+            ["yy rule 1", "yy rule 2", "zz rule 1", "zz rule 2"]
+        );
+        assert_eq!(
+            names(tree.redundant_rules(&rules)),
+            ["gd rule 1", "lnx rule 1", "qs rule 1"]
+        );
+    }
+
+    #[test]
+    fn test_never_firing_rule() {
+        let (rules, _) = generator("in{x<5:a,R}\na{x>10:A,m<10:R,A}\n\n").unwrap();
+        let tree = DecisionTree::compile(&rules).unwrap();
+        assert_eq!(tree.accepted_volume(), 4 * 3991 * 4000 * 4000);
+        let redundant = tree.redundant_rules(&rules);
+        assert_eq!(redundant.len(), 1);
+        assert_eq!(redundant[0].to_string(), "a rule 1");
+    }
+
+    #[test]
+    fn test_cycle() {
+        let (rules, _) = generator("in{x<5:a,R}\na{m<10:in,A}\n\n").unwrap();
+        assert!(DecisionTree::compile(&rules).is_err());
+    }
 }
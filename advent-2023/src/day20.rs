@@ -1,8 +1,8 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 
-use anyhow::{Context, bail};
+use advent_common::modular::crt;
+use anyhow::{Context, bail, ensure};
 use aoc_runner_derive::{aoc, aoc_generator};
-use num::integer::lcm;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Pulse {
@@ -139,37 +139,211 @@ fn part1(configuration: &Configuration) -> u64 {
     low_pulses * high_pulses
 }
 
-#[aoc(day20, part2)]
-fn part2(configuration: &Configuration) -> Option<u64> {
-    let rx_input = configuration.iter().find_map(|(name, module)| {
-        module
-            .destinations
-            .contains(&"rx".to_string())
-            .then(|| name.clone())
-    })?;
-
-    let conjunction_inputs = match &configuration[&rx_input].module_type {
-        ModuleType::Conjunction { memory } => memory.keys().cloned().collect::<Vec<_>>(),
-        _ => panic!("rx input must be a conjunction"),
+const MAX_PRESSES: u64 = 1_000_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Counter {
+    input: String,
+    modules: usize,
+    first: u64,
+    period: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Analysis {
+    feeder: String,
+    counters: Vec<Counter>,
+}
+
+fn reachable(configuration: &Configuration, start: &str, stop: &str) -> HashSet<String> {
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::from([start.to_string()]);
+    while let Some(name) = queue.pop_front() {
+        if name == stop || !seen.insert(name.clone()) {
+            continue;
+        }
+
+        if let Some(module) = configuration.get(&name) {
+            queue.extend(module.destinations.iter().cloned());
+        }
+    }
+    seen
+}
+
+fn analyse(configuration: &Configuration) -> anyhow::Result<Analysis> {
+    let feeders = configuration
+        .iter()
+        .filter(|(_, module)| module.destinations.iter().any(|d| d == "rx"))
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    let feeder = match feeders.as_slice() {
+        [feeder] => feeder.clone(),
+        [] => bail!("no module sends pulses to rx"),
+        _ => bail!(
+            "rx is fed by several modules ({}), expected one",
+            feeders.join(", ")
+        ),
     };
 
-    let mut cycles = HashMap::new();
+    let inputs = match &configuration[&feeder].module_type {
+        ModuleType::Conjunction { memory } => {
+            let mut inputs = memory.keys().cloned().collect::<Vec<_>>();
+            inputs.sort();
+            inputs
+        }
+        ModuleType::FlipFlop { .. } => {
+            bail!("rx is fed by flip-flop {feeder}, which does not combine independent counters")
+        }
+        ModuleType::Broadcast => bail!("rx is fed directly by the broadcaster"),
+    };
+
+    let broadcaster = configuration
+        .get("broadcaster")
+        .context("no broadcaster module")?;
+    let subnetworks = broadcaster
+        .destinations
+        .iter()
+        .map(|start| (start, reachable(configuration, start, &feeder)))
+        .collect::<Vec<_>>();
+
+    for (i, (a, first)) in subnetworks.iter().enumerate() {
+        for (b, second) in &subnetworks[i + 1..] {
+            if let Some(shared) = first.intersection(second).min() {
+                bail!("counters starting at {a} and {b} are not independent: both reach {shared}");
+            }
+        }
+    }
+
+    let sizes = inputs
+        .iter()
+        .map(|input| {
+            subnetworks
+                .iter()
+                .find(|(_, modules)| modules.contains(input))
+                .map(|(_, modules)| modules.len())
+                .with_context(|| format!("{feeder} input {input} is not driven by the broadcaster"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut hits: HashMap<&str, Vec<u64>> = HashMap::new();
     let mut configuration = configuration.clone();
     let mut button_presses = 0;
-
-    while cycles.len() < conjunction_inputs.len() {
+    while inputs.iter().any(|input| {
+        hits.get(input.as_str())
+            .is_none_or(|presses| presses.len() < 3)
+    }) {
         button_presses += 1;
+        if button_presses > MAX_PRESSES {
+            let missing = inputs
+                .iter()
+                .filter(|input| {
+                    hits.get(input.as_str())
+                        .is_none_or(|presses| presses.len() < 3)
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+            bail!(
+                "{} did not send {feeder} three high pulses within {MAX_PRESSES} presses",
+                missing.join(", ")
+            );
+        }
+
         simulate_pulses(&mut configuration, |from, to, pulse| {
-            if to == rx_input
+            if to == feeder
                 && pulse == Pulse::High
-                && conjunction_inputs.contains(&from.to_string())
+                && let Some(input) = inputs.iter().find(|input| *input == from)
             {
-                cycles.entry(from.to_string()).or_insert(button_presses);
+                let presses = hits.entry(input.as_str()).or_default();
+                if presses.last() != Some(&button_presses) {
+                    presses.push(button_presses);
+                }
             }
         });
     }
 
-    cycles.values().copied().reduce(lcm)
+    let counters = inputs
+        .iter()
+        .zip(sizes)
+        .map(|(input, modules)| {
+            let presses = &hits[input.as_str()];
+            let (first, second, third) = (presses[0], presses[1], presses[2]);
+            ensure!(
+                second - first == third - second,
+                "{input} sends high pulses at presses {first}, {second} and {third}, which is not periodic"
+            );
+            Ok(Counter {
+                input: input.clone(),
+                modules,
+                first,
+                period: second - first,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(Analysis { feeder, counters })
+}
+
+impl Analysis {
+    fn first_low_press(&self) -> anyhow::Result<u64> {
+        let congruences = self
+            .counters
+            .iter()
+            .map(|counter| {
+                (
+                    (counter.first % counter.period) as i64,
+                    counter.period as i64,
+                )
+            })
+            .collect::<Vec<_>>();
+        let (residue, modulus) = crt(&congruences).with_context(|| {
+            let phases = self
+                .counters
+                .iter()
+                .map(|c| format!("{} at {} mod {}", c.input, c.first % c.period, c.period))
+                .collect::<Vec<_>>();
+            format!("counters never fire together: {}", phases.join(", "))
+        })?;
+        let (residue, modulus) = (residue as u64, modulus as u64);
+
+        let earliest = self.counters.iter().map(|c| c.first).max().unwrap_or(1);
+        let mut press = residue;
+        if press < earliest {
+            press += (earliest - press).div_ceil(modulus) * modulus;
+        }
+        Ok(press)
+    }
+}
+
+fn to_dot(configuration: &Configuration) -> String {
+    let mut names = configuration.keys().collect::<Vec<_>>();
+    names.sort();
+
+    let mut dot = String::from("digraph modules {\n");
+    for name in &names {
+        let (shape, prefix) = match configuration[*name].module_type {
+            ModuleType::FlipFlop { .. } => ("box", "%"),
+            ModuleType::Conjunction { .. } => ("diamond", "&"),
+            ModuleType::Broadcast => ("doublecircle", ""),
+        };
+        dot += &format!("    \"{name}\" [shape={shape}, label=\"{prefix}{name}\"];\n");
+    }
+    for name in &names {
+        for destination in &configuration[*name].destinations {
+            dot += &format!("    \"{name}\" -> \"{destination}\";\n");
+        }
+    }
+    dot += "}\n";
+    dot
+}
+
+#[aoc(day20, part2)]
+fn part2(configuration: &Configuration) -> anyhow::Result<u64> {
+    analyse(configuration)?.first_low_press()
+}
+
+#[aoc(day20, part2, dot)]
+fn part2_dot(configuration: &Configuration) -> String {
+    to_dot(configuration)
 }
 
 #[cfg(test)]
@@ -197,4 +371,62 @@ mod tests {
         let configuration = generator(input).unwrap();
         assert_eq!(part1(&configuration), 11687500);
     }
+
+    const COUNTERS: &str = "broadcaster -> a1, b1
+%a1 -> ia
+&ia -> fd
+%b1 -> b2
+%b2 -> ib
+&ib -> fd
+&fd -> rx";
+
+    fn brute_force(configuration: &Configuration) -> u64 {
+        let mut configuration = configuration.clone();
+        (1..)
+            .find(|_| {
+                let mut low = false;
+                simulate_pulses(&mut configuration, |_, to, pulse| {
+                    low |= to == "rx" && pulse == Pulse::Low;
+                });
+                low
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_analyse() {
+        let configuration = generator(COUNTERS).unwrap();
+        let analysis = analyse(&configuration).unwrap();
+        assert_eq!(analysis.feeder, "fd");
+        let periods = analysis
+            .counters
+            .iter()
+            .map(|c| (c.input.as_str(), c.modules, c.first, c.period))
+            .collect::<Vec<_>>();
+        assert_eq!(periods, [("ia", 2, 2, 2), ("ib", 3, 4, 4)]);
+        assert_eq!(part2(&configuration).unwrap(), brute_force(&configuration));
+    }
+
+    #[test]
+    fn test_unsupported_networks() {
+        let error = analyse(&generator("broadcaster -> a\n%a -> rx").unwrap()).unwrap_err();
+        assert!(error.to_string().contains("flip-flop a"));
+
+        let shared = "broadcaster -> a, b
+%a -> c
+%b -> c
+&c -> fd
+&fd -> rx";
+        let error = analyse(&generator(shared).unwrap()).unwrap_err();
+        assert!(error.to_string().contains("not independent"));
+    }
+
+    #[test]
+    fn test_dot() {
+        let dot = to_dot(&generator(COUNTERS).unwrap());
+        assert!(dot.starts_with("digraph modules {"));
+        assert!(dot.contains("\"a1\" [shape=box, label=\"%a1\"];"));
+        assert!(dot.contains("\"fd\" [shape=diamond, label=\"&fd\"];"));
+        assert!(dot.contains("\"fd\" -> \"rx\";"));
+    }
 }
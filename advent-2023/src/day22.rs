@@ -1,97 +1,32 @@
-use std::{
-    collections::{HashMap, HashSet},
-    str::FromStr,
-};
+use std::collections::{HashMap, HashSet};
 
+use advent_common::cuboid::Cuboid;
 use anyhow::{anyhow, bail};
 use aoc_runner_derive::{aoc, aoc_generator};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-struct Brick {
-    start: (usize, usize, usize),
-    end: (usize, usize, usize),
-}
-
-impl Brick {
-    fn min_z(&self) -> usize {
-        let (_, _, z1) = self.start;
-        let (_, _, z2) = self.end;
-        z1.min(z2)
-    }
+fn parse_brick(s: &str) -> anyhow::Result<Cuboid> {
+    let (start_str, end_str) = s
+        .split_once('~')
+        .ok_or_else(|| anyhow!("Invalid brick format"))?;
 
-    fn max_z(&self) -> usize {
-        let (_, _, z1) = self.start;
-        let (_, _, z2) = self.end;
-        z1.max(z2)
-    }
+    let start_coords: Vec<i64> = start_str
+        .split(',')
+        .map(str::parse)
+        .collect::<Result<Vec<_>, _>>()?;
 
-    fn occupies(&self) -> Vec<(usize, usize, usize)> {
-        let (x1, y1, z1) = self.start;
-        let (x2, y2, z2) = self.end;
+    let end_coords: Vec<i64> = end_str
+        .split(',')
+        .map(str::parse)
+        .collect::<Result<Vec<_>, _>>()?;
 
-        (x1.min(x2)..=x1.max(x2))
-            .flat_map(|x| {
-                (y1.min(y2)..=y1.max(y2))
-                    .flat_map(move |y| (z1.min(z2)..=z1.max(z2)).map(move |z| (x, y, z)))
-            })
-            .collect()
-    }
-
-    fn drop_to(&self, new_z: usize) -> Brick {
-        let z_diff = self.min_z() - new_z;
-        let (x1, y1, z1) = self.start;
-        let (x2, y2, z2) = self.end;
-        Brick {
-            start: (x1, y1, z1 - z_diff),
-            end: (x2, y2, z2 - z_diff),
-        }
-    }
-
-    fn overlaps_xy(&self, other: &Brick) -> bool {
-        let (x1, y1, _) = self.start;
-        let (x2, y2, _) = self.end;
-        let (other_x1, other_y1, _) = other.start;
-        let (other_x2, other_y2, _) = other.end;
-
-        let x_overlap =
-            x1.min(x2) <= other_x1.max(other_x2) && other_x1.min(other_x2) <= x1.max(x2);
-        let y_overlap =
-            y1.min(y2) <= other_y1.max(other_y2) && other_y1.min(other_y2) <= y1.max(y2);
-
-        x_overlap && y_overlap
-    }
-}
-
-impl FromStr for Brick {
-    type Err = anyhow::Error;
-
-    fn from_str(s: &str) -> anyhow::Result<Self> {
-        let (start_str, end_str) = s
-            .split_once('~')
-            .ok_or_else(|| anyhow!("Invalid brick format"))?;
-
-        let start_coords: Vec<usize> = start_str
-            .split(',')
-            .map(str::parse)
-            .collect::<Result<Vec<_>, _>>()?;
-
-        let end_coords: Vec<usize> = end_str
-            .split(',')
-            .map(str::parse)
-            .collect::<Result<Vec<_>, _>>()?;
-
-        match (start_coords.as_slice(), end_coords.as_slice()) {
-            ([x1, y1, z1], [x2, y2, z2]) => Ok(Brick {
-                start: (*x1, *y1, *z1),
-                end: (*x2, *y2, *z2),
-            }),
-            _ => bail!("Invalid coordinate count"),
-        }
+    match (start_coords.as_slice(), end_coords.as_slice()) {
+        (&[x1, y1, z1], &[x2, y2, z2]) => Ok(Cuboid::new([x1, y1, z1], [x2, y2, z2])),
+        _ => bail!("Invalid coordinate count"),
     }
 }
 
 fn build_support_relationships(
-    bricks: &[Brick],
+    bricks: &[Cuboid],
 ) -> (
     HashMap<usize, HashSet<usize>>,
     HashMap<usize, HashSet<usize>>,
@@ -103,7 +38,7 @@ fn build_support_relationships(
 
     for (i, brick) in bricks.iter().enumerate() {
         for (j, other) in bricks.iter().enumerate() {
-            if i != j && other.max_z() + 1 == brick.min_z() && brick.overlaps_xy(other) {
+            if i != j && brick.translate([0, 0, -1]).intersects(other) {
                 supports.get_mut(&j).unwrap().insert(i);
                 supported_by.get_mut(&i).unwrap().insert(j);
             }
@@ -113,32 +48,26 @@ fn build_support_relationships(
     (supports, supported_by)
 }
 
-fn settle_bricks(bricks: &[Brick]) -> Vec<Brick> {
+fn settle_bricks(bricks: &[Cuboid]) -> Vec<Cuboid> {
     let mut sorted_bricks = bricks.to_vec();
-    sorted_bricks.sort_by_key(Brick::min_z);
-
-    let mut settled = Vec::new();
-    let mut occupied = HashSet::new();
+    sorted_bricks.sort_by_key(|brick| brick.min[2]);
 
+    let mut settled: Vec<Cuboid> = Vec::with_capacity(sorted_bricks.len());
     for brick in sorted_bricks {
-        let mut current = brick.clone();
-
-        while current.min_z() > 1 {
-            let dropped = current.drop_to(current.min_z() - 1);
-            if dropped.occupies().iter().any(|pos| occupied.contains(pos)) {
-                break;
-            }
-            current = dropped;
-        }
-
-        occupied.extend(current.occupies());
-        settled.push(current);
+        let shadow = Cuboid::new([brick.min[0], brick.min[1], 1], brick.max);
+        let floor = settled
+            .iter()
+            .filter(|other| other.intersects(&shadow))
+            .map(|other| other.max[2])
+            .max()
+            .unwrap_or(0);
+        settled.push(brick.translate([0, 0, floor + 1 - brick.min[2]]));
     }
 
     settled
 }
 
-fn count_falling_bricks(bricks: &[Brick], removed_index: usize) -> usize {
+fn count_falling_bricks(bricks: &[Cuboid], removed_index: usize) -> usize {
     let (_, supported_by) = build_support_relationships(bricks);
     let mut falling = HashSet::from([removed_index]);
 
@@ -162,7 +91,7 @@ fn count_falling_bricks(bricks: &[Brick], removed_index: usize) -> usize {
     falling.len() - 1
 }
 
-fn count_safe_to_disintegrate(bricks: &[Brick]) -> usize {
+fn count_safe_to_disintegrate(bricks: &[Cuboid]) -> usize {
     let (supports, supported_by) = build_support_relationships(bricks);
 
     (0..bricks.len())
@@ -179,18 +108,18 @@ fn count_safe_to_disintegrate(bricks: &[Brick]) -> usize {
 }
 
 #[aoc_generator(day22)]
-fn generator(input: &str) -> anyhow::Result<Vec<Brick>> {
-    input.lines().map(str::parse).collect()
+fn generator(input: &str) -> anyhow::Result<Vec<Cuboid>> {
+    input.lines().map(parse_brick).collect()
 }
 
 #[aoc(day22, part1)]
-fn part1(bricks: &[Brick]) -> usize {
+fn part1(bricks: &[Cuboid]) -> usize {
     let settled = settle_bricks(bricks);
     count_safe_to_disintegrate(&settled)
 }
 
 #[aoc(day22, part2)]
-fn part2(bricks: &[Brick]) -> usize {
+fn part2(bricks: &[Cuboid]) -> usize {
     let settled = settle_bricks(bricks);
 
     (0..settled.len())
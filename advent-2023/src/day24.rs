@@ -1,16 +1,17 @@
 use std::str::FromStr;
 
-use anyhow::{Result, anyhow, ensure};
+use anyhow::{Context, Result, anyhow, ensure};
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
+use num::{BigInt, BigRational, Signed, ToPrimitive, Zero, rational::Ratio};
 
-type Vec3 = (f64, f64, f64);
 type IVec3 = (i64, i64, i64);
+type Rational = Ratio<i128>;
 
 #[derive(Debug, Clone)]
 struct Hailstone {
-    position: Vec3,
-    velocity: Vec3,
+    position: IVec3,
+    velocity: IVec3,
 }
 
 impl FromStr for Hailstone {
@@ -23,15 +24,15 @@ impl FromStr for Hailstone {
 
         let position = pos_str
             .split(", ")
-            .map(|x| x.trim().parse::<f64>())
+            .map(|x| x.trim().parse::<i64>())
             .collect::<Result<Vec<_>, _>>()?;
         let velocity = vel_str
             .split(", ")
-            .map(|x| x.trim().parse::<f64>())
+            .map(|x| x.trim().parse::<i64>())
             .collect::<Result<Vec<_>, _>>()?;
 
         ensure!(
-            position.len() == 3 || velocity.len() == 3,
+            position.len() == 3 && velocity.len() == 3,
             "Expected 3D coordinates"
         );
 
@@ -47,132 +48,195 @@ fn generator(input: &str) -> Result<Vec<Hailstone>> {
     input.lines().map(str::parse).collect()
 }
 
-fn find_2d_intersection(h1: &Hailstone, h2: &Hailstone) -> Option<(f64, f64, f64, f64)> {
+fn find_2d_intersection(
+    h1: &Hailstone,
+    h2: &Hailstone,
+) -> Option<(Rational, Rational, Rational, Rational)> {
     let ((x1, y1, _), (vx1, vy1, _)) = (h1.position, h1.velocity);
     let ((x2, y2, _), (vx2, vy2, _)) = (h2.position, h2.velocity);
+    let (x1, y1, vx1, vy1) = (x1 as i128, y1 as i128, vx1 as i128, vy1 as i128);
+    let (x2, y2, vx2, vy2) = (x2 as i128, y2 as i128, vx2 as i128, vy2 as i128);
 
     let det = vx1 * vy2 - vy1 * vx2;
-    if det.abs() < 1e-10 {
+    if det == 0 {
         return None;
     }
 
     let (dx, dy) = (x2 - x1, y2 - y1);
-    let t1 = (dx * vy2 - dy * vx2) / det;
-    let t2 = (dx * vy1 - dy * vx1) / det;
-
-    Some((x1 + t1 * vx1, y1 + t1 * vy1, t1, t2))
+    let t1 = Rational::new(dx * vy2 - dy * vx2, det);
+    let t2 = Rational::new(dx * vy1 - dy * vx1, det);
+
+    Some((
+        Rational::from(x1) + t1 * vx1,
+        Rational::from(y1) + t1 * vy1,
+        t1,
+        t2,
+    ))
 }
 
-#[aoc(day24, part1)]
-fn part1(hailstones: &[Hailstone]) -> usize {
-    const MIN: f64 = 200_000_000_000_000.0;
-    const MAX: f64 = 400_000_000_000_000.0;
-
-    (0..hailstones.len())
+fn count_intersections(hailstones: &[Hailstone], min: i64, max: i64) -> usize {
+    let bounds = Rational::from(min as i128)..=Rational::from(max as i128);
+    hailstones
+        .iter()
         .tuple_combinations()
-        .filter_map(|(i, j)| find_2d_intersection(&hailstones[i], &hailstones[j]))
-        .filter(|&(x, y, t1, t2)| {
-            t1 >= 0.0 && t2 >= 0.0 && (MIN..=MAX).contains(&x) && (MIN..=MAX).contains(&y)
+        .filter_map(|(h1, h2)| find_2d_intersection(h1, h2))
+        .filter(|(x, y, t1, t2)| {
+            !t1.is_negative() && !t2.is_negative() && bounds.contains(x) && bounds.contains(y)
         })
         .count()
 }
 
-fn to_ivec3((x, y, z): Vec3) -> IVec3 {
-    (x as i64, y as i64, z as i64)
+#[aoc(day24, part1)]
+fn part1(hailstones: &[Hailstone]) -> usize {
+    count_intersections(hailstones, 200_000_000_000_000, 400_000_000_000_000)
 }
 
-fn check_velocity(hailstones: &[Hailstone], rock_vel: IVec3) -> Option<IVec3> {
-    if hailstones.len() < 2 {
-        return None;
-    }
-
-    let (rvx, rvy, rvz) = rock_vel;
-    let h0 = &hailstones[0];
-    let h1 = &hailstones[1];
-
-    let (hx0, hy0, hz0) = to_ivec3(h0.position);
-    let (vx0, vy0, vz0) = to_ivec3(h0.velocity);
-    let (hx1, hy1, _) = to_ivec3(h1.position);
-    let (vx1, vy1, vz1) = to_ivec3(h1.velocity);
-
-    if (vx0, vy0, vz0) == rock_vel || (vx1, vy1, vz1) == rock_vel {
-        return None;
-    }
-
-    let a = vy0 - rvy;
-    let b = rvx - vx0;
-    let c = hx0 * (vy0 - rvy) - hy0 * (vx0 - rvx);
-
-    let d = vy1 - rvy;
-    let e = rvx - vx1;
-    let f = hx1 * (vy1 - rvy) - hy1 * (vx1 - rvx);
-
-    let det = a * e - b * d;
-    if det == 0 {
-        return None;
-    }
+fn cross(a: [BigInt; 3], b: [BigInt; 3]) -> [BigInt; 3] {
+    let [ax, ay, az] = a;
+    let [bx, by, bz] = b;
+    [
+        &ay * &bz - &az * &by,
+        &az * &bx - &ax * &bz,
+        ax * by - ay * bx,
+    ]
+}
 
-    let rx_num = c * e - b * f;
-    let ry_num = a * f - c * d;
+fn to_big((x, y, z): IVec3) -> [BigInt; 3] {
+    [x.into(), y.into(), z.into()]
+}
 
-    if rx_num % det != 0 || ry_num % det != 0 {
-        return None;
-    }
+fn pair_equations(h1: &Hailstone, h2: &Hailstone) -> Vec<(Vec<BigInt>, BigInt)> {
+    let [dx, dy, dz] = to_big(h2.velocity)
+        .into_iter()
+        .zip(to_big(h1.velocity))
+        .map(|(a, b)| a - b)
+        .collect_array()
+        .unwrap();
+    let [ex, ey, ez] = to_big(h2.position)
+        .into_iter()
+        .zip(to_big(h1.position))
+        .map(|(a, b)| a - b)
+        .collect_array()
+        .unwrap();
+    let rhs = cross(to_big(h2.position), to_big(h2.velocity))
+        .into_iter()
+        .zip(cross(to_big(h1.position), to_big(h1.velocity)))
+        .map(|(a, b)| a - b);
+
+    let zero = BigInt::zero;
+    [
+        vec![zero(), dz.clone(), -&dy, zero(), -&ez, ey.clone()],
+        vec![-&dz, zero(), dx.clone(), ez, zero(), -&ex],
+        vec![dy, -dx, zero(), -ey, ex, zero()],
+    ]
+    .into_iter()
+    .zip(rhs)
+    .collect()
+}
 
-    let (rx, ry) = (rx_num / det, ry_num / det);
+fn solve_linear_system(mut rows: Vec<Vec<BigRational>>) -> Option<Vec<BigRational>> {
+    let n = rows.len();
+    for col in 0..n {
+        let pivot = (col..n).find(|&row| !rows[row][col].is_zero())?;
+        rows.swap(col, pivot);
 
-    let t0_num = rx - hx0;
-    let t0_den = vx0 - rvx;
+        let pivot_row = rows[col].clone();
+        for (row, values) in rows.iter_mut().enumerate() {
+            if row == col || values[col].is_zero() {
+                continue;
+            }
 
-    if t0_den == 0 || t0_num % t0_den != 0 || t0_num * t0_den <= 0 {
-        return None;
+            let factor = &values[col] / &pivot_row[col];
+            for (value, pivot_value) in values.iter_mut().zip(&pivot_row).skip(col) {
+                *value -= &factor * pivot_value;
+            }
+        }
     }
 
-    let t0 = t0_num / t0_den;
-    let rz = hz0 + t0 * vz0 - t0 * rvz;
-
-    Some((rx, ry, rz))
+    Some(
+        rows.into_iter()
+            .enumerate()
+            .map(|(i, row)| &row[n] / &row[i])
+            .collect(),
+    )
 }
 
-fn verify_solution(hailstones: &[Hailstone], rock_pos: IVec3, rock_vel: IVec3) -> bool {
-    let (rx, ry, rz) = rock_pos;
-    let (rvx, rvy, rvz) = rock_vel;
-
+fn hits_all(hailstones: &[Hailstone], position: IVec3, velocity: IVec3) -> bool {
     hailstones.iter().all(|h| {
-        let (hx, hy, hz) = to_ivec3(h.position);
-        let (vx, vy, vz) = to_ivec3(h.velocity);
-
-        if vx == rvx && hx != rx {
-            return false;
-        }
-
-        let t_num = rx - hx;
-        let t_den = vx - rvx;
-
-        if t_den != 0 {
-            if t_num % t_den != 0 || t_num * t_den < 0 {
-                return false;
-            }
-            let t = t_num / t_den;
-            hy + t * vy == ry + t * rvy && hz + t * vz == rz + t * rvz
-        } else {
-            true
+        let relative_position = to_big(h.position)
+            .into_iter()
+            .zip(to_big(position))
+            .map(|(a, b)| a - b)
+            .collect_array()
+            .unwrap();
+        let relative_velocity = to_big(velocity)
+            .into_iter()
+            .zip(to_big(h.velocity))
+            .map(|(a, b)| a - b)
+            .collect_array()
+            .unwrap();
+        if relative_velocity.iter().all(Zero::is_zero) {
+            return relative_position.iter().all(Zero::is_zero);
         }
+        let parallel = cross(relative_position.clone(), relative_velocity.clone())
+            .iter()
+            .all(Zero::is_zero);
+        let forward = relative_position
+            .iter()
+            .zip(&relative_velocity)
+            .map(|(p, v)| p * v)
+            .sum::<BigInt>()
+            >= BigInt::zero();
+        parallel && forward
     })
 }
 
-#[aoc(day24, part2)]
-fn part2(hailstones: &[Hailstone]) -> i64 {
-    (-300i64..=300)
-        .flat_map(|vx| {
-            (-300i64..=300).flat_map(move |vy| (-300i64..=300).map(move |vz| (vx, vy, vz)))
+fn find_rock(hailstones: &[Hailstone]) -> Result<(IVec3, IVec3)> {
+    let solution = (0..hailstones.len())
+        .tuple_combinations()
+        .find_map(|(i, j, k)| {
+            let rows = pair_equations(&hailstones[i], &hailstones[j])
+                .into_iter()
+                .chain(pair_equations(&hailstones[i], &hailstones[k]))
+                .map(|(coefficients, rhs)| {
+                    coefficients
+                        .into_iter()
+                        .chain([rhs])
+                        .map(BigRational::from_integer)
+                        .collect()
+                })
+                .collect();
+            solve_linear_system(rows)
         })
-        .find_map(|vel| {
-            check_velocity(hailstones, vel)
-                .filter(|&pos| verify_solution(hailstones, pos, vel))
-                .map(|(x, y, z)| x + y + z)
+        .context("No three hailstones determine a unique rock trajectory")?;
+
+    let values = solution
+        .iter()
+        .map(|value| {
+            ensure!(
+                value.is_integer(),
+                "Rock coordinate {value} is not an integer"
+            );
+            value
+                .to_integer()
+                .to_i64()
+                .context(format!("Rock coordinate {value} does not fit in i64"))
         })
-        .unwrap_or(0)
+        .collect::<Result<Vec<_>>>()?;
+
+    let position = (values[0], values[1], values[2]);
+    let velocity = (values[3], values[4], values[5]);
+    ensure!(
+        hits_all(hailstones, position, velocity),
+        "Rock at {position:?} @ {velocity:?} misses some hailstones"
+    );
+    Ok((position, velocity))
+}
+
+#[aoc(day24, part2)]
+fn part2(hailstones: &[Hailstone]) -> Result<i64> {
+    let ((x, y, z), _) = find_rock(hailstones)?;
+    Ok(x + y + z)
 }
 
 #[cfg(test)]
@@ -188,19 +252,47 @@ mod tests {
     #[test]
     fn test_part1() {
         let hailstones = generator(EXAMPLE).unwrap();
-        let result = (0..hailstones.len())
-            .tuple_combinations()
-            .filter_map(|(i, j)| find_2d_intersection(&hailstones[i], &hailstones[j]))
-            .filter(|&(x, y, t1, t2)| {
-                t1 >= 0.0 && t2 >= 0.0 && (7.0..=27.0).contains(&x) && (7.0..=27.0).contains(&y)
-            })
-            .count();
-        assert_eq!(result, 2);
+        assert_eq!(count_intersections(&hailstones, 7, 27), 2);
     }
 
     #[test]
     fn test_part2() {
         let hailstones = generator(EXAMPLE).unwrap();
-        assert_eq!(part2(&hailstones), 47);
+        assert_eq!(part2(&hailstones).unwrap(), 47);
+    }
+
+    #[test]
+    fn test_find_rock() {
+        let hailstones = generator(EXAMPLE).unwrap();
+        assert_eq!(find_rock(&hailstones).unwrap(), ((24, 13, 10), (-3, 1, 2)));
+    }
+
+    #[test]
+    fn test_exact_intersection() {
+        let hailstones = generator(EXAMPLE).unwrap();
+        let (x, y, t1, t2) = find_2d_intersection(&hailstones[0], &hailstones[1]).unwrap();
+        assert_eq!(x, Rational::new(43, 3));
+        assert_eq!(y, Rational::new(46, 3));
+        assert!(t1.is_positive() && t2.is_positive());
+        assert!(find_2d_intersection(&hailstones[1], &hailstones[2]).is_none());
+    }
+
+    #[test]
+    fn test_boundary_is_inclusive() {
+        let hailstones = generator("0, 0, 0 @ 1, 1, 0\n10, 0, 0 @ -1, 1, 0").unwrap();
+        assert_eq!(count_intersections(&hailstones, 5, 5), 1);
+        assert_eq!(count_intersections(&hailstones, 6, 7), 0);
+    }
+
+    #[test]
+    fn test_hits_all_rejects_stationary_miss() {
+        let hailstones = generator(EXAMPLE).unwrap();
+        let (position, velocity) = find_rock(&hailstones).unwrap();
+        assert!(hits_all(&hailstones, position, velocity));
+
+        let alongside = generator("25, 13, 10 @ -3, 1, 2").unwrap();
+        assert!(!hits_all(&alongside, position, velocity));
+        let same = generator("24, 13, 10 @ -3, 1, 2").unwrap();
+        assert!(hits_all(&same, position, velocity));
     }
 }
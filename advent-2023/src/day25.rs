@@ -1,5 +1,4 @@
 use std::{
-    cmp::Reverse,
     collections::{HashMap, HashSet, VecDeque},
     iter,
 };
@@ -68,28 +67,166 @@ fn normalize_edge(a: &str, b: &str) -> (String, String) {
     }
 }
 
-#[aoc(day25, part1)]
-fn part1(graph: &Graph) -> Option<usize> {
-    let nodes: Vec<_> = graph.keys().cloned().collect();
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cut {
+    edges: Vec<(String, String)>,
+    sizes: (usize, usize),
+}
+
+struct IndexedGraph<'a> {
+    names: Vec<&'a str>,
+    edges: Vec<(usize, usize)>,
+    adjacency: Vec<Vec<(usize, usize)>>,
+}
+
+impl<'a> IndexedGraph<'a> {
+    fn new(graph: &'a Graph) -> Self {
+        let names = graph.keys().map(String::as_str).sorted().collect_vec();
+        let index = names
+            .iter()
+            .enumerate()
+            .map(|(i, &name)| (name, i))
+            .collect::<HashMap<_, _>>();
+
+        let mut edges = vec![];
+        let mut adjacency = vec![vec![]; names.len()];
+        for (u, &name) in names.iter().enumerate() {
+            for neighbor in graph[name].iter().sorted() {
+                let v = index[neighbor.as_str()];
+                if u < v {
+                    adjacency[u].push((v, edges.len()));
+                    adjacency[v].push((u, edges.len()));
+                    edges.push((u, v));
+                }
+            }
+        }
+
+        IndexedGraph {
+            names,
+            edges,
+            adjacency,
+        }
+    }
+
+    fn cut(&self, side: &HashSet<usize>) -> Cut {
+        let edges = self
+            .edges
+            .iter()
+            .filter(|(u, v)| side.contains(u) != side.contains(v))
+            .map(|&(u, v)| normalize_edge(self.names[u], self.names[v]))
+            .sorted()
+            .collect();
+        Cut {
+            edges,
+            sizes: (side.len(), self.names.len() - side.len()),
+        }
+    }
+
+    fn bounded_cut(&self, source: usize, sink: usize, limit: usize) -> Option<HashSet<usize>> {
+        let mut flow = vec![0i8; self.edges.len()];
+        let residual = |flow: &[i8], from: usize, edge: usize| {
+            if self.edges[edge].0 == from {
+                1 - flow[edge]
+            } else {
+                1 + flow[edge]
+            }
+        };
+
+        let mut parent = vec![None; self.names.len()];
+        let mut visited = vec![false; self.names.len()];
+        for _ in 0..limit {
+            parent.fill(None);
+            visited.fill(false);
+            visited[source] = true;
+            let mut queue = VecDeque::from([source]);
+            while let Some(node) = queue.pop_front() {
+                if node == sink {
+                    break;
+                }
+
+                for &(neighbor, edge) in &self.adjacency[node] {
+                    if residual(&flow, node, edge) > 0 && !visited[neighbor] {
+                        visited[neighbor] = true;
+                        parent[neighbor] = Some((node, edge));
+                        queue.push_back(neighbor);
+                    }
+                }
+            }
+
+            if !visited[sink] {
+                return Some(
+                    (0..self.names.len())
+                        .filter(|&node| visited[node])
+                        .collect(),
+                );
+            }
+
+            let mut node = sink;
+            while let Some((previous, edge)) = parent[node] {
+                flow[edge] += if self.edges[edge].0 == previous {
+                    1
+                } else {
+                    -1
+                };
+                node = previous;
+            }
+        }
+
+        None
+    }
+}
+
+fn minimum_cut(graph: &Graph, upper_bound: Option<Cut>) -> Option<Cut> {
+    let indexed = IndexedGraph::new(graph);
+    if indexed.names.len() < 2 {
+        return None;
+    }
+
+    let mut best = upper_bound.unwrap_or_else(|| {
+        let smallest = (0..indexed.names.len())
+            .min_by_key(|&node| indexed.adjacency[node].len())
+            .unwrap();
+        indexed.cut(&HashSet::from([smallest]))
+    });
+
+    for sink in 1..indexed.names.len() {
+        if let Some(side) = indexed.bounded_cut(0, sink, best.edges.len()) {
+            best = indexed.cut(&side);
+        }
+    }
+
+    Some(best)
+}
+
+fn heuristic_cut(graph: &Graph, size: usize) -> Option<Cut> {
+    let nodes: Vec<_> = graph.keys().cloned().sorted().collect();
     let total_nodes = nodes.len();
 
     let edge_count = count_edge_usage(graph, &nodes);
 
     let mut edges: Vec<_> = edge_count.into_iter().collect();
-    edges.sort_unstable_by_key(|(_, count)| Reverse(*count));
+    edges.sort_unstable_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
 
     (0..edges.len().min(15))
-        .combinations(3)
+        .combinations(size)
         .find_map(|indices| {
             let excluded: HashSet<_> = indices.iter().map(|&i| edges[i].0.clone()).collect();
 
             let reachable = count_reachable(graph, &nodes[0], &excluded);
 
-            (reachable < total_nodes && reachable > 0)
-                .then(|| reachable * (total_nodes - reachable))
+            (reachable < total_nodes && reachable > 0).then(|| Cut {
+                edges: excluded.into_iter().sorted().collect(),
+                sizes: (reachable, total_nodes - reachable),
+            })
         })
 }
 
+#[aoc(day25, part1)]
+fn part1(graph: &Graph) -> Option<usize> {
+    let cut = minimum_cut(graph, heuristic_cut(graph, 3))?;
+    Some(cut.sizes.0 * cut.sizes.1)
+}
+
 fn count_edge_usage(graph: &Graph, nodes: &[String]) -> HashMap<(String, String), usize> {
     let total_nodes = nodes.len();
 
@@ -199,4 +336,72 @@ frs: qnr lhk lsr";
         let graph = generator(EXAMPLE).unwrap();
         assert_eq!(part1(&graph), Some(54));
     }
+
+    fn sorted_sizes(cut: &Cut) -> (usize, usize) {
+        (cut.sizes.0.min(cut.sizes.1), cut.sizes.0.max(cut.sizes.1))
+    }
+
+    #[test]
+    fn test_minimum_cut() {
+        let graph = generator(EXAMPLE).unwrap();
+        let cut = minimum_cut(&graph, None).unwrap();
+        assert_eq!(
+            cut.edges,
+            [
+                ("bvb".to_string(), "cmg".to_string()),
+                ("hfx".to_string(), "pzl".to_string()),
+                ("jqt".to_string(), "nvd".to_string()),
+            ]
+        );
+        assert_eq!(sorted_sizes(&cut), (6, 9));
+    }
+
+    #[test]
+    fn test_heuristic_is_verified() {
+        let graph = generator(EXAMPLE).unwrap();
+        let heuristic = heuristic_cut(&graph, 3).unwrap();
+        assert_eq!(
+            minimum_cut(&graph, Some(heuristic.clone())),
+            Some(heuristic)
+        );
+
+        let bad_guess = Cut {
+            edges: graph["jqt"]
+                .iter()
+                .map(|neighbor| normalize_edge("jqt", neighbor))
+                .collect(),
+            sizes: (1, 14),
+        };
+        let cut = minimum_cut(&graph, Some(bad_guess)).unwrap();
+        assert_eq!(cut.edges.len(), 3);
+    }
+
+    #[test]
+    fn test_other_cut_sizes() {
+        let graph = generator(
+            "a: b c d
+b: c d
+c: d
+e: f g h
+f: g h
+g: h
+a: e
+b: f",
+        )
+        .unwrap();
+        let cut = minimum_cut(&graph, None).unwrap();
+        assert_eq!(
+            cut.edges,
+            [
+                ("a".to_string(), "e".to_string()),
+                ("b".to_string(), "f".to_string()),
+            ]
+        );
+        assert_eq!(sorted_sizes(&cut), (4, 4));
+
+        let graph = generator("a: b c\nb: c\nc: d").unwrap();
+        let cut = minimum_cut(&graph, None).unwrap();
+        assert_eq!(cut.edges, [("c".to_string(), "d".to_string())]);
+        assert_eq!(sorted_sizes(&cut), (1, 3));
+    }
 }
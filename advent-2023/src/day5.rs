@@ -1,5 +1,6 @@
-use std::{cmp::min, collections::HashMap, str::FromStr};
+use std::{collections::HashMap, ops::RangeInclusive, str::FromStr};
 
+use advent_common::interval::IntervalSet;
 use anyhow::Context;
 use aoc_runner_derive::{aoc, aoc_generator};
 
@@ -36,9 +37,8 @@ impl FromStr for Category {
 }
 
 struct RangeMap {
-    destination_start: u32,
-    source_start: u32,
-    length: u32,
+    source: RangeInclusive<u32>,
+    offset: i128,
 }
 
 impl FromStr for RangeMap {
@@ -47,67 +47,35 @@ impl FromStr for RangeMap {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let parts = s
             .split_ascii_whitespace()
-            .map(str::parse)
+            .map(str::parse::<u32>)
             .collect::<Result<Vec<_>, _>>()?;
-        let destination_start = parts[0];
-        let source_start = parts[1];
-        let length = parts[2];
+        let &[destination_start, source_start, length] = parts.as_slice() else {
+            anyhow::bail!("expected three numbers: {}", s);
+        };
+        let source_end = length
+            .checked_sub(1)
+            .and_then(|last| source_start.checked_add(last))
+            .context("range length out of bounds")?;
 
         Ok(Self {
-            destination_start,
-            source_start,
-            length,
+            source: source_start..=source_end,
+            offset: i128::from(destination_start) - i128::from(source_start),
         })
     }
 }
 
-impl RangeMap {
-    fn lookup(&self, value: u32) -> Option<u32> {
-        if value < self.source_start || value >= self.source_start + self.length {
-            return None;
-        }
-
-        let offset = value - self.source_start;
-        Some(self.destination_start + offset)
-    }
-}
-
 struct CategoryMap {
     destination: Category,
     range_maps: Vec<RangeMap>,
 }
 
 impl CategoryMap {
-    fn map_value(&self, value: u32) -> u32 {
-        self.range_maps
-            .iter()
-            .find_map(|rm| rm.lookup(value))
-            .unwrap_or(value)
-    }
-
-    fn map_range(&self, (start, length): (u32, u32)) -> Vec<(u32, u32)> {
-        let candidate = self
-            .range_maps
-            .iter()
-            .find(|rm| start >= rm.source_start && start < rm.source_start + rm.length);
-        if let Some(range) = candidate {
-            let current_end = start + length;
-            let range_end = range.source_start + range.length;
-            let dest_start = range.lookup(start).unwrap();
-            if current_end <= range_end {
-                return vec![(dest_start, length)];
-            }
-
-            let overage = current_end - range_end;
-            let dest_length = range_end - start;
-
-            let mut result = vec![(dest_start, dest_length)];
-            let rest = self.map_range((range_end, overage));
-            result.extend_from_slice(&rest);
-            result
-        } else {
-            vec![(start, length)]
-        }
+    fn map_set(&self, values: &IntervalSet<u32>) -> Option<IntervalSet<u32>> {
+        values.map_offsets(
+            self.range_maps
+                .iter()
+                .map(|rm| (rm.source.clone(), rm.offset)),
+        )
     }
 }
 
@@ -131,11 +99,10 @@ fn generator(input: &str) -> anyhow::Result<Almanac> {
             let (label, mapping_lines) = part.split_once('\n').context("splitting label line")?;
             let (source, rest) = label.split_once("-to-").context("splitting label")?;
             let (destination, _) = rest.split_once(' ').context("splitting destination")?;
-            let mut range_maps = mapping_lines
+            let range_maps = mapping_lines
                 .lines()
                 .map(str::parse)
                 .collect::<Result<Vec<RangeMap>, _>>()?;
-            range_maps.sort_unstable_by_key(|rm| rm.source_start);
 
             acc.insert(
                 source.parse()?,
@@ -154,49 +121,45 @@ fn generator(input: &str) -> anyhow::Result<Almanac> {
     })
 }
 
-#[aoc(day5, part1)]
-fn part1(input: &Almanac) -> Option<u32> {
-    let mut lowest_value = u32::MAX;
-    for value in &input.seeds {
-        let mut current = *value;
+impl Almanac {
+    fn lowest_location(&self, seeds: IntervalSet<u32>) -> Option<u32> {
+        let mut current = seeds;
         let mut source = Category::Seed;
-        while let Some(map) = input.category_maps.get(&source) {
-            current = map.map_value(current);
+        while let Some(map) = self.category_maps.get(&source) {
+            current = map.map_set(&current)?;
             source = map.destination;
         }
 
-        lowest_value = min(lowest_value, current);
+        current.min()
     }
+}
 
-    Some(lowest_value)
+#[aoc(day5, part1)]
+fn part1(input: &Almanac) -> Option<u32> {
+    input.lowest_location(input.seeds.iter().map(|&seed| seed..=seed).collect())
 }
 
 #[aoc(day5, part2)]
 fn part2(input: &Almanac) -> Option<u32> {
-    let mut lowest_value = u32::MAX;
-    let pairs = input.seeds.chunks_exact(2);
-    for pair in pairs {
-        let start = pair[0];
-        let length = pair[1];
+    let seeds = input
+        .seeds
+        .chunks_exact(2)
+        .filter(|pair| pair[1] > 0)
+        .map(|pair| Some(pair[0]..=pair[0].checked_add(pair[1] - 1)?))
+        .collect::<Option<_>>()?;
+    input.lowest_location(seeds)
+}
 
-        let mut current = vec![(start, length)];
-        let mut source = Category::Seed;
-        while let Some(map) = input.category_maps.get(&source) {
-            current = current
-                .iter()
-                .flat_map(|&range| map.map_range(range))
-                .collect();
-            source = map.destination;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let pair_lowest = current
-            .iter()
-            .map(|&(start, _)| start)
-            .min()
-            .unwrap_or(u32::MAX);
+    #[test]
+    fn test_seed_range_bounds() {
+        let almanac = generator("seeds: 4294967290 6\n\nseed-to-location map:\n0 0 1").unwrap();
+        assert_eq!(part2(&almanac), Some(4294967290));
 
-        lowest_value = min(lowest_value, pair_lowest);
+        let almanac = generator("seeds: 4294967290 7\n\nseed-to-location map:\n0 0 1").unwrap();
+        assert_eq!(part2(&almanac), None);
     }
-
-    Some(lowest_value)
 }
@@ -1,8 +1,8 @@
 use std::collections::HashMap;
 
+use advent_common::modular::lcm;
 use anyhow::Context;
 use aoc_runner_derive::{aoc, aoc_generator};
-use num::Integer;
 
 const START: &str = "AAA";
 const END: &str = "ZZZ";
@@ -55,7 +55,7 @@ fn part1(input: &(String, Map)) -> usize {
 }
 
 #[aoc(day8, part2)]
-fn part2(input: &(String, Map)) -> Option<usize> {
+fn part2(input: &(String, Map)) -> Option<i64> {
     let (instructions, map) = input;
     let start_nodes = map
         .keys()
@@ -84,7 +84,7 @@ fn part2(input: &(String, Map)) -> Option<usize> {
                         instruction
                     })
                 })
-                .count()
+                .count() as i64
         })
-        .reduce(|acc, length| acc.lcm(&length))
+        .reduce(lcm)
 }
@@ -1,5 +1,6 @@
 use std::collections::HashSet;
 
+use advent_common::modular::crt;
 use anyhow::Result;
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
@@ -7,6 +8,17 @@ use regex::Regex;
 
 type Point = (i32, i32);
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Bounds {
+    width: i32,
+    height: i32,
+}
+
+const BATHROOM: Bounds = Bounds {
+    width: 101,
+    height: 103,
+};
+
 #[derive(Debug, Clone)]
 struct Robot {
     position: Point,
@@ -14,23 +26,23 @@ struct Robot {
 }
 
 impl Robot {
-    fn move_robot(&self, steps: i32, width: i32, height: i32) -> Point {
+    fn move_robot(&self, steps: i32, bounds: Bounds) -> Point {
         let (px, py) = self.position;
         let (vx, vy) = self.velocity;
 
-        let new_x = ((px + vx * steps) % width + width) % width;
-        let new_y = ((py + vy * steps) % height + height) % height;
+        let new_x = (px + vx * steps).rem_euclid(bounds.width);
+        let new_y = (py + vy * steps).rem_euclid(bounds.height);
 
         (new_x, new_y)
     }
 }
 
-fn calculate_safety_factor(robots: &[Robot], steps: i32, width: i32, height: i32) -> i32 {
-    let (mid_x, mid_y) = (width / 2, height / 2);
+fn calculate_safety_factor(robots: &[Robot], steps: i32, bounds: Bounds) -> i32 {
+    let (mid_x, mid_y) = (bounds.width / 2, bounds.height / 2);
 
     robots
         .iter()
-        .map(|robot| robot.move_robot(steps, width, height))
+        .map(|robot| robot.move_robot(steps, bounds))
         .filter(|&(x, y)| x != mid_x && y != mid_y)
         .map(|(x, y)| match (x < mid_x, y < mid_y) {
             (true, true) => 0,
@@ -45,23 +57,61 @@ fn calculate_safety_factor(robots: &[Robot], steps: i32, width: i32, height: i32
         .expect("product overflow")
 }
 
-fn has_dense_cluster(robots: &[Robot], steps: i32, width: i32, height: i32) -> bool {
+fn spread(values: impl Iterator<Item = i32>) -> i64 {
+    let (count, sum, squares) = values.fold((0i64, 0i64, 0i64), |(n, s, q), v| {
+        let v = i64::from(v);
+        (n + 1, s + v, q + v * v)
+    });
+    count * squares - sum * sum
+}
+
+fn tightest_step(period: i32, spread_at: impl Fn(i32) -> i64) -> i32 {
+    (0..period)
+        .min_by_key(|&steps| spread_at(steps))
+        .unwrap_or(0)
+}
+
+fn find_image(robots: &[Robot], bounds: Bounds) -> Option<i32> {
+    let x_steps = tightest_step(bounds.width, |steps| {
+        spread(robots.iter().map(|robot| robot.move_robot(steps, bounds).0))
+    });
+    let y_steps = tightest_step(bounds.height, |steps| {
+        spread(robots.iter().map(|robot| robot.move_robot(steps, bounds).1))
+    });
+
+    crt(&[
+        (x_steps.into(), bounds.width.into()),
+        (y_steps.into(), bounds.height.into()),
+    ])
+    .and_then(|(steps, _)| steps.try_into().ok())
+}
+
+fn to_pbm(robots: &[Robot], steps: i32, bounds: Bounds) -> String {
     let positions: HashSet<Point> = robots
         .iter()
-        .map(|robot| robot.move_robot(steps, width, height))
+        .map(|robot| robot.move_robot(steps, bounds))
         .collect();
 
-    (0..height).any(|y| {
-        (0..width)
-            .map(|x| positions.contains(&(x, y)))
-            .chunk_by(|&v| v)
-            .into_iter()
-            .filter(|(k, _)| *k)
-            .map(|(_, group)| group.count())
-            .max()
-            .unwrap_or(0)
-            >= 10
-    })
+    let rows = (0..bounds.height).map(|y| {
+        (0..bounds.width)
+            .map(|x| {
+                if positions.contains(&(x, y)) {
+                    '1'
+                } else {
+                    '0'
+                }
+            })
+            .join(" ")
+    });
+
+    std::iter::once("P1".to_string())
+        .chain(std::iter::once(format!(
+            "{} {}",
+            bounds.width, bounds.height
+        )))
+        .chain(rows)
+        .join("\n")
+        + "\n"
 }
 
 #[aoc_generator(day14)]
@@ -89,16 +139,18 @@ fn generator(input: &str) -> Result<Vec<Robot>> {
 
 #[aoc(day14, part1)]
 fn part1(robots: &[Robot]) -> i32 {
-    calculate_safety_factor(robots, 100, 101, 103)
+    calculate_safety_factor(robots, 100, BATHROOM)
 }
 
 #[aoc(day14, part2)]
-fn part2(robots: &[Robot]) -> i32 {
-    let (width, height) = (101, 103);
+fn part2(robots: &[Robot]) -> Result<i32> {
+    find_image(robots, BATHROOM).ok_or_else(|| anyhow::anyhow!("Periods have no common frame"))
+}
 
-    (0..=(width * height))
-        .find(|&seconds| has_dense_cluster(robots, seconds, width, height))
-        .unwrap_or(0)
+#[aoc(day14, part2, pbm)]
+fn part2_pbm(robots: &[Robot]) -> Result<String> {
+    let steps = part2(robots)?;
+    Ok(to_pbm(robots, steps, BATHROOM))
 }
 
 #[cfg(test)]
@@ -118,6 +170,11 @@ p=7,3 v=-1,2
 p=2,4 v=2,-3
 p=9,5 v=-3,-3"#;
 
+    const EXAMPLE_BOUNDS: Bounds = Bounds {
+        width: 11,
+        height: 7,
+    };
+
     #[test]
     fn test_parse_robots() {
         let robots = generator(EXAMPLE).unwrap();
@@ -135,34 +192,77 @@ p=9,5 v=-3,-3"#;
             velocity: (2, -3),
         };
 
-        assert_eq!(robot.move_robot(1, 11, 7), (4, 1));
-        assert_eq!(robot.move_robot(2, 11, 7), (6, 5));
-        assert_eq!(robot.move_robot(3, 11, 7), (8, 2));
-        assert_eq!(robot.move_robot(4, 11, 7), (10, 6));
-        assert_eq!(robot.move_robot(5, 11, 7), (1, 3));
+        assert_eq!(robot.move_robot(1, EXAMPLE_BOUNDS), (4, 1));
+        assert_eq!(robot.move_robot(2, EXAMPLE_BOUNDS), (6, 5));
+        assert_eq!(robot.move_robot(3, EXAMPLE_BOUNDS), (8, 2));
+        assert_eq!(robot.move_robot(4, EXAMPLE_BOUNDS), (10, 6));
+        assert_eq!(robot.move_robot(5, EXAMPLE_BOUNDS), (1, 3));
     }
 
     #[test]
     fn test_safety_factor() {
         let robots = generator(EXAMPLE).unwrap();
-        assert_eq!(calculate_safety_factor(&robots, 100, 11, 7), 12);
+        assert_eq!(calculate_safety_factor(&robots, 100, EXAMPLE_BOUNDS), 12);
     }
 
     #[test]
     fn test_part1_example() {
         let robots = generator(EXAMPLE).unwrap();
-        assert_eq!(calculate_safety_factor(&robots, 100, 11, 7), 12);
+        assert_eq!(calculate_safety_factor(&robots, 100, EXAMPLE_BOUNDS), 12);
+    }
+
+    #[test]
+    fn test_find_image() {
+        // All four robots meet at 2,3 after 12 steps.
+        let robots = generator(
+            "p=0,5 v=1,1
+p=3,2 v=2,3
+p=4,0 v=-1,2
+p=1,6 v=3,-2",
+        )
+        .unwrap();
+        let bounds = Bounds {
+            width: 5,
+            height: 7,
+        };
+        assert_eq!(find_image(&robots, bounds), Some(12));
+        assert_eq!(
+            robots
+                .iter()
+                .map(|robot| robot.move_robot(12, bounds))
+                .all_equal_value(),
+            Ok((2, 3))
+        );
     }
 
     #[test]
-    fn test_dense_cluster_detection() {
-        let robots: Vec<Robot> = (45..56)
-            .map(|x| Robot {
-                position: (x, 50),
+    fn test_find_image_without_common_frame() {
+        // Columns line up on odd steps and rows on even steps.
+        let robots = generator("p=0,0 v=0,0\np=3,4 v=1,1").unwrap();
+        let bounds = Bounds {
+            width: 4,
+            height: 6,
+        };
+        assert_eq!(find_image(&robots, bounds), None);
+    }
+
+    #[test]
+    fn test_pbm_export() {
+        let robots = vec![
+            Robot {
+                position: (0, 0),
+                velocity: (1, 0),
+            },
+            Robot {
+                position: (2, 1),
                 velocity: (0, 0),
-            })
-            .collect();
+            },
+        ];
+        let bounds = Bounds {
+            width: 3,
+            height: 2,
+        };
 
-        assert!(has_dense_cluster(&robots, 0, 101, 103));
+        assert_eq!(to_pbm(&robots, 1, bounds), "P1\n3 2\n0 1 0\n0 0 1\n");
     }
 }
@@ -1,210 +1,237 @@
-use std::collections::HashMap;
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    str::FromStr,
+};
 
-use anyhow::anyhow;
+use anyhow::{Context, anyhow, bail, ensure};
 use aoc_runner_derive::{aoc, aoc_generator};
+use itertools::Itertools;
 
 type Position = (i32, i32);
 
-#[derive(Debug)]
+const NUMERIC_LAYOUT: &str = "789
+456
+123
+.0A";
+
+const DIRECTIONAL_LAYOUT: &str = ".^A
+<v>";
+
+const DIRECTIONS: [(char, Position); 4] =
+    [('^', (-1, 0)), ('v', (1, 0)), ('<', (0, -1)), ('>', (0, 1))];
+
+#[derive(Debug, Clone)]
 struct Keypad {
     buttons: HashMap<char, Position>,
-    gap: Position,
+    keys: HashMap<Position, char>,
 }
 
 impl Keypad {
     fn numeric() -> Self {
-        let buttons = [
-            ('7', (0, 0)),
-            ('8', (0, 1)),
-            ('9', (0, 2)),
-            ('4', (1, 0)),
-            ('5', (1, 1)),
-            ('6', (1, 2)),
-            ('1', (2, 0)),
-            ('2', (2, 1)),
-            ('3', (2, 2)),
-            ('0', (3, 1)),
-            ('A', (3, 2)),
-        ]
-        .into_iter()
-        .collect();
-
-        Self {
-            buttons,
-            gap: (3, 0),
-        }
+        NUMERIC_LAYOUT.parse().unwrap()
     }
 
     fn directional() -> Self {
-        let buttons = [
-            ('^', (0, 1)),
-            ('A', (0, 2)),
-            ('<', (1, 0)),
-            ('v', (1, 1)),
-            ('>', (1, 2)),
-        ]
-        .into_iter()
-        .collect();
-
-        Self {
-            buttons,
-            gap: (0, 0),
-        }
+        DIRECTIONAL_LAYOUT.parse().unwrap()
     }
 
-    fn get_all_paths(&self, from: char, to: char) -> Vec<String> {
-        let start = self.buttons[&from];
-        let end = self.buttons[&to];
+    fn step(&self, from: char, direction: char) -> Option<char> {
+        let (row, col) = self.buttons[&from];
+        let (_, (dr, dc)) = DIRECTIONS.iter().find(|(key, _)| *key == direction)?;
+        self.keys.get(&(row + dr, col + dc)).copied()
+    }
+
+    fn can_steer(&self) -> bool {
+        DIRECTIONS
+            .iter()
+            .all(|(key, _)| self.buttons.contains_key(key))
+    }
+}
+
+impl FromStr for Keypad {
+    type Err = anyhow::Error;
 
-        if start == end {
-            return vec!["A".to_string()];
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let mut buttons = HashMap::new();
+        for (row, line) in s.lines().enumerate() {
+            for (col, key) in line.chars().enumerate() {
+                if key == '.' || key == ' ' {
+                    continue;
+                }
+                if buttons.insert(key, (row as i32, col as i32)).is_some() {
+                    bail!("Button {key} appears twice in keypad");
+                }
+            }
         }
+        ensure!(buttons.contains_key(&'A'), "Keypad has no A button");
 
-        let (sr, sc) = start;
-        let (er, ec) = end;
+        let keys = buttons.iter().map(|(&key, &pos)| (pos, key)).collect();
+        Ok(Self { buttons, keys })
+    }
+}
 
-        let dr = er - sr;
-        let dc = ec - sc;
+#[derive(Debug, Clone)]
+struct Chain {
+    keypads: Vec<Keypad>,
+    moves: Vec<HashMap<(char, char), (usize, String)>>,
+}
 
-        let vert_moves = match dr.signum() {
-            1 => "v".repeat(dr.unsigned_abs() as usize),
-            -1 => "^".repeat(dr.unsigned_abs() as usize),
-            _ => String::new(),
-        };
+impl Chain {
+    fn new(keypads: Vec<Keypad>) -> anyhow::Result<Self> {
+        ensure!(!keypads.is_empty(), "Chain needs at least one keypad");
+        if let Some(index) = keypads
+            .iter()
+            .skip(1)
+            .position(|keypad| !keypad.can_steer())
+        {
+            bail!("Keypad {} cannot steer a robot", index + 1);
+        }
 
-        let horiz_moves = match dc.signum() {
-            1 => ">".repeat(dc.unsigned_abs() as usize),
-            -1 => "<".repeat(dc.unsigned_abs() as usize),
-            _ => String::new(),
+        let mut chain = Self {
+            moves: vec![HashMap::new(); keypads.len() - 1],
+            keypads,
         };
+        for level in (0..chain.moves.len()).rev() {
+            chain.moves[level] = chain.plan_level(level);
+        }
+        Ok(chain)
+    }
 
-        let horiz_first_valid = self.is_path_valid(start, dc, 0, dr);
-        let vert_first_valid = self.is_path_valid(start, 0, dr, dc);
+    fn standard(robots: usize) -> Self {
+        let keypads = std::iter::once(Keypad::numeric())
+            .chain(std::iter::repeat_n(Keypad::directional(), robots + 1))
+            .collect();
+        Self::new(keypads).unwrap()
+    }
 
-        match (horiz_moves.is_empty(), vert_moves.is_empty()) {
-            (false, false) => {
-                let mut paths = Vec::new();
-                if horiz_first_valid {
-                    paths.push(format!("{horiz_moves}{vert_moves}A"));
-                }
-                if vert_first_valid {
-                    let path = format!("{vert_moves}{horiz_moves}A");
-                    if !paths.contains(&path) {
-                        paths.push(path);
-                    }
-                }
-                paths
-            }
-            (false, true) => vec![format!("{horiz_moves}A")],
-            (true, false) => vec![format!("{vert_moves}A")],
-            (true, true) => vec!["A".to_string()],
+    fn press_cost(&self, level: usize, from: char, to: char) -> Option<usize> {
+        match self.moves.get(level) {
+            Some(moves) => moves.get(&(from, to)).map(|&(cost, _)| cost),
+            None => Some(1),
         }
     }
 
-    #[allow(clippy::similar_names)]
-    fn is_path_valid(
-        &self,
-        start: Position,
-        dc_first: i32,
-        dr_first: i32,
-        second_move: i32,
-    ) -> bool {
-        let (mut row, mut col) = start;
-
-        for _ in 0..dc_first.abs() {
-            col += dc_first.signum();
-            if (row, col) == self.gap {
-                return false;
-            }
-        }
+    fn plan_level(&self, level: usize) -> HashMap<(char, char), (usize, String)> {
+        let keypad = &self.keypads[level];
+        let mut moves = HashMap::new();
 
-        for _ in 0..dr_first.abs() {
-            row += dr_first.signum();
-            if (row, col) == self.gap {
-                return false;
-            }
-        }
+        for &from in keypad.buttons.keys() {
+            let mut dist = HashMap::from([((from, 'A'), 0)]);
+            let mut previous = HashMap::new();
+            let mut heap = BinaryHeap::from([Reverse((0, from, 'A'))]);
 
-        let is_second_horizontal = dr_first != 0;
-        for _ in 0..second_move.abs() {
-            if is_second_horizontal {
-                col += second_move.signum();
-            } else {
-                row += second_move.signum();
+            while let Some(Reverse((cost, key, held))) = heap.pop() {
+                if cost > dist[&(key, held)] {
+                    continue;
+                }
+                for (direction, _) in DIRECTIONS {
+                    let Some(next) = keypad.step(key, direction) else {
+                        continue;
+                    };
+                    let Some(press) = self.press_cost(level + 1, held, direction) else {
+                        continue;
+                    };
+                    let next_cost = cost + press;
+                    if next_cost < dist.get(&(next, direction)).copied().unwrap_or(usize::MAX) {
+                        dist.insert((next, direction), next_cost);
+                        previous.insert((next, direction), (key, held));
+                        heap.push(Reverse((next_cost, next, direction)));
+                    }
+                }
             }
-            if (row, col) == self.gap {
-                return false;
+
+            for &to in keypad.buttons.keys() {
+                let Some((&(key, held), cost)) = dist
+                    .iter()
+                    .filter(|&(&(key, _), _)| key == to)
+                    .filter_map(|(state, &cost)| {
+                        Some((state, cost + self.press_cost(level + 1, state.1, 'A')?))
+                    })
+                    .min_by_key(|&(&(key, held), cost)| (cost, key, held))
+                else {
+                    continue;
+                };
+
+                let mut presses = vec!['A'];
+                let mut state = (key, held);
+                while let Some(&before) = previous.get(&state) {
+                    presses.push(state.1);
+                    state = before;
+                }
+                moves.insert((from, to), (cost, presses.into_iter().rev().collect()));
             }
         }
 
-        true
+        moves
     }
-}
 
-fn compute_min_length(
-    sequence: &str,
-    depth: usize,
-    max_depth: usize,
-    directional: &Keypad,
-    cache: &mut HashMap<(String, usize), usize>,
-) -> usize {
-    if depth == max_depth {
-        return sequence.len();
+    fn shortest_length(&self, code: &str) -> Option<usize> {
+        let Some(moves) = self.moves.first() else {
+            return Some(code.len());
+        };
+        std::iter::once('A')
+            .chain(code.chars())
+            .tuple_windows()
+            .map(|pair| moves.get(&pair).map(|&(cost, _)| cost))
+            .sum()
     }
 
-    let key = (sequence.to_string(), depth);
-    if let Some(&cached) = cache.get(&key) {
-        return cached;
+    fn shortest_sequence(&self, code: &str) -> Option<String> {
+        self.moves
+            .iter()
+            .try_fold(code.to_string(), |sequence, moves| {
+                std::iter::once('A')
+                    .chain(sequence.chars())
+                    .tuple_windows()
+                    .map(|pair| moves.get(&pair).map(|(_, presses)| presses.as_str()))
+                    .collect()
+            })
     }
 
-    let total = sequence
-        .chars()
-        .scan('A', |current, target| {
-            let paths = directional.get_all_paths(*current, target);
-            *current = target;
-            Some(paths)
-        })
-        .map(|paths| {
-            paths
-                .iter()
-                .map(|path| compute_min_length(path, depth + 1, max_depth, directional, cache))
-                .min()
-                .unwrap()
-        })
-        .sum();
-
-    cache.insert(key, total);
-    total
-}
-
-fn find_all_sequences(code: &str, numeric: &Keypad) -> Option<Vec<String>> {
-    code.chars()
-        .scan(
-            ('A', vec![String::new()]),
-            |(current, sequences), target| {
-                let paths = numeric.get_all_paths(*current, target);
-                *current = target;
+    fn replay(&self, presses: &str) -> anyhow::Result<Vec<String>> {
+        let human = self.keypads.last().unwrap();
+        if let Some(key) = presses.chars().find(|key| !human.buttons.contains_key(key)) {
+            bail!("Human pressed {key}, which is not on their keypad");
+        }
 
-                *sequences = sequences
-                    .iter()
-                    .flat_map(|seq| paths.iter().map(move |path| format!("{seq}{path}")))
-                    .collect();
+        let mut typed = vec![presses.to_string()];
+        for (level, keypad) in self.keypads.iter().enumerate().rev().skip(1) {
+            let mut arm = 'A';
+            let mut output = String::new();
+            for (press, instruction) in typed.last().unwrap().chars().enumerate() {
+                if instruction == 'A' {
+                    output.push(arm);
+                } else {
+                    arm = keypad.step(arm, instruction).with_context(|| {
+                        format!("Robot at keypad {level} leaves its keypad on press {press}")
+                    })?;
+                }
+            }
+            typed.push(output);
+        }
 
-                Some(sequences.clone())
-            },
-        )
-        .last()
+        typed.reverse();
+        Ok(typed)
+    }
 }
 
-fn find_shortest_sequence(code: &str, num_robots: usize) -> Option<usize> {
-    let numeric = Keypad::numeric();
-    let directional = Keypad::directional();
-    let mut cache = HashMap::new();
+fn numeric_part(code: &str) -> anyhow::Result<usize> {
+    Ok(code
+        .trim_end_matches(|c: char| !c.is_ascii_digit())
+        .parse()?)
+}
 
-    find_all_sequences(code, &numeric)?
+fn complexity(codes: &[String], chain: &Chain) -> anyhow::Result<usize> {
+    codes
         .iter()
-        .map(|seq| compute_min_length(seq, 0, num_robots, &directional, &mut cache))
-        .min()
+        .map(|code| {
+            let length = chain
+                .shortest_length(code)
+                .ok_or_else(|| anyhow!("No sequence found for {code}"))?;
+            Ok(length * numeric_part(code)?)
+        })
+        .sum()
 }
 
 #[aoc_generator(day21)]
@@ -214,80 +241,130 @@ fn generator(input: &str) -> Vec<String> {
 
 #[aoc(day21, part1)]
 fn part1(codes: &[String]) -> anyhow::Result<usize> {
+    complexity(codes, &Chain::standard(2))
+}
+
+#[aoc(day21, part1, sequences)]
+fn part1_sequences(codes: &[String]) -> anyhow::Result<String> {
+    let chain = Chain::standard(2);
     codes
         .iter()
         .map(|code| {
-            let length =
-                find_shortest_sequence(code, 2).ok_or_else(|| anyhow!("No sequence found"))?;
-            let numeric_part: usize = code[..code.len() - 1].parse()?;
-            Ok(length * numeric_part)
+            let sequence = chain
+                .shortest_sequence(code)
+                .ok_or_else(|| anyhow!("No sequence found for {code}"))?;
+            let typed = chain.replay(&sequence)?;
+            Ok(typed
+                .iter()
+                .enumerate()
+                .map(|(level, keys)| format!("keypad {level}: {keys}"))
+                .join("\n"))
         })
-        .sum()
+        .collect::<anyhow::Result<Vec<_>>>()
+        .map(|reports| reports.join("\n\n"))
 }
 
 #[aoc(day21, part2)]
 fn part2(codes: &[String]) -> anyhow::Result<usize> {
-    codes
-        .iter()
-        .map(|code| {
-            let length =
-                find_shortest_sequence(code, 25).ok_or_else(|| anyhow!("No sequence found"))?;
-            let numeric_part: usize = code[..code.len() - 1].parse()?;
-            Ok(length * numeric_part)
-        })
-        .sum()
+    complexity(codes, &Chain::standard(25))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const EXAMPLE: [&str; 5] = ["029A", "980A", "179A", "456A", "379A"];
+
     #[test]
-    fn test_numeric_keypad_paths() {
+    fn test_layouts() {
         let keypad = Keypad::numeric();
+        assert_eq!(keypad.buttons.len(), 11);
+        assert_eq!(keypad.buttons[&'0'], (3, 1));
+        assert_eq!(keypad.step('1', 'v'), None);
+        assert_eq!(keypad.step('0', '<'), None);
+        assert_eq!(keypad.step('0', '>'), Some('A'));
 
-        let paths = keypad.get_all_paths('A', '0');
-        assert!(paths.contains(&"<A".to_string()));
+        let keypad = Keypad::directional();
+        assert!(keypad.can_steer());
+        assert_eq!(keypad.step('<', '^'), None);
 
-        let paths = keypad.get_all_paths('0', '2');
-        assert!(paths.contains(&"^A".to_string()));
+        assert!("12\n34".parse::<Keypad>().is_err());
+        assert!("AA".parse::<Keypad>().is_err());
+    }
 
-        let paths = keypad.get_all_paths('2', '9');
-        assert!(
-            paths
-                .iter()
-                .any(|p| p == ">^^A" || p == "^>^A" || p == "^^>A")
-        );
+    #[test]
+    fn test_numeric_keypad_moves() {
+        let chain = Chain::new(vec![Keypad::numeric(), Keypad::directional()]).unwrap();
+        assert_eq!(chain.moves[0][&('A', '0')], (2, "<A".to_string()));
+        assert_eq!(chain.moves[0][&('0', '2')], (2, "^A".to_string()));
+        assert_eq!(chain.moves[0][&('2', '9')].0, 4);
     }
 
     #[test]
-    fn test_directional_keypad_paths() {
-        let keypad = Keypad::directional();
+    fn test_find_shortest_sequence() {
+        let chain = Chain::standard(2);
+        assert_eq!(chain.shortest_length("029A"), Some(68));
+
+        let sequence = chain.shortest_sequence("029A").unwrap();
+        assert_eq!(sequence.len(), 68);
+        let typed = chain.replay(&sequence).unwrap();
+        assert_eq!(typed.len(), 4);
+        assert_eq!(typed[0], "029A");
+        assert_eq!(typed[3], sequence);
+    }
 
-        let paths = keypad.get_all_paths('A', '<');
-        assert!(paths.iter().any(|p| p == "v<<A" || p == "<v<A"));
+    #[test]
+    fn test_replay_example() {
+        let chain = Chain::standard(2);
+        let typed = chain
+            .replay("<vA<AA>>^AvAA<^A>A<v<A>>^AvA^A<vA>^A<v<A>^A>AAvA^A<v<A>A>^AAAvA<^A>A")
+            .unwrap();
+        assert_eq!(
+            typed,
+            [
+                "029A",
+                "<A^A>^^AvvvA",
+                "v<<A>>^A<A>AvA<^AA>A<vAAA>^A",
+                "<vA<AA>>^AvAA<^A>A<v<A>>^AvA^A<vA>^A<v<A>^A>AAvA^A<v<A>A>^AAAvA<^A>A",
+            ]
+        );
 
-        let paths = keypad.get_all_paths('<', 'A');
-        assert!(paths.iter().any(|p| p == ">>^A" || p == ">^>A"));
+        assert!(chain.replay("<A").is_err());
+        assert!(chain.replay("x").is_err());
     }
 
     #[test]
-    fn test_find_shortest_sequence() {
-        let length = find_shortest_sequence("029A", 2);
-        assert_eq!(length, Some(68));
+    fn test_part1_example() {
+        let codes = EXAMPLE.map(str::to_string);
+        assert_eq!(part1(&codes).unwrap(), 126384);
+
+        let report = part1_sequences(&codes).unwrap();
+        assert!(report.starts_with("keypad 0: 029A\n"));
     }
 
     #[test]
-    fn test_part1_example() {
-        let codes = vec![
-            "029A".to_string(),
-            "980A".to_string(),
-            "179A".to_string(),
-            "456A".to_string(),
-            "379A".to_string(),
-        ];
-
-        let result = part1(&codes).unwrap();
-        assert_eq!(result, 126384);
+    fn test_heterogeneous_chain() {
+        let wide: Keypad = "^A.\n<v>".parse().unwrap();
+        let chain = Chain::new(vec![
+            Keypad::numeric(),
+            Keypad::directional(),
+            wide,
+            Keypad::directional(),
+        ])
+        .unwrap();
+
+        let sequence = chain.shortest_sequence("179A").unwrap();
+        assert_eq!(chain.shortest_length("179A"), Some(sequence.len()));
+        assert_eq!(chain.replay(&sequence).unwrap()[0], "179A");
+
+        let detour: Keypad = "1.3\n2A4".parse().unwrap();
+        let chain = Chain::new(vec![detour, Keypad::directional()]).unwrap();
+        assert_eq!(chain.shortest_sequence("1").unwrap(), "<^A");
+        assert_eq!(chain.shortest_length("3"), Some(3));
+
+        let only_human = Chain::new(vec![Keypad::numeric()]).unwrap();
+        assert_eq!(only_human.shortest_length("029A"), Some(4));
+
+        assert!(Chain::new(vec![Keypad::numeric(), Keypad::numeric()]).is_err());
     }
 }
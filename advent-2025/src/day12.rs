@@ -1,6 +1,11 @@
-use std::{collections::BTreeSet, str::FromStr};
+use std::{
+    collections::BTreeSet,
+    fmt,
+    str::FromStr,
+    time::{Duration, Instant},
+};
 
-use anyhow::anyhow;
+use anyhow::{anyhow, bail};
 use aoc_runner_derive::{aoc, aoc_generator};
 use winnow::{
     ascii::{dec_uint, line_ending, space1},
@@ -13,6 +18,8 @@ type Point = (i32, i32);
 type Cells = BTreeSet<Point>;
 type Placement = Vec<usize>;
 
+const TIME_BUDGET: Duration = Duration::from_secs(10);
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 struct Shape {
     cells: Cells,
@@ -96,43 +103,294 @@ impl FromIterator<Point> for Shape {
     }
 }
 
-fn backtrack_search(shape_placements: &[(usize, Vec<Placement>)], board: &mut [bool]) -> bool {
-    fn place_copies(
-        remaining: usize,
-        start_idx: usize,
-        placements: &[Placement],
-        rest: &[(usize, Vec<Placement>)],
-        board: &mut [bool],
-    ) -> bool {
+#[derive(Debug, Clone)]
+struct Layout {
+    width: usize,
+    height: usize,
+    pieces: Vec<(usize, Placement)>,
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut grid = vec!['.'; self.width * self.height];
+        let labels = ('A'..='Z').chain('a'..='z').cycle();
+        for ((_, cells), label) in self.pieces.iter().zip(labels) {
+            for &cell in cells {
+                grid[cell] = label;
+            }
+        }
+        for row in grid.chunks(self.width) {
+            writeln!(f, "{}", row.iter().collect::<String>())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Packing {
+    Fits(Layout),
+    Impossible,
+    Unknown,
+}
+
+struct Links {
+    left: Vec<usize>,
+    right: Vec<usize>,
+    up: Vec<usize>,
+    down: Vec<usize>,
+    column: Vec<usize>,
+    row: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl Links {
+    const ROOT: usize = 0;
+
+    fn new(pieces: usize, cells: usize) -> Self {
+        let headers = 1 + pieces + cells;
+        let mut links = Self {
+            left: (0..headers).collect(),
+            right: (0..headers).collect(),
+            up: (0..headers).collect(),
+            down: (0..headers).collect(),
+            column: (0..headers).collect(),
+            row: vec![usize::MAX; headers],
+            size: vec![0; headers],
+        };
+        let list = std::iter::once(Self::ROOT)
+            .chain(pieces + 1..headers)
+            .collect::<Vec<_>>();
+        for (i, &header) in list.iter().enumerate() {
+            links.left[header] = list[(i + list.len() - 1) % list.len()];
+            links.right[header] = list[(i + 1) % list.len()];
+        }
+        links
+    }
+
+    fn add_row(&mut self, row: usize, columns: &[usize]) {
+        let first = self.column.len();
+        for (i, &column) in columns.iter().enumerate() {
+            let node = first + i;
+            self.left.push(if i == 0 {
+                first + columns.len() - 1
+            } else {
+                node - 1
+            });
+            self.right.push(if i + 1 == columns.len() {
+                first
+            } else {
+                node + 1
+            });
+            self.up.push(self.up[column]);
+            self.down.push(column);
+            self.column.push(column);
+            self.row.push(row);
+
+            let last = self.up[column];
+            self.down[last] = node;
+            self.up[column] = node;
+            self.size[column] += 1;
+        }
+    }
+
+    fn cover(&mut self, column: usize) {
+        let (left, right) = (self.left[column], self.right[column]);
+        self.right[left] = right;
+        self.left[right] = left;
+
+        let mut i = self.down[column];
+        while i != column {
+            let mut j = self.right[i];
+            while j != i {
+                let (up, down) = (self.up[j], self.down[j]);
+                self.down[up] = down;
+                self.up[down] = up;
+                self.size[self.column[j]] -= 1;
+                j = self.right[j];
+            }
+            i = self.down[i];
+        }
+    }
+
+    fn uncover(&mut self, column: usize) {
+        let mut i = self.up[column];
+        while i != column {
+            let mut j = self.left[i];
+            while j != i {
+                let (up, down) = (self.up[j], self.down[j]);
+                self.down[up] = j;
+                self.up[down] = j;
+                self.size[self.column[j]] += 1;
+                j = self.left[j];
+            }
+            i = self.up[i];
+        }
+
+        let (left, right) = (self.left[column], self.right[column]);
+        self.right[left] = column;
+        self.left[right] = column;
+    }
+}
+
+struct Packer {
+    links: Links,
+    rows: Vec<(usize, Placement)>,
+    needed: Vec<usize>,
+    areas: Vec<usize>,
+    last_row: Vec<usize>,
+    free_cells: usize,
+    chosen: Vec<usize>,
+    solution: Vec<usize>,
+    deadline: Instant,
+    visited: usize,
+}
+
+impl Packer {
+    fn remaining_area(&self) -> usize {
+        self.needed
+            .iter()
+            .zip(&self.areas)
+            .map(|(&needed, &area)| needed * area)
+            .sum()
+    }
+
+    fn is_cell(&self, column: usize) -> bool {
+        column > self.needed.len()
+    }
+
+    fn place(&mut self, node: usize) {
+        let row = self.links.row[node];
+        let shape = self.rows[row].0;
+        self.needed[shape] -= 1;
+        self.free_cells -= self.areas[shape];
+        self.chosen.push(row);
+
+        let mut j = self.links.right[node];
+        while j != node {
+            if self.is_cell(self.links.column[j]) {
+                self.links.cover(self.links.column[j]);
+            }
+            j = self.links.right[j];
+        }
+        if self.needed[shape] == 0 {
+            self.links.cover(shape + 1);
+        }
+    }
+
+    fn unplace(&mut self, node: usize) {
+        let row = self.links.row[node];
+        let shape = self.rows[row].0;
+        if self.needed[shape] == 0 {
+            self.links.uncover(shape + 1);
+        }
+
+        let mut j = self.links.left[node];
+        while j != node {
+            if self.is_cell(self.links.column[j]) {
+                self.links.uncover(self.links.column[j]);
+            }
+            j = self.links.left[j];
+        }
+
+        self.chosen.pop();
+        self.free_cells += self.areas[shape];
+        self.needed[shape] += 1;
+    }
+
+    fn search(&mut self) -> Option<bool> {
+        if self.visited.is_multiple_of(4096) && Instant::now() >= self.deadline {
+            return None;
+        }
+        self.visited += 1;
+
+        let remaining = self.remaining_area();
         if remaining == 0 {
-            return backtrack_search(rest, board);
+            self.solution.clone_from(&self.chosen);
+            return Some(true);
+        }
+        if remaining > self.free_cells {
+            return Some(false);
+        }
+        let slack = usize::from(remaining < self.free_cells);
+
+        let mut column = self.links.right[Links::ROOT];
+        let mut cell = column;
+        let mut coverable = 0;
+        while column != Links::ROOT {
+            if self.links.size[column] > 0 {
+                coverable += 1;
+            }
+            if self.links.size[column] < self.links.size[cell] {
+                cell = column;
+            }
+            column = self.links.right[column];
+        }
+        if remaining > coverable {
+            return Some(false);
         }
 
-        for i in start_idx..placements.len() {
-            let cells = &placements[i];
-            if cells.iter().all(|&idx| !board[idx]) {
-                for &idx in cells {
-                    board[idx] = true;
-                }
+        let mut shape_branch = None;
+        for (shape, &needed) in self.needed.iter().enumerate() {
+            let size = self.links.size[shape + 1];
+            if needed > 0 && size < needed {
+                return Some(false);
+            }
+            if needed > 0
+                && size < self.links.size[cell] + slack
+                && shape_branch.is_none_or(|best: usize| size < self.links.size[best + 1])
+            {
+                shape_branch = Some(shape);
+            }
+        }
 
-                if place_copies(remaining - 1, i + 1, placements, rest, board) {
-                    return true;
+        if let Some(shape) = shape_branch {
+            let header = shape + 1;
+            let mut node = self.links.down[header];
+            while node != header {
+                let row = self.links.row[node];
+                if self.last_row[shape] != usize::MAX && row <= self.last_row[shape] {
+                    node = self.links.down[node];
+                    continue;
                 }
 
-                for &idx in cells {
-                    board[idx] = false;
+                let previous_last = std::mem::replace(&mut self.last_row[shape], row);
+                self.place(node);
+                let result = self.search();
+                self.unplace(node);
+                self.last_row[shape] = previous_last;
+
+                if result != Some(false) {
+                    return result;
                 }
+                node = self.links.down[node];
             }
+            return Some(false);
         }
 
-        false
-    }
+        self.links.cover(cell);
 
-    let Some(((qty, placements), rest)) = shape_placements.split_first() else {
-        return true;
-    };
+        let mut node = self.links.down[cell];
+        while node != cell {
+            self.place(node);
+            let result = self.search();
+            self.unplace(node);
 
-    place_copies(*qty, 0, placements, rest, board)
+            if result != Some(false) {
+                self.links.uncover(cell);
+                return result;
+            }
+            node = self.links.down[node];
+        }
+
+        let mut result = Some(false);
+        if slack > 0 {
+            self.free_cells -= 1;
+            result = self.search();
+            self.free_cells += 1;
+        }
+        self.links.uncover(cell);
+        result
+    }
 }
 
 struct Region {
@@ -142,7 +400,7 @@ struct Region {
 }
 
 impl Region {
-    fn can_fit(&self, shapes: &[Shape]) -> bool {
+    fn pack(&self, shapes: &[Shape], budget: Duration) -> Packing {
         let grid_size = self.width * self.height;
         let total_cells_needed = self
             .quantities
@@ -152,37 +410,99 @@ impl Region {
             .sum::<usize>();
 
         if total_cells_needed > grid_size {
-            return false;
+            return Packing::Impossible;
         }
 
-        // return true - YOLO
-        self.can_fit_packing(shapes)
+        if let Some(layout) = self.pack_in_boxes(shapes) {
+            return Packing::Fits(layout);
+        }
+
+        self.pack_exactly(shapes, budget)
     }
 
-    fn can_fit_packing(&self, shapes: &[Shape]) -> bool {
-        let shape_placements = self
+    fn pack_in_boxes(&self, shapes: &[Shape]) -> Option<Layout> {
+        let extent = |axis: fn(&Point) -> i32| {
+            shapes
+                .iter()
+                .flat_map(|shape| shape.cells.iter().map(axis))
+                .max()
+                .map_or(1, |max| max as usize + 1)
+        };
+        let (box_width, box_height) = (extent(|p| p.0), extent(|p| p.1));
+        let columns = self.width / box_width;
+        let boxes = columns * (self.height / box_height);
+        if self.quantities.iter().sum::<usize>() > boxes {
+            return None;
+        }
+
+        let pieces = self
             .quantities
             .iter()
-            .zip(shapes.iter())
-            .filter(|&(&quantity, _)| quantity > 0)
-            .try_fold(vec![], |mut acc, (&qty, shape)| {
-                let placements = shape.placements(self.width, self.height);
-                if placements.is_empty() {
-                    return None;
-                }
+            .enumerate()
+            .flat_map(|(shape, &qty)| std::iter::repeat_n(shape, qty))
+            .enumerate()
+            .map(|(slot, shape)| {
+                let (x, y) = (slot % columns * box_width, slot / columns * box_height);
+                let cells = shapes[shape]
+                    .cells
+                    .iter()
+                    .map(|&(dx, dy)| (y + dy as usize) * self.width + x + dx as usize)
+                    .collect();
+                (shape, cells)
+            })
+            .collect();
 
-                acc.push((qty, placements));
-                Some(acc)
-            });
+        Some(Layout {
+            width: self.width,
+            height: self.height,
+            pieces,
+        })
+    }
 
-        let Some(mut shape_placements) = shape_placements else {
-            return false;
-        };
+    fn pack_exactly(&self, shapes: &[Shape], budget: Duration) -> Packing {
+        let grid_size = self.width * self.height;
+        let mut links = Links::new(shapes.len(), grid_size);
+        let mut rows = vec![];
+        for (shape, (&qty, cells)) in self.quantities.iter().zip(shapes).enumerate() {
+            if qty == 0 {
+                links.cover(shape + 1);
+                continue;
+            }
+            for placement in cells.placements(self.width, self.height) {
+                let columns = std::iter::once(shape + 1)
+                    .chain(placement.iter().map(|&cell| 1 + shapes.len() + cell))
+                    .collect::<Vec<_>>();
+                links.add_row(rows.len(), &columns);
+                rows.push((shape, placement));
+            }
+        }
 
-        shape_placements.sort_by_key(|(quantity, placements)| placements.len() / quantity);
+        let mut packer = Packer {
+            links,
+            rows,
+            needed: self.quantities.clone(),
+            areas: shapes.iter().map(|shape| shape.cells.len()).collect(),
+            last_row: vec![usize::MAX; shapes.len()],
+            free_cells: grid_size,
+            chosen: vec![],
+            solution: vec![],
+            deadline: Instant::now() + budget,
+            visited: 0,
+        };
 
-        let mut board = vec![false; self.width * self.height];
-        backtrack_search(&shape_placements, &mut board)
+        match packer.search() {
+            Some(true) => Packing::Fits(Layout {
+                width: self.width,
+                height: self.height,
+                pieces: packer
+                    .solution
+                    .iter()
+                    .map(|&row| packer.rows[row].clone())
+                    .collect(),
+            }),
+            Some(false) => Packing::Impossible,
+            None => Packing::Unknown,
+        }
     }
 }
 
@@ -251,16 +571,42 @@ fn generator(input: &str) -> anyhow::Result<Input> {
 }
 
 #[aoc(day12, part1)]
-fn part1(input: &Input) -> usize {
+fn part1(input: &Input) -> anyhow::Result<usize> {
     input
         .regions
         .iter()
-        .filter(|region| region.can_fit(&input.shapes))
-        .count()
+        .enumerate()
+        .try_fold(0, |count, (index, region)| {
+            match region.pack(&input.shapes, TIME_BUDGET) {
+                Packing::Fits(_) => Ok(count + 1),
+                Packing::Impossible => Ok(count),
+                Packing::Unknown => bail!("region {index} is still unknown after {TIME_BUDGET:?}"),
+            }
+        })
+}
+
+#[aoc(day12, part1, layouts)]
+fn part1_layouts(input: &Input) -> String {
+    input
+        .regions
+        .iter()
+        .enumerate()
+        .map(|(index, region)| {
+            let heading = format!("{index}: {}x{}", region.width, region.height);
+            match region.pack(&input.shapes, TIME_BUDGET) {
+                Packing::Fits(layout) => format!("{heading}\n{layout}"),
+                Packing::Impossible => format!("{heading} impossible\n"),
+                Packing::Unknown => format!("{heading} unknown\n"),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 #[cfg(test)]
 mod tests {
+    use itertools::Itertools;
+
     use super::*;
 
     const EXAMPLE: &str = r"0:
@@ -353,18 +699,89 @@ mod tests {
         assert_eq!(line.variants().len(), 2);
     }
 
+    fn assert_valid(layout: &Layout, region: &Region, shapes: &[Shape]) {
+        let placements = shapes
+            .iter()
+            .map(|shape| shape.placements(region.width, region.height))
+            .collect::<Vec<_>>();
+        let mut used = vec![false; region.width * region.height];
+        for (shape, cells) in &layout.pieces {
+            assert!(placements[*shape].contains(cells));
+            for &cell in cells {
+                assert!(!used[cell]);
+                used[cell] = true;
+            }
+        }
+
+        let counts = layout.pieces.iter().map(|(shape, _)| *shape).counts();
+        for (shape, &qty) in region.quantities.iter().enumerate() {
+            assert_eq!(counts.get(&shape).copied().unwrap_or(0), qty);
+        }
+    }
+
     #[test]
     fn test_can_fit_example_regions() {
         let input = generator(EXAMPLE).unwrap();
 
-        assert!(input.regions[0].can_fit(&input.shapes));
-        assert!(input.regions[1].can_fit(&input.shapes));
-        assert!(!input.regions[2].can_fit(&input.shapes));
+        for region in &input.regions[..2] {
+            let Packing::Fits(layout) = region.pack(&input.shapes, TIME_BUDGET) else {
+                panic!("region should fit");
+            };
+            assert_valid(&layout, region, &input.shapes);
+        }
+        assert!(matches!(
+            input.regions[2].pack(&input.shapes, TIME_BUDGET),
+            Packing::Impossible
+        ));
+    }
+
+    #[test]
+    fn test_render_layout() {
+        let input = generator(EXAMPLE).unwrap();
+        let Packing::Fits(layout) = input.regions[0].pack(&input.shapes, TIME_BUDGET) else {
+            panic!("region should fit");
+        };
+
+        let rendered = layout.to_string();
+        assert_eq!(rendered.lines().count(), 4);
+        assert_eq!(rendered.chars().filter(|&c| c == 'A').count(), 7);
+        assert_eq!(rendered.chars().filter(|&c| c == 'B').count(), 7);
+    }
+
+    #[test]
+    fn test_box_shortcut() {
+        let input = generator(EXAMPLE).unwrap();
+        let region = Region {
+            width: 30,
+            height: 30,
+            quantities: vec![20, 20, 20, 20, 10, 10],
+        };
+
+        let Some(layout) = region.pack_in_boxes(&input.shapes) else {
+            panic!("boxes should fit");
+        };
+        assert_valid(&layout, &region, &input.shapes);
+    }
+
+    #[test]
+    fn test_time_budget() {
+        let input = generator(EXAMPLE).unwrap();
+        let region = Region {
+            width: 13,
+            height: 13,
+            quantities: vec![4, 4, 4, 4, 4, 4],
+        };
+
+        assert!(matches!(
+            region.pack(&input.shapes, Duration::ZERO),
+            Packing::Unknown
+        ));
     }
 
     #[test]
     fn test_part1() {
         let input = generator(EXAMPLE).unwrap();
-        assert_eq!(part1(&input), 2);
+        assert_eq!(part1(&input).unwrap(), 2);
+        assert!(part1_layouts(&input).contains("2: 12x5 impossible"));
     }
 }
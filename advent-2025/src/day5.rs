@@ -1,10 +1,9 @@
-use std::ops::RangeInclusive;
-
+use advent_common::interval::IntervalSet;
 use anyhow::Context;
 use aoc_runner_derive::{aoc, aoc_generator};
 
 struct Input {
-    ranges: Vec<RangeInclusive<u64>>,
+    fresh: IntervalSet<u64>,
     ids: Vec<u64>,
 }
 
@@ -12,21 +11,21 @@ struct Input {
 fn generator(input: &str) -> anyhow::Result<Input> {
     let (ranges_str, ids_str) = input.split_once("\n\n").context("expected blank line")?;
 
-    let ranges = ranges_str
+    let fresh = ranges_str
         .lines()
         .map(|line| {
             let (start, end) = line.split_once('-').unwrap();
             let range = start.parse::<u64>()?..=end.parse()?;
             Ok(range)
         })
-        .collect::<anyhow::Result<Vec<_>>>()?;
+        .collect::<anyhow::Result<_>>()?;
 
     let ids = ids_str
         .lines()
         .map(str::parse)
         .collect::<Result<Vec<u64>, _>>()?;
 
-    Ok(Input { ranges, ids })
+    Ok(Input { fresh, ids })
 }
 
 #[aoc(day5, part1)]
@@ -34,27 +33,11 @@ fn part1(input: &Input) -> usize {
     input
         .ids
         .iter()
-        .filter(|id| input.ranges.iter().any(|range| range.contains(id)))
+        .filter(|&&id| input.fresh.contains(id))
         .count()
 }
 
 #[aoc(day5, part2)]
-fn part2(input: &Input) -> u64 {
-    let mut ranges = input.ranges.clone();
-    ranges.sort_by_key(|r| *r.start());
-
-    ranges
-        .into_iter()
-        .fold(Vec::<RangeInclusive<u64>>::new(), |mut merged, range| {
-            match merged.last_mut() {
-                Some(last) if *last.end() >= *range.start() => {
-                    *last = *last.start()..=(*last.end()).max(*range.end());
-                }
-                _ => merged.push(range),
-            }
-            merged
-        })
-        .iter()
-        .map(|r| r.end() - r.start() + 1)
-        .sum()
+fn part2(input: &Input) -> u128 {
+    input.fresh.len()
 }
@@ -1,5 +1,6 @@
-use std::{collections::HashMap, ops::RangeInclusive};
+use std::collections::HashMap;
 
+use advent_common::interval::IntervalSet;
 use anyhow::Context;
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
@@ -17,7 +18,7 @@ impl Point {
 
 struct Polygon {
     scanline_ys: Vec<u32>,
-    interval_cache: HashMap<u32, Vec<RangeInclusive<u32>>>,
+    interval_cache: HashMap<u32, IntervalSet<u32>>,
 }
 
 impl Polygon {
@@ -52,7 +53,7 @@ impl Polygon {
         }
     }
 
-    fn compute_intervals_at_y(edges: &[(Point, Point)], y: u32) -> Vec<RangeInclusive<u32>> {
+    fn compute_intervals_at_y(edges: &[(Point, Point)], y: u32) -> IntervalSet<u32> {
         let crossings = edges
             .iter()
             .filter_map(|&(Point(ax, ay), Point(bx, by))| {
@@ -75,19 +76,7 @@ impl Polygon {
             Some(x_min..=x_max)
         });
 
-        raycast_intervals
-            .chain(horizontal_intervals)
-            .sorted_unstable_by_key(|r| *r.start())
-            .fold(vec![], |mut merged, range| {
-                match merged.last_mut() {
-                    Some(last) if *range.start() <= *last.end() + 1 => {
-                        *last = *last.start()..=(*last.end()).max(*range.end());
-                    }
-                    _ => merged.push(range),
-                }
-
-                merged
-            })
+        raycast_intervals.chain(horizontal_intervals).collect()
     }
 
     fn scanline_ys_in_range(&self, y1: u32, y2: u32) -> impl Iterator<Item = u32> + '_ {
@@ -103,9 +92,9 @@ impl Polygon {
         let (y1, y2) = if ay <= by { (ay, by) } else { (by, ay) };
 
         self.scanline_ys_in_range(y1, y2).all(|y| {
-            self.interval_cache.get(&y).is_some_and(|intervals| {
-                intervals.iter().any(|r| r.contains(&x1) && r.contains(&x2))
-            })
+            self.interval_cache
+                .get(&y)
+                .is_some_and(|intervals| intervals.contains_range(x1..=x2))
         })
     }
 }
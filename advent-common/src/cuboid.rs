@@ -0,0 +1,288 @@
+pub type Point = [i64; 3];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Cuboid {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl Cuboid {
+    pub fn new(a: Point, b: Point) -> Self {
+        Cuboid {
+            min: std::array::from_fn(|axis| a[axis].min(b[axis])),
+            max: std::array::from_fn(|axis| a[axis].max(b[axis])),
+        }
+    }
+
+    pub fn point(point: Point) -> Self {
+        Cuboid {
+            min: point,
+            max: point,
+        }
+    }
+
+    fn extent(&self, axis: usize) -> u64 {
+        (self.max[axis] - self.min[axis] + 1) as u64
+    }
+
+    pub fn volume(&self) -> u64 {
+        (0..3).map(|axis| self.extent(axis)).product()
+    }
+
+    pub fn surface_area(&self) -> u64 {
+        let [x, y, z] = [0, 1, 2].map(|axis| self.extent(axis));
+        2 * (x * y + y * z + z * x)
+    }
+
+    pub fn contains(&self, point: Point) -> bool {
+        (0..3).all(|axis| (self.min[axis]..=self.max[axis]).contains(&point[axis]))
+    }
+
+    pub fn intersects(&self, other: &Cuboid) -> bool {
+        (0..3).all(|axis| self.min[axis] <= other.max[axis] && other.min[axis] <= self.max[axis])
+    }
+
+    pub fn intersection(&self, other: &Cuboid) -> Option<Cuboid> {
+        self.intersects(other).then(|| Cuboid {
+            min: std::array::from_fn(|axis| self.min[axis].max(other.min[axis])),
+            max: std::array::from_fn(|axis| self.max[axis].min(other.max[axis])),
+        })
+    }
+
+    pub fn translate(&self, offset: Point) -> Cuboid {
+        Cuboid {
+            min: std::array::from_fn(|axis| self.min[axis] + offset[axis]),
+            max: std::array::from_fn(|axis| self.max[axis] + offset[axis]),
+        }
+    }
+
+    fn subtract(&self, other: &Cuboid) -> Vec<Cuboid> {
+        let Some(overlap) = self.intersection(other) else {
+            return vec![*self];
+        };
+
+        let mut pieces = vec![];
+        let mut rest = *self;
+        for axis in 0..3 {
+            if rest.min[axis] < overlap.min[axis] {
+                let mut below = rest;
+                below.max[axis] = overlap.min[axis] - 1;
+                pieces.push(below);
+                rest.min[axis] = overlap.min[axis];
+            }
+            if overlap.max[axis] < rest.max[axis] {
+                let mut above = rest;
+                above.min[axis] = overlap.max[axis] + 1;
+                pieces.push(above);
+                rest.max[axis] = overlap.max[axis];
+            }
+        }
+        pieces
+    }
+
+    fn contact_area(&self, other: &Cuboid) -> u64 {
+        let overlap = |axis: usize| {
+            (self.max[axis].min(other.max[axis]) - self.min[axis].max(other.min[axis]) + 1).max(0)
+                as u64
+        };
+        (0..3)
+            .filter(|&axis| {
+                self.max[axis] + 1 == other.min[axis] || other.max[axis] + 1 == self.min[axis]
+            })
+            .map(|axis| {
+                (0..3)
+                    .filter(|&other_axis| other_axis != axis)
+                    .map(overlap)
+                    .product::<u64>()
+            })
+            .sum()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CuboidSet {
+    cuboids: Vec<Cuboid>,
+}
+
+impl CuboidSet {
+    pub fn new() -> Self {
+        CuboidSet { cuboids: vec![] }
+    }
+
+    pub fn cuboids(&self) -> &[Cuboid] {
+        &self.cuboids
+    }
+
+    pub fn volume(&self) -> u64 {
+        self.cuboids.iter().map(Cuboid::volume).sum()
+    }
+
+    pub fn contains(&self, point: Point) -> bool {
+        self.cuboids.iter().any(|cuboid| cuboid.contains(point))
+    }
+
+    pub fn intersects(&self, cuboid: &Cuboid) -> bool {
+        self.cuboids
+            .iter()
+            .any(|existing| existing.intersects(cuboid))
+    }
+
+    pub fn insert(&mut self, cuboid: Cuboid) {
+        self.remove(&cuboid);
+        self.cuboids.push(cuboid);
+    }
+
+    pub fn remove(&mut self, cuboid: &Cuboid) {
+        if !self.intersects(cuboid) {
+            return;
+        }
+        self.cuboids = self
+            .cuboids
+            .iter()
+            .flat_map(|existing| existing.subtract(cuboid))
+            .collect();
+    }
+
+    pub fn union(&self, other: &CuboidSet) -> CuboidSet {
+        let mut union = self.clone();
+        for &cuboid in &other.cuboids {
+            union.insert(cuboid);
+        }
+        union
+    }
+
+    pub fn difference(&self, other: &CuboidSet) -> CuboidSet {
+        let mut difference = self.clone();
+        for cuboid in &other.cuboids {
+            difference.remove(cuboid);
+        }
+        difference
+    }
+
+    pub fn surface_area(&self) -> u64 {
+        let faces = self.cuboids.iter().map(Cuboid::surface_area).sum::<u64>();
+        let contacts = self
+            .cuboids
+            .iter()
+            .enumerate()
+            .flat_map(|(i, a)| self.cuboids[i + 1..].iter().map(|b| a.contact_area(b)))
+            .sum::<u64>();
+        faces - 2 * contacts
+    }
+}
+
+impl FromIterator<Cuboid> for CuboidSet {
+    fn from_iter<I: IntoIterator<Item = Cuboid>>(iter: I) -> Self {
+        let mut set = CuboidSet::new();
+        for cuboid in iter {
+            set.insert(cuboid);
+        }
+        set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    const NEIGHBORS: [Point; 6] = [
+        [1, 0, 0],
+        [-1, 0, 0],
+        [0, 1, 0],
+        [0, -1, 0],
+        [0, 0, 1],
+        [0, 0, -1],
+    ];
+
+    fn points(cuboid: &Cuboid) -> impl Iterator<Item = Point> + '_ {
+        (cuboid.min[0]..=cuboid.max[0]).flat_map(move |x| {
+            (cuboid.min[1]..=cuboid.max[1])
+                .flat_map(move |y| (cuboid.min[2]..=cuboid.max[2]).map(move |z| [x, y, z]))
+        })
+    }
+
+    fn voxels(set: &CuboidSet) -> HashSet<Point> {
+        set.cuboids().iter().flat_map(points).collect()
+    }
+
+    fn surface(voxels: &HashSet<Point>) -> u64 {
+        voxels
+            .iter()
+            .flat_map(|&[x, y, z]| NEIGHBORS.map(|[dx, dy, dz]| [x + dx, y + dy, z + dz]))
+            .filter(|neighbor| !voxels.contains(neighbor))
+            .count() as u64
+    }
+
+    fn is_disjoint(set: &CuboidSet) -> bool {
+        let cuboids = set.cuboids();
+        (0..cuboids.len()).all(|i| cuboids[i + 1..].iter().all(|b| !cuboids[i].intersects(b)))
+    }
+
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self, range: i64) -> i64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % range as u64) as i64
+        }
+
+        fn cuboid(&mut self) -> Cuboid {
+            let a = [(); 3].map(|_| self.next(8) - 2);
+            let b = a.map(|value| value + self.next(4));
+            Cuboid::new(b, a)
+        }
+
+        fn set(&mut self) -> CuboidSet {
+            let count = self.next(5);
+            (0..count).map(|_| self.cuboid()).collect()
+        }
+    }
+
+    #[test]
+    fn test_matches_voxels() {
+        let mut rng = Rng(0x9e3779b97f4a7c15);
+        for _ in 0..500 {
+            let (a, b) = (rng.set(), rng.set());
+            let (a_voxels, b_voxels) = (voxels(&a), voxels(&b));
+            let point = [(); 3].map(|_| rng.next(12) - 3);
+
+            let union = a.union(&b);
+            let difference = a.difference(&b);
+            assert!(is_disjoint(&a) && is_disjoint(&union) && is_disjoint(&difference));
+            assert_eq!(voxels(&union), &a_voxels | &b_voxels);
+            assert_eq!(voxels(&difference), &a_voxels - &b_voxels);
+            assert_eq!(a.volume(), a_voxels.len() as u64);
+            assert_eq!(a.surface_area(), surface(&a_voxels));
+            assert_eq!(union.surface_area(), surface(&(&a_voxels | &b_voxels)));
+            assert_eq!(a.contains(point), a_voxels.contains(&point));
+
+            let cuboid = rng.cuboid();
+            assert_eq!(
+                a.intersects(&cuboid),
+                points(&cuboid).any(|point| a_voxels.contains(&point))
+            );
+        }
+    }
+
+    #[test]
+    fn test_cuboid() {
+        let cuboid = Cuboid::new([3, 1, 2], [1, 4, 2]);
+        assert_eq!(cuboid.min, [1, 1, 2]);
+        assert_eq!(cuboid.max, [3, 4, 2]);
+        assert_eq!(cuboid.volume(), 12);
+        assert_eq!(cuboid.surface_area(), 2 * (12 + 4 + 3));
+        assert_eq!(
+            cuboid.intersection(&Cuboid::new([3, 4, 0], [9, 9, 9])),
+            Some(Cuboid::point([3, 4, 2]))
+        );
+        assert_eq!(cuboid.intersection(&Cuboid::point([4, 4, 2])), None);
+        assert_eq!(
+            cuboid.translate([0, 0, -1]),
+            Cuboid::new([1, 1, 1], [3, 4, 1])
+        );
+    }
+}
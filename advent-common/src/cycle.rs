@@ -0,0 +1,86 @@
+use std::{collections::HashMap, hash::Hash};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cycle<T> {
+    pub start: usize,
+    pub length: usize,
+    values: Vec<T>,
+}
+
+impl<T> Cycle<T> {
+    fn locate(&self, step: usize) -> (usize, usize) {
+        if step < self.values.len() {
+            (step, 0)
+        } else {
+            let offset = step - self.start;
+            (self.start + offset % self.length, offset / self.length)
+        }
+    }
+
+    pub fn at(&self, step: usize) -> &T {
+        &self.values[self.locate(step).0]
+    }
+}
+
+impl Cycle<i64> {
+    pub fn extrapolate(&self, step: usize) -> i64 {
+        let (index, periods) = self.locate(step);
+        let drift = self.values[self.start + self.length] - self.values[self.start];
+        self.values[index] + periods as i64 * drift
+    }
+}
+
+pub fn find_cycle<S, K, T>(
+    mut state: S,
+    mut step: impl FnMut(S) -> S,
+    mut key: impl FnMut(&S) -> K,
+    mut observe: impl FnMut(&S) -> T,
+) -> Cycle<T>
+where
+    K: Hash + Eq,
+{
+    let mut seen = HashMap::new();
+    let mut values = vec![];
+    loop {
+        values.push(observe(&state));
+        let index = values.len() - 1;
+        if let Some(start) = seen.insert(key(&state), index) {
+            return Cycle {
+                start,
+                length: index - start,
+                values,
+            };
+        }
+        state = step(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exact_cycle() {
+        let cycle = find_cycle(3, |n| (n * n + 1) % 10, |&n| n, |&n| n);
+        assert_eq!((cycle.start, cycle.length), (1, 6));
+        assert_eq!(
+            (0..15).map(|step| *cycle.at(step)).collect::<Vec<_>>(),
+            [3, 0, 1, 2, 5, 6, 7, 0, 1, 2, 5, 6, 7, 0, 1]
+        );
+        assert_eq!(*cycle.at(100), 5);
+    }
+
+    #[test]
+    fn test_translated_cycle() {
+        let step = |x: i64| if x % 4 == 0 { x + 1 } else { x + 3 };
+        let cycle = find_cycle(0, step, |&x| x % 4, |&x| x);
+        assert_eq!((cycle.start, cycle.length), (0, 2));
+
+        let mut x = 0;
+        for n in 0..20 {
+            assert_eq!(cycle.extrapolate(n), x);
+            x = step(x);
+        }
+        assert_eq!(cycle.extrapolate(1_000_000_000), 2_000_000_000);
+    }
+}
@@ -0,0 +1,284 @@
+use std::ops::RangeInclusive;
+
+pub trait Integer: Copy + Ord + Into<i128> + TryFrom<i128> {}
+
+impl<T: Copy + Ord + Into<i128> + TryFrom<i128>> Integer for T {}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IntervalSet<T> {
+    ranges: Vec<(T, T)>,
+}
+
+impl<T: Integer> IntervalSet<T> {
+    pub fn new() -> Self {
+        IntervalSet { ranges: vec![] }
+    }
+
+    fn normalize(mut ranges: Vec<(T, T)>) -> Self {
+        ranges.retain(|&(start, end)| start <= end);
+        ranges.sort_unstable();
+
+        let mut merged: Vec<(T, T)> = Vec::with_capacity(ranges.len());
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some((_, last)) if start.into() <= (*last).into() + 1 => *last = end.max(*last),
+                _ => merged.push((start, end)),
+            }
+        }
+        IntervalSet { ranges: merged }
+    }
+
+    pub fn insert(&mut self, range: RangeInclusive<T>) {
+        let mut ranges = std::mem::take(&mut self.ranges);
+        ranges.push(range.into_inner());
+        *self = Self::normalize(ranges);
+    }
+
+    pub fn ranges(&self) -> impl Iterator<Item = RangeInclusive<T>> + '_ {
+        self.ranges.iter().map(|&(start, end)| start..=end)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn len(&self) -> u128 {
+        self.ranges
+            .iter()
+            .map(|&(start, end)| (end.into() - start.into() + 1) as u128)
+            .sum()
+    }
+
+    pub fn min(&self) -> Option<T> {
+        self.ranges.first().map(|&(start, _)| start)
+    }
+
+    pub fn max(&self) -> Option<T> {
+        self.ranges.last().map(|&(_, end)| end)
+    }
+
+    fn find(&self, value: T) -> Option<(T, T)> {
+        let index = self.ranges.partition_point(|&(_, end)| end < value);
+        self.ranges
+            .get(index)
+            .copied()
+            .filter(|&(start, _)| start <= value)
+    }
+
+    pub fn contains(&self, value: T) -> bool {
+        self.find(value).is_some()
+    }
+
+    pub fn contains_range(&self, range: RangeInclusive<T>) -> bool {
+        let (start, end) = range.into_inner();
+        start > end || self.find(start).is_some_and(|(_, last)| end <= last)
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self::normalize(self.ranges.iter().chain(&other.ranges).copied().collect())
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut ranges = vec![];
+        let (mut i, mut j) = (0, 0);
+        while let (Some(&(a_start, a_end)), Some(&(b_start, b_end))) =
+            (self.ranges.get(i), other.ranges.get(j))
+        {
+            let (start, end) = (a_start.max(b_start), a_end.min(b_end));
+            if start <= end {
+                ranges.push((start, end));
+            }
+            if a_end < b_end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        IntervalSet { ranges }
+    }
+
+    pub fn complement(&self, bounds: RangeInclusive<T>) -> Self {
+        let (low, high) = bounds.into_inner();
+        let step = |value: T, delta: i128| T::try_from(value.into() + delta).ok();
+
+        let mut ranges = vec![];
+        let mut next = Some(low);
+        for &(start, end) in &self.ranges {
+            let Some(from) = next else { break };
+            if start > high {
+                break;
+            }
+            if from < start {
+                ranges.push((from, step(start, -1).unwrap().min(high)));
+            }
+            next = next.max(step(end, 1));
+            if end >= high {
+                next = None;
+            }
+        }
+        if let Some(from) = next.filter(|&from| from <= high) {
+            ranges.push((from, high));
+        }
+        IntervalSet { ranges }
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        match (self.min(), self.max()) {
+            (Some(low), Some(high)) => self.intersection(&other.complement(low..=high)),
+            _ => Self::new(),
+        }
+    }
+
+    pub fn shift(&self, offset: i128) -> Option<Self> {
+        let shifted = |value: T| T::try_from(value.into() + offset).ok();
+        let ranges = self
+            .ranges
+            .iter()
+            .map(|&(start, end)| Some((shifted(start)?, shifted(end)?)))
+            .collect::<Option<_>>()?;
+        Some(IntervalSet { ranges })
+    }
+
+    pub fn map_offsets(
+        &self,
+        pieces: impl IntoIterator<Item = (RangeInclusive<T>, i128)>,
+    ) -> Option<Self> {
+        let mut remaining = self.clone();
+        let mut mapped = Self::new();
+        for (source, offset) in pieces {
+            let source = Self::from_iter([source]);
+            mapped = mapped.union(&remaining.intersection(&source).shift(offset)?);
+            remaining = remaining.difference(&source);
+        }
+        Some(mapped.union(&remaining))
+    }
+}
+
+impl<T: Integer> FromIterator<RangeInclusive<T>> for IntervalSet<T> {
+    fn from_iter<I: IntoIterator<Item = RangeInclusive<T>>>(iter: I) -> Self {
+        Self::normalize(iter.into_iter().map(RangeInclusive::into_inner).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OFFSET: i64 = 32;
+
+    fn bits(set: &IntervalSet<i64>) -> u128 {
+        set.ranges()
+            .flatten()
+            .fold(0, |bits, value| bits | 1 << (value + OFFSET))
+    }
+
+    fn span(range: RangeInclusive<i64>) -> u128 {
+        range.fold(0, |bits, value| bits | 1 << (value + OFFSET))
+    }
+
+    fn is_canonical(set: &IntervalSet<i64>) -> bool {
+        set.ranges.iter().all(|(start, end)| start <= end)
+            && set.ranges.windows(2).all(|pair| pair[0].1 + 1 < pair[1].0)
+    }
+
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self, range: i64) -> i64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % range as u64) as i64
+        }
+
+        fn range(&mut self) -> RangeInclusive<i64> {
+            let start = self.next(64);
+            start..=start + self.next(12) - 2
+        }
+
+        fn set(&mut self) -> IntervalSet<i64> {
+            let count = self.next(6);
+            (0..count).map(|_| self.range()).collect()
+        }
+    }
+
+    #[test]
+    fn test_matches_bitset() {
+        let mut rng = Rng(0x9e3779b97f4a7c15);
+        for _ in 0..2000 {
+            let (a, b) = (rng.set(), rng.set());
+            let bounds = rng.range();
+            let value = rng.next(80) - 8;
+            let (a_bits, b_bits) = (bits(&a), bits(&b));
+
+            for result in [
+                a.union(&b),
+                a.intersection(&b),
+                a.difference(&b),
+                a.complement(bounds.clone()),
+            ] {
+                assert!(is_canonical(&result), "{result:?}");
+            }
+            assert_eq!(bits(&a.union(&b)), a_bits | b_bits);
+            assert_eq!(bits(&a.intersection(&b)), a_bits & b_bits);
+            assert_eq!(bits(&a.difference(&b)), a_bits & !b_bits);
+            assert_eq!(
+                bits(&a.complement(bounds.clone())),
+                !a_bits & span(bounds.clone())
+            );
+            assert_eq!(a.len(), a_bits.count_ones() as u128);
+            assert_eq!(a.contains(value), a_bits & span(value..=value) != 0);
+            assert_eq!(
+                a.contains_range(bounds.clone()),
+                a_bits & span(bounds.clone()) == span(bounds)
+            );
+
+            let mut inserted = a.clone();
+            for range in b.ranges() {
+                inserted.insert(range);
+            }
+            assert_eq!(inserted, a.union(&b));
+        }
+    }
+
+    #[test]
+    fn test_map_offsets_matches_bitset() {
+        let mut rng = Rng(0x2545f4914f6cdd1d);
+        for _ in 0..2000 {
+            let set = rng.set();
+            let pieces = (0..rng.next(4))
+                .map(|_| (rng.range(), (rng.next(33) - 16) as i128))
+                .collect::<Vec<_>>();
+
+            let mut expected = 0;
+            for value in set.ranges().flatten() {
+                let offset = pieces
+                    .iter()
+                    .find(|(source, _)| source.contains(&value))
+                    .map_or(0, |&(_, offset)| offset as i64);
+                expected |= span(value + offset..=value + offset);
+            }
+
+            let mapped = set.map_offsets(pieces.clone()).unwrap();
+            assert!(is_canonical(&mapped), "{mapped:?}");
+            assert_eq!(bits(&mapped), expected, "{set:?} {pieces:?}");
+        }
+    }
+
+    #[test]
+    fn test_integer_bounds() {
+        let blocked = IntervalSet::from_iter([0..=5, 4..=7, u32::MAX - 1..=u32::MAX]);
+        let allowed = blocked.complement(0..=u32::MAX);
+        assert_eq!(allowed.ranges().collect::<Vec<_>>(), [8..=u32::MAX - 2]);
+        assert_eq!(allowed.len() + blocked.len(), 1 << 32);
+        assert_eq!(
+            IntervalSet::<u32>::new().complement(0..=u32::MAX).len(),
+            1 << 32
+        );
+
+        let set = IntervalSet::from_iter([250u8..=255]);
+        assert_eq!(set.shift(5), None);
+        assert_eq!(set.map_offsets([(250..=250, -250)]).unwrap().min(), Some(0));
+        assert_eq!(set.map_offsets([(255..=255, 1)]), None);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod cuboid;
+pub mod cycle;
+pub mod interval;
+pub mod modular;
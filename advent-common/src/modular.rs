@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+
+pub fn extended_gcd(a: i64, b: i64) -> (i64, i64, i64) {
+    let (mut old_r, mut r) = (a, b);
+    let (mut old_s, mut s) = (1, 0);
+    let (mut old_t, mut t) = (0, 1);
+
+    while r != 0 {
+        let quotient = old_r / r;
+        (old_r, r) = (r, old_r - quotient * r);
+        (old_s, s) = (s, old_s - quotient * s);
+        (old_t, t) = (t, old_t - quotient * t);
+    }
+
+    if old_r < 0 {
+        (-old_r, -old_s, -old_t)
+    } else {
+        (old_r, old_s, old_t)
+    }
+}
+
+pub fn gcd(a: i64, b: i64) -> i64 {
+    extended_gcd(a, b).0
+}
+
+pub fn lcm(a: i64, b: i64) -> i64 {
+    if a == 0 || b == 0 {
+        0
+    } else {
+        (a / gcd(a, b) * b).abs()
+    }
+}
+
+pub fn mul_mod(a: i64, b: i64, m: i64) -> i64 {
+    (a as i128 * b as i128).rem_euclid(m as i128) as i64
+}
+
+pub fn pow_mod(base: i64, mut exp: u64, m: i64) -> i64 {
+    let mut result = 1 % m;
+    let mut base = base.rem_euclid(m);
+
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = mul_mod(result, base, m);
+        }
+        base = mul_mod(base, base, m);
+        exp >>= 1;
+    }
+
+    result
+}
+
+pub fn mod_inverse(a: i64, m: i64) -> Option<i64> {
+    let (g, x, _) = extended_gcd(a.rem_euclid(m), m);
+    (g == 1).then(|| x.rem_euclid(m))
+}
+
+pub fn crt(congruences: &[(i64, i64)]) -> Option<(i64, i64)> {
+    congruences
+        .iter()
+        .try_fold((0, 1), |(residue, modulus), &(r, m)| {
+            let (g, p, _) = extended_gcd(modulus, m);
+            let difference = r - residue;
+            if difference % g != 0 {
+                return None;
+            }
+
+            let step = mul_mod(difference / g, p, m / g);
+            let combined = modulus.checked_mul(m / g)?;
+            let residue = (residue as i128 + modulus as i128 * step as i128)
+                .rem_euclid(combined as i128) as i64;
+            Some((residue, combined))
+        })
+}
+
+pub fn discrete_log(base: i64, target: i64, m: i64) -> Option<u64> {
+    if m <= 0 {
+        return None;
+    }
+    let steps = m.isqrt() + 1;
+
+    let mut baby_steps = HashMap::new();
+    let mut value = 1 % m;
+    for j in 0..steps {
+        baby_steps.entry(value).or_insert(j);
+        value = mul_mod(value, base, m);
+    }
+
+    let target = target.rem_euclid(m);
+    if let Some(&j) = baby_steps.get(&target) {
+        return Some(j as u64);
+    }
+
+    let giant_step = pow_mod(mod_inverse(base, m)?, steps as u64, m);
+    let mut value = target;
+    for i in 0..steps {
+        if let Some(&j) = baby_steps.get(&value) {
+            return Some((i * steps + j) as u64);
+        }
+        value = mul_mod(value, giant_step, m);
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extended_gcd() {
+        for (a, b) in [(240, 46), (46, 240), (-12, 18), (17, 5), (0, 9), (9, 0)] {
+            let (g, x, y) = extended_gcd(a, b);
+            assert_eq!(a * x + b * y, g);
+            assert!(g >= 0);
+        }
+        assert_eq!(gcd(240, 46), 2);
+        assert_eq!(lcm(4, 6), 12);
+        assert_eq!(lcm(-4, 6), 12);
+    }
+
+    #[test]
+    fn test_modular_arithmetic() {
+        assert_eq!(pow_mod(3, 4, 7), 4);
+        assert_eq!(pow_mod(2, 10, 1000), 24);
+        assert_eq!(pow_mod(-2, 3, 7), 6);
+        assert_eq!(pow_mod(5, 0, 1), 0);
+
+        assert_eq!(mod_inverse(3, 7), Some(5));
+        assert_eq!(mod_inverse(7, 11), Some(8));
+        assert_eq!(mod_inverse(-3, 7), Some(2));
+        assert_eq!(mod_inverse(2, 4), None);
+
+        assert_eq!(mul_mod(999999999999, 999999999999, 1000000007), 49014001);
+        assert_eq!(
+            mul_mod(i64::MAX - 1, i64::MAX - 2, i64::MAX),
+            2,
+            "products beyond i64 must not overflow"
+        );
+    }
+
+    #[test]
+    fn test_crt() {
+        assert_eq!(crt(&[]), Some((0, 1)));
+        assert_eq!(crt(&[(2, 3), (3, 5), (2, 7)]), Some((23, 105)));
+        assert_eq!(crt(&[(2, 4), (4, 6)]), Some((10, 12)));
+        assert_eq!(crt(&[(-1, 4), (5, 6)]), Some((11, 12)));
+        assert_eq!(crt(&[(1, 4), (2, 6)]), None);
+        assert_eq!(
+            crt(&[(0, 7), (12, 13), (55, 59), (25, 31), (12, 19)]),
+            Some((1068781, 3162341))
+        );
+    }
+
+    #[test]
+    fn test_discrete_log() {
+        assert_eq!(discrete_log(7, 5764801, 20201227), Some(8));
+        assert_eq!(discrete_log(7, 17807724, 20201227), Some(11));
+        assert_eq!(discrete_log(3, 1, 7), Some(0));
+        assert_eq!(discrete_log(2, 3, 7), None);
+        assert_eq!(discrete_log(2, 3, 8), None);
+        assert_eq!(discrete_log(2, 1, 8), Some(0));
+        assert_eq!(discrete_log(2, 4, 8), Some(2));
+        assert_eq!(discrete_log(6, -4, 10), Some(1));
+        assert_eq!(discrete_log(3, 1, 0), None);
+        assert_eq!(discrete_log(3, 1, -7), None);
+
+        for target in 1..101 {
+            let exponent = discrete_log(3, target, 101).unwrap();
+            assert_eq!(pow_mod(3, exponent, 101), target);
+            assert!((0..exponent).all(|smaller| pow_mod(3, smaller, 101) != target));
+        }
+    }
+}